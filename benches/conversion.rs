@@ -0,0 +1,120 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use lab_parser::anim::bone::AnimDataBone;
+#[cfg(feature = "mmap")]
+use lab_parser::anim::reader::open_lab_file;
+use std::fs;
+use std::io::Cursor;
+#[cfg(feature = "mmap")]
+use std::path::Path;
+
+/// the bundled fixtures of each key type, the matrix ones hold the first 60 frames of the quaternion one
+const FIXTURES: [(&str, &str); 3] = [
+    ("quat", "src/tests/anim-quat.lab"),
+    ("mat43", "src/tests/anim-mat43.lab"),
+    ("mat44", "src/tests/anim-mat44.lab"),
+];
+
+/// reading the file and computing the matrices, without writing anything
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for (key_type, path) in FIXTURES.iter() {
+        let bytes = fs::read(path).unwrap();
+        group.bench_function(*key_type, |b| {
+            b.iter(|| {
+                let mut anim_data = AnimDataBone::new();
+                anim_data.load(&mut Cursor::new(black_box(&bytes))).unwrap();
+                anim_data
+            })
+        });
+    }
+    group.finish();
+}
+
+/// the whole .lab to .dae conversion, into memory so disk speed doesn't count
+fn export(c: &mut Criterion) {
+    let mut group = c.benchmark_group("export");
+    for (key_type, path) in FIXTURES.iter() {
+        let bytes = fs::read(path).unwrap();
+        group.bench_function(*key_type, |b| {
+            b.iter(|| {
+                let mut anim_data = AnimDataBone::new();
+                anim_data.load(&mut Cursor::new(black_box(&bytes))).unwrap();
+
+                let mut xml_content = Vec::new();
+                anim_data.write_collada(&mut xml_content).unwrap();
+                xml_content.len()
+            })
+        });
+    }
+    group.finish();
+}
+
+/// opening the file and loading it, from a copy read into memory and, with the `mmap` feature, from a mapping
+/// of the file. run with `--features mmap` to compare the two
+fn open(c: &mut Criterion) {
+    let mut group = c.benchmark_group("open");
+    for (key_type, path) in FIXTURES.iter() {
+        group.bench_function(format!("{}/owned", key_type), |b| {
+            b.iter(|| {
+                let mut anim_data = AnimDataBone::new();
+                anim_data.load(&mut Cursor::new(fs::read(black_box(path)).unwrap())).unwrap();
+                anim_data
+            })
+        });
+        #[cfg(feature = "mmap")]
+        group.bench_function(format!("{}/mapped", key_type), |b| {
+            b.iter(|| {
+                let mut anim_data = AnimDataBone::new();
+                anim_data.load(&mut open_lab_file(Path::new(black_box(path))).unwrap()).unwrap();
+                anim_data
+            })
+        });
+    }
+    group.finish();
+}
+
+/// looking up every bone by name, its children and its ancestors, through the index built when the file is
+/// loaded (`cached`) and by scanning the bones for every query as the loader did before (`scan`)
+fn queries(c: &mut Criterion) {
+    let mut anim_data = AnimDataBone::new();
+    anim_data.load(&mut Cursor::new(fs::read(FIXTURES[0].1).unwrap())).unwrap();
+    let bones = 0..anim_data.get_num_bones();
+    let names: Vec<String> = bones.clone().map(|bone| anim_data.get_bone_name(bone)).collect();
+
+    let mut group = c.benchmark_group("queries");
+    group.bench_function("cached", |b| {
+        b.iter(|| {
+            let mut count = 0;
+            for (bone, name) in names.iter().enumerate() {
+                count += anim_data.find_bone(black_box(name)).unwrap();
+                count += anim_data.get_bone_children(bone).len();
+                count += anim_data.ancestors(bone).len();
+            }
+            count
+        })
+    });
+    group.bench_function("scan", |b| {
+        let find_bone = |name: &str| bones.clone().find(|&bone| anim_data.get_bone_name(bone) == name);
+        let find_parent = |bone: usize| {
+            let parent_id = anim_data.get_bone_parent_id(bone);
+            bones.clone().find(|&other| anim_data.get_bone_id(other) == parent_id)
+        };
+        b.iter(|| {
+            let mut count = 0;
+            for (bone, name) in names.iter().enumerate() {
+                count += find_bone(black_box(name)).unwrap();
+                count += bones.clone().filter(|&other| find_parent(other) == Some(bone)).count();
+                let mut ancestor = Some(bone);
+                while let Some(ancestor_index) = ancestor {
+                    count += 1;
+                    ancestor = find_parent(ancestor_index);
+                }
+            }
+            count
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse, export, open, queries);
+criterion_main!(benches);
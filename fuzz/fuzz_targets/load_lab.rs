@@ -0,0 +1,10 @@
+#![no_main]
+use lab_parser::anim::bone::AnimDataBone;
+use libfuzzer_sys::fuzz_target;
+use std::io::Cursor;
+
+fuzz_target!(|data: &[u8]| {
+    // malformed files must come back as an error, any panic is a bug in the parser
+    let mut anim_data = AnimDataBone::new();
+    let _ = anim_data.load_from_file(&mut Cursor::new(data));
+});
@@ -1,41 +1,152 @@
-use byteorder::{LittleEndian, ReadBytesExt};
-use cgmath::{Matrix4, Quaternion, SquareMatrix, Transform, Vector3};
+use bincode::Options as _;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use cgmath::{InnerSpace, Matrix4, Quaternion, SquareMatrix, Vector3};
 use chrono::*;
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io::prelude::*;
 use std::io::{Seek, SeekFrom};
 use std::path::Path;
-use std::{fs::File, io::Read, mem::size_of, u32};
+use std::str::{self, Utf8Error};
+use std::{io, io::Read, mem::size_of, u32};
 use xmlwriter::*;
 use collada::{document::ColladaDocument};
+use encoding_rs::Encoding;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 
-use crate::main;
+use super::clips::AnimationClips;
+use super::d3d::{decompose, file_encoding, get_translation, lwMatrix43, lwMatrix44, MAT43_BYTES, MAT44_BYTES};
+use super::error::LabError;
+use super::euler::{to_euler_angles, unwrap_euler_angles, EulerOrder};
+use super::options::{DummySpace, ExportOptions, DEFAULT_EPSILON};
+use super::skin::SkinWeights;
+use super::validation::{self, ValidationReport};
 
-use super::d3d::{lwMatrix43, lwMatrix44};
+/// playback rate of the animations in the game
+pub const FRAMES_PER_SECOND: f32 = 25.0;
 
-#[derive(Debug, PartialEq)]
+/// size of a bone in the file: its name, its id and its parent's id
+const BONE_BYTES: u64 = 64 + 8;
+
+/// size of a dummy in the file: its id, its parent bone's id and its matrix
+const DUMMY_BYTES: u64 = 8 + MAT44_BYTES as u64;
+
+/// declaration written at the start of every collada file
+pub const XML_PROLOG: &str = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n";
+
+/// names of the axes in the sids of the rotations of an euler export
+const EULER_AXIS_NAMES: [&str; 3] = ["X", "Y", "Z"];
+
+/// sid of the matrix of a node, the target of a joint's animation channel
+const MATRIX_SID: &str = "transform";
+
+/// sid of the translation of a joint node in an euler export, the rotations are `rotation{axis}`
+const LOCATION_SID: &str = "location";
+
+/// version of the .lab files this library creates, the one used by the game's own files
+const LAB_VERSION: u32 = 0x1005;
+
+/// bytes at the start of a cache written by `write_cache`
+const CACHE_MAGIC: [u8; 4] = *b"LABC";
+
+/// version of the cache format, to be bumped whenever `AnimCache` or what `prepare` computes changes so older
+/// caches are loaded again from their source
+const CACHE_VERSION: u32 = 3;
+
+/// the key type of the header. any value but the three known ones is read as `BoneKeyTypeInvalid`, whose
+/// keys aren't read. compressed keys, e.g. quaternions quantized to three int16s with the largest component
+/// dropped, would need their own value here, but no sample file stores them so neither the value nor the
+/// layout is known
+#[derive(Debug, Clone, PartialEq)]
 enum BoneInfoKeyType {
     BoneKeyTypeMat43 = 1,
     BoneKeyTypeMat44,
     BoneKeyTypeQuaternion,
     BoneKeyTypeInvalid,
 }
-#[derive(Debug)]
+/// the kind of key data stored for the bones of an animation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyKind {
+    Mat43,
+    Mat44,
+    Quaternion,
+    Invalid,
+}
+
+impl KeyKind {
+    /// the short name used in summaries, e.g. `quat`
+    pub fn name(&self) -> &'static str {
+        match self {
+            KeyKind::Mat43 => "mat43",
+            KeyKind::Mat44 => "mat44",
+            KeyKind::Quaternion => "quat",
+            KeyKind::Invalid => "invalid",
+        }
+    }
+}
+
+/// a borrowed view over the key data of a single bone, matching the key kind of the file
+#[derive(Debug, Clone, Copy)]
+pub enum BoneKeys<'k> {
+    Mat43(&'k [lwMatrix43]),
+    Mat44(&'k [lwMatrix44]),
+    Quaternion {
+        positions: &'k [Vector3<f32>],
+        rotations: &'k [Quaternion<f32>],
+    },
+    None,
+}
+
+/// a dummy (attachment point) along with the name of the bone it hangs off, `None` if its parent id doesn't
+/// match any bone
+#[derive(Debug, Clone, PartialEq)]
+pub struct DummyView {
+    pub id: u32,
+    pub parent_bone_name: Option<String>,
+    pub matrix: Matrix4<f32>,
+}
+
+/// the counts of a file's header, as read by `probe` without loading the bones or the keys
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabInfo {
+    pub version: u32,
+    pub key_kind: KeyKind,
+    pub bone_count: usize,
+    pub frame_count: usize,
+    pub dummy_count: usize,
+}
+
+/// the handedness of a skeleton's coordinate system, as given by its bind matrices
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Handedness {
+    Right,
+    Left,
+}
+
+/// the 20 byte header at the start of a file: five little-endian u32s holding the version, the bone count,
+/// the frame count, the dummy count and the key type. the bones start right after it, so there is no field
+/// that could hold a scale or a name for the animation: the 64 byte bone names are the only strings in any
+/// sample file, which is why the exported scene and clips get generic names.
+///
+/// the two bytes after the u16 the cli checks against `MIN_VERSION` are the high half of the u32 version,
+/// which is zero in every sample file
+#[derive(Debug, Clone)]
 struct BoneInfoHeader {
+    version: u32,
     bone_num: u32,
     frame_num: u32,
     dummy_num: u32,
     key_type: BoneInfoKeyType,
 }
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct BoneBaseInfo {
     name: [u8; 64],
     id: u32,
     parent_id: u32,
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 struct BoneDummyInfo {
     id: u32,
     parent_bone_id: u32,
@@ -48,37 +159,91 @@ struct BoneKeyInfo {
     mat44_seq: Option<Vec<lwMatrix44>>,
     pos_seq: Option<Vec<Vector3<f32>>>,
     quat_seq: Option<Vec<Quaternion<f32>>>,
+    scale_seq: Option<Vec<Vector3<f32>>>,
+}
+
+/// lookups over the bone hierarchy that are built once after loading, indexed by the bone's position in the file
+#[derive(Debug, Clone, Default)]
+struct BoneIndex {
+    index_by_id: HashMap<u32, usize>,
+    index_by_name: HashMap<String, usize>,
+    parents: Vec<Option<usize>>,
+    children: Vec<Vec<usize>>,
+    depths: Vec<usize>,
+}
+
+/// a loaded animation along with everything computed from it, as written by `write_cache`. the data of the
+/// file is kept in the .lab format, the matrices as the rows of cgmath's arrays
+#[derive(Serialize, Deserialize)]
+struct AnimCache {
+    magic: [u8; 4],
+    version: u32,
+    options: CacheOptions,
+    lab: Vec<u8>,
+    /// the footer of the loaded file, which the lab leaves out when it is a root motion track
+    footer: Vec<u8>,
+    position_matrices: Vec<[[f32; 4]; 4]>,
+    transformation_matrices: Vec<Vec<[[f32; 4]; 4]>>,
+    warnings: Vec<String>,
+}
+
+/// the export options that change what `load` reads, computes or warns about, which a cache is only valid
+/// for. the ones that only change the written output can differ between the cache and its use
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct CacheOptions {
+    center_root: bool,
+    dummy_space: DummySpace,
+    max_frames: Option<usize>,
+    epsilon: Option<f32>,
+    name_encoding: Option<String>,
+    auto_handedness: bool,
+    fix_mirror: bool,
+    keep_unnormalized_quats: bool,
+    interleaved_keys: bool,
+    root_motion_track: bool,
+    root_transform: Option<[[f32; 4]; 4]>,
+    axis_map: Option<[[f32; 4]; 4]>,
+    strict_sizes: bool,
+    ignored_bones: Vec<String>,
+    max_depth: Option<usize>,
 }
 
-#[derive(Debug)]
-pub struct AnimDataBone<'a> {
+/// the animation data of a .lab file. it owns all of its data, so it can be cloned or sent to another thread,
+/// e.g. to export the same animation with different options in parallel
+#[derive(Debug, Clone)]
+pub struct AnimDataBone {
     header: BoneInfoHeader,
     base_seq: Vec<BoneBaseInfo>,
     dummy_seq: HashMap<u32, Vec<BoneDummyInfo>>,
     key_seq: Vec<BoneKeyInfo>,
     invmat_seq: Vec<lwMatrix44>,
-    bone_map: HashMap<u32, RefCell<Joint<'a>>>,
+    footer: Vec<u8>,
+    root_motion: Option<Vec<Vector3<f32>>>,
 
     transformation_matrices: Vec<Vec<Matrix4<f32>>>,
     position_matrices: Vec<Matrix4<f32>>,
-    root_joint: Option<&'a RefCell<Joint<'a>>>,
+    bone_index: BoneIndex,
+
+    options: ExportOptions,
+    skin_weights: Option<SkinWeights>,
+    clips: Option<AnimationClips>,
+    warnings: Vec<String>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 struct DummyObject {
     parent_id: u32,
     position_matrix: Option<Matrix4<f32>>,
     id: u32,
 }
 
-#[derive(Debug, Default)]
-struct Joint<'a> {
-    parent: Option<&'a RefCell<Joint<'a>>>,
-    children: Vec<&'a RefCell<Joint<'a>>>,
-    bone_id: u32,
+/// a node of the joint tree that is built for an export, linked to its parent and children by their index
+/// in the file
+#[derive(Debug, Clone, Default)]
+struct Joint {
+    parent: Option<usize>,
+    children: Vec<usize>,
     bone_name: String,
-    parent_id: u32,
-    transformation_matrix: Option<Matrix4<f32>>,
     position_matrix: Option<Matrix4<f32>>,
     dummies: Vec<DummyObject>,
 }
@@ -86,6 +251,7 @@ struct Joint<'a> {
 impl BoneInfoHeader {
     pub fn new() -> BoneInfoHeader {
         BoneInfoHeader {
+            version: LAB_VERSION,
             bone_num: 0,
             frame_num: 0,
             dummy_num: 0,
@@ -103,16 +269,33 @@ impl BoneBaseInfo {
         }
     }
 
+    /// the bytes of the name, up to the first nul byte
+    pub fn get_name_bytes(&self) -> &[u8] {
+        let length = self
+            .name
+            .iter()
+            .position(|byte| *byte == b'\0')
+            .unwrap_or(self.name.len());
+        &self.name[..length]
+    }
+
+    /// the name decoded as utf-8. bytes that aren't valid utf-8 are replaced rather than failing the whole
+    /// conversion over a single oddly named bone
     pub fn get_name(&self) -> String {
-        let mut name_vec: Vec<u8> = Vec::new();
-        for i in self.name.iter() {
-            if *i == ('\0' as u8) {
-                break;
-            }
+        String::from_utf8_lossy(self.get_name_bytes()).into_owned()
+    }
 
-            name_vec.push(*i);
-        }
-        return String::from_utf8(name_vec).unwrap();
+    /// the name decoded as utf-8, failing on bytes that aren't valid utf-8 instead of replacing them
+    pub fn try_get_name(&self) -> Result<String, Utf8Error> {
+        str::from_utf8(self.get_name_bytes()).map(str::to_string)
+    }
+
+    /// the name decoded with a legacy encoding, e.g. Shift-JIS or windows-1252
+    pub fn get_name_with_encoding(&self, encoding: &'static Encoding) -> String {
+        encoding
+            .decode_without_bom_handling(self.get_name_bytes())
+            .0
+            .into_owned()
     }
 }
 
@@ -138,173 +321,551 @@ impl BoneKeyInfo {
             mat44_seq: None,
             pos_seq: None,
             quat_seq: None,
+            scale_seq: None,
         }
     }
 }
 
-impl<'a> AnimDataBone<'a> {
-    pub fn new() -> AnimDataBone<'a> {
+impl AnimDataBone {
+    pub fn new() -> AnimDataBone {
         AnimDataBone {
             header: BoneInfoHeader::new(),
             base_seq: Vec::new(),
             dummy_seq: HashMap::new(),
             key_seq: Vec::new(),
             invmat_seq: Vec::new(),
+            footer: Vec::new(),
+            root_motion: None,
             position_matrices: Vec::new(),
             transformation_matrices: Vec::new(),
-            bone_map: HashMap::new(),
-            root_joint: None,
+            bone_index: BoneIndex::default(),
+            options: ExportOptions::default(),
+            skin_weights: None,
+            clips: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// vertex weights to export as a collada skin controller along with the skeleton
+    pub fn set_skin_weights(&mut self, skin_weights: SkinWeights) {
+        self.skin_weights = Some(skin_weights);
+    }
+
+    /// named actions to export as collada animation clips. they are checked against the frame count
+    /// when the animation is loaded
+    pub fn set_clips(&mut self, clips: AnimationClips) {
+        self.clips = Some(clips);
+    }
+
+    pub fn with_options(options: ExportOptions) -> AnimDataBone {
+        AnimDataBone {
+            options,
+            ..AnimDataBone::new()
+        }
+    }
+
+    pub fn load_from_file<R: Read + Seek>(&mut self, file: &mut R) -> Result<String, LabError> {
+        self.load(file)?;
+        Ok(self.to_collada())
+    }
+
+    /// writes the xml prolog and the collada document of the loaded data to any sink, e.g. a file, a socket
+    /// or an in-memory buffer
+    pub fn write_collada<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let xml_content = self.to_collada();
+        writer.write_all(XML_PROLOG.as_bytes())?;
+        writer.write_all(xml_content.as_bytes())
+    }
+
+    /// generates the joint tree of the loaded data and writes it in collada format
+    pub fn to_collada(&self) -> String {
+        // generate a joint tree and write all the required data in collada format into a .dae file
+        let (joints, root_index) = self.generate_joint_structure();
+        self.write_collada_data(&joints, root_index)
+    }
+
+    /// writes the xml prolog and a collada document of the skeleton posed at the given frame, with every joint
+    /// holding its local matrix of that frame instead of its rest pose and without any animation
+    pub fn write_pose_collada<W: Write>(&self, writer: &mut W, frame: usize) -> Result<(), LabError> {
+        let xml_content = self.to_pose_collada(frame)?;
+        writer.write_all(XML_PROLOG.as_bytes())?;
+        writer.write_all(xml_content.as_bytes())?;
+        Ok(())
+    }
+
+    /// the collada document of the skeleton posed at the given frame, see `write_pose_collada`
+    pub fn to_pose_collada(&self, frame: usize) -> Result<String, LabError> {
+        if frame >= self.get_num_frames() {
+            return Err(LabError::FrameOutOfRange(frame, self.get_num_frames()));
+        }
+
+        let (mut joints, root_index) = self.generate_joint_structure();
+        for (joint, frames) in joints.iter_mut().zip(self.transformation_matrices.iter()) {
+            joint.position_matrix = Some(frames[frame]);
         }
+
+        let mut writer = self.start_collada_document();
+        self.write_visual_scene_data(&mut writer, &joints, root_index);
+        self.write_scene_element(&mut writer);
+
+        writer.end_element();
+
+        Ok(writer.end_document())
     }
 
-    pub fn load_from_file(&'a mut self, file: &mut File) -> String {
+    /// loads all the animation data from the file and computes the rest pose and per-frame matrices,
+    /// without building the joint tree. the loaded data can still be queried after this call
+    pub fn load<R: Read + Seek>(&mut self, file: &mut R) -> Result<(), LabError> {
         // load all animation related data from the file
-        self.load_header(file);
-        self.load_base_seq(file);
-        self.load_invmat_seq(file);
-        self.load_dummy_seq(file);
-        self.load_key_seq(file);
+        self.load_header(file)?;
+
+        // a file without frames still parses, but every animation source in the output would be empty
+        if self.header.frame_num == 0 && self.header.key_type != BoneInfoKeyType::BoneKeyTypeInvalid {
+            return Err(LabError::NoFrames);
+        }
+        if self.header.bone_num == 0 {
+            return Err(LabError::NoBones);
+        }
+        self.check_header_counts(file)?;
+
+        self.load_base_seq(file)?;
+        self.load_invmat_seq(file)?;
+        self.load_dummy_seq(file)?;
+        self.load_key_seq(file)?;
+        self.load_footer(file)?;
+        if self.options.strict_sizes {
+            self.check_sizes()?;
+        }
+        self.check_hierarchy()?;
+
+        if self.get_exported_animation_frame_count() < self.get_num_frames() {
+            self.warn(format!(
+                "the animation has {} frames, only the first {} are exported",
+                self.get_num_frames(),
+                self.get_exported_animation_frame_count()
+            ));
+        }
+
+        if let Some(clips) = &self.clips {
+            clips.validate(self.get_exported_animation_frame_count())?;
+        }
+
+        self.prepare();
+        self.check_skin_weights()?;
+        if self.options.validate || cfg!(debug_assertions) {
+            self.validate_ids()?;
+        }
+        Ok(())
+    }
+
+    /// checks that the skeleton has a root bone and that the parent of every other bone is in the file, so
+    /// the joint tree can be built. `validate` reports the other problems of a hierarchy, e.g. loops
+    pub fn check_hierarchy(&self) -> Result<(), LabError> {
+        if self.get_root_bone_index().is_none() {
+            return Err(LabError::NoRoot);
+        }
+
+        let ids: HashSet<u32> = self.base_seq.iter().map(|bone| bone.id).collect();
+        match self
+            .base_seq
+            .iter()
+            .position(|bone| bone.parent_id != u32::MAX && !ids.contains(&bone.parent_id))
+        {
+            Some(bone) => Err(LabError::DanglingParent(bone)),
+            None => Ok(()),
+        }
+    }
+
+    /// checks that every bone has an inverse bind matrix and a key for every frame, as the header says. the
+    /// load fills in what a short file is missing otherwise, so this is only run with `strict_sizes`
+    pub fn check_sizes(&self) -> Result<(), LabError> {
+        let bone_num = self.header.bone_num as usize;
+        let frame_num = self.header.frame_num as usize;
+        if self.invmat_seq.len() != bone_num {
+            return Err(LabError::SizeMismatch(format!(
+                "{} inverse bind matrices for {} bones",
+                self.invmat_seq.len(),
+                bone_num
+            )));
+        }
+        if self.key_seq.len() != bone_num {
+            return Err(LabError::SizeMismatch(format!("keys for {} of {} bones", self.key_seq.len(), bone_num)));
+        }
+
+        for (bone, key) in self.key_seq.iter().enumerate() {
+            let sequences = [
+                ("matrix", key.mat43_seq.as_ref().map(Vec::len)),
+                ("matrix", key.mat44_seq.as_ref().map(Vec::len)),
+                ("position", key.pos_seq.as_ref().map(Vec::len)),
+                ("rotation", key.quat_seq.as_ref().map(Vec::len)),
+                ("scale", key.scale_seq.as_ref().map(Vec::len)),
+            ];
+            let expects_keys = self.header.key_type != BoneInfoKeyType::BoneKeyTypeInvalid;
+            if expects_keys && sequences.iter().all(|(_, length)| length.is_none()) {
+                return Err(LabError::SizeMismatch(format!("bone {} has no keys", self.get_bone_name(bone))));
+            }
+            for (name, length) in sequences.iter() {
+                match length {
+                    Some(length) if *length != frame_num => {
+                        return Err(LabError::SizeMismatch(format!(
+                            "bone {} has {} {} keys for {} frames",
+                            self.get_bone_name(bone),
+                            length,
+                            name,
+                            frame_num
+                        )))
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// the warnings raised while loading the animation, e.g. about frames that were dropped or filled in
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// keeps a warning for `warnings`, the caller decides whether and how to show it
+    fn warn(&mut self, message: String) {
+        self.warnings.push(message);
+    }
+
+    /// computes everything that is derived from the animation data once it has been loaded or built
+    fn prepare(&mut self) {
+        self.build_bone_index();
 
         // use loaded data to generate structures that can be consumed by animation programs (blender, maya etc)
         self.generate_position_matrices_at_rest();
         self.generate_transformation_matrices_for_all_frames();
-        // generate a joint tree and write all the required data in collada format into a .dae file
-        let xml_content = self.generate_joint_structure();
-        xml_content
+
+        if !self.options.ignored_bones.is_empty() || self.options.max_depth.is_some() {
+            self.prune_ignored_bones();
+        }
+
+        if self.root_motion.is_some() {
+            self.apply_root_motion();
+        }
+
+        if self.options.fix_mirror {
+            self.fix_mirrored_bones();
+        }
+
+        if self.options.center_root {
+            self.center_root_motion();
+        }
+
+        // mirroring the root mirrors the whole skeleton, before any transform the user asked for
+        if self.options.auto_handedness && self.handedness() == Handedness::Left {
+            self.apply_root_transform(Matrix4::from_nonuniform_scale(1.0, 1.0, -1.0));
+        }
+
+        if let Some(root_transform) = self.options.root_transform {
+            self.apply_root_transform(root_transform);
+        }
+        if let Some(axis_map) = self.options.axis_map {
+            self.apply_root_transform(axis_map.matrix());
+        }
+    }
+
+    /// checks that the skin weights only reference bones of the animation, once the ignored bones are pruned
+    fn check_skin_weights(&self) -> Result<(), LabError> {
+        match self.skin_weights.as_ref().and_then(SkinWeights::max_bone_index) {
+            Some(max_bone_index) if max_bone_index >= self.get_num_bones() => {
+                Err(LabError::SkinWeightsOutOfRange(max_bone_index, self.get_num_bones()))
+            }
+            _ => Ok(()),
+        }
     }
 
-    fn load_header(&mut self, file: &mut File) {
-        file.seek(SeekFrom::Start(4)).unwrap();
+    fn load_header<R: Read + Seek>(&mut self, file: &mut R) -> Result<(), LabError> {
+        file.seek(SeekFrom::Start(0))?;
 
-        self.header.bone_num = file.read_u32::<LittleEndian>().unwrap();
-        self.header.frame_num = file.read_u32::<LittleEndian>().unwrap();
-        self.header.dummy_num = file.read_u32::<LittleEndian>().unwrap();
-        let key_type = file.read_u32::<LittleEndian>().unwrap();
+        self.header.version = file.read_u32::<LittleEndian>()?;
+        self.header.bone_num = file.read_u32::<LittleEndian>()?;
+        self.header.frame_num = file.read_u32::<LittleEndian>()?;
+        self.header.dummy_num = file.read_u32::<LittleEndian>()?;
+        let key_type = file.read_u32::<LittleEndian>()?;
 
         self.header.key_type = match key_type {
             1 => BoneInfoKeyType::BoneKeyTypeMat43,
             2 => BoneInfoKeyType::BoneKeyTypeMat44,
             3 => BoneInfoKeyType::BoneKeyTypeQuaternion,
-            _ => BoneInfoKeyType::BoneKeyTypeInvalid,
+            4 => BoneInfoKeyType::BoneKeyTypeInvalid,
+            _ => {
+                // most likely a newer format, e.g. with compressed keys, which is better reported than exported
+                // as a skeleton that doesn't move
+                self.warn(format!("the file has the unknown key type {}, its keys aren't read", key_type));
+                BoneInfoKeyType::BoneKeyTypeInvalid
+            }
         };
+
+        Ok(())
     }
 
-    fn load_base_seq(&mut self, file: &mut File) {
-        for _ in 0..self.header.bone_num {
-            let mut bone_seq = BoneBaseInfo::new();
-            file.read_exact(&mut bone_seq.name).unwrap();
-            bone_seq.id = file.read_u32::<LittleEndian>().unwrap();
-            bone_seq.parent_id = file.read_u32::<LittleEndian>().unwrap();
+    fn load_base_seq<R: Read + Seek>(&mut self, file: &mut R) -> Result<(), LabError> {
+        for i in 0..self.header.bone_num as usize {
+            // files cut off while downloading usually end in this block, so report which bone is incomplete
+            let mut read_bone = || -> io::Result<BoneBaseInfo> {
+                let mut bone_seq = BoneBaseInfo::new();
+                file.read_exact(&mut bone_seq.name)?;
+                bone_seq.id = file.read_u32::<LittleEndian>()?;
+                bone_seq.parent_id = file.read_u32::<LittleEndian>()?;
+                Ok(bone_seq)
+            };
 
-            self.base_seq.push(bone_seq);
+            match read_bone() {
+                Ok(bone_seq) => self.base_seq.push(bone_seq),
+                Err(ref error) if error.kind() == io::ErrorKind::UnexpectedEof => {
+                    return Err(LabError::TruncatedBone(i))
+                }
+                Err(error) => return Err(error.into()),
+            }
         }
+
+        Ok(())
     }
 
-    fn load_invmat_seq(&mut self, file: &mut File) {
-        for _ in 0..self.header.bone_num {
-            let mut bytes: [u8; 64] = [0; 64];
-            file.read_exact(&mut bytes).unwrap();
+    /// a file that ends inside this block gets the identity for the inverse bind matrices it is missing, so
+    /// every bone still has one and the export goes on with the bones bound at the origin
+    fn load_invmat_seq<R: Read + Seek>(&mut self, file: &mut R) -> Result<(), LabError> {
+        for i in 0..self.header.bone_num as usize {
+            let mut bytes = [0; MAT44_BYTES];
+            match file.read_exact(&mut bytes) {
+                Ok(()) => {}
+                Err(ref error) if error.kind() == io::ErrorKind::UnexpectedEof && self.options.strict_sizes => {
+                    return Err(LabError::SizeMismatch(format!(
+                        "it ends after {} of the {} inverse bind matrices",
+                        i, self.header.bone_num
+                    )));
+                }
+                Err(ref error) if error.kind() == io::ErrorKind::UnexpectedEof => {
+                    self.warn(format!(
+                        "the file ends in the inverse bind matrix of bone {}, the {} missing ones are set to the identity",
+                        self.get_bone_name(i),
+                        self.header.bone_num as usize - i
+                    ));
+                    let identity = lwMatrix44 { matrix: Matrix4::identity() };
+                    self.invmat_seq.resize(self.header.bone_num as usize, identity);
+                    break;
+                }
+                Err(error) => return Err(error.into()),
+            }
 
-            let decoded: [[f32; 4]; 4] = bincode::deserialize(&bytes).unwrap();
+            let decoded: [[f32; 4]; 4] = file_encoding().deserialize(&bytes).unwrap();
             let invmat = lwMatrix44::new(decoded);
 
             self.invmat_seq.push(invmat);
         }
+
+        Ok(())
     }
 
-    fn load_dummy_seq(&mut self, file: &mut File) -> Option<()> {
+    fn load_dummy_seq<R: Read + Seek>(&mut self, file: &mut R) -> Result<(), LabError> {
+        if self.header.dummy_num == 0 {
+            if let Some(dummy_num) = self.read_inline_dummy_count(file)? {
+                self.header.dummy_num = dummy_num;
+            }
+        }
+
         for _ in 0..self.header.dummy_num {
-            let mut bytes: [u8; 64] = [0; 64];
-            let id = file.read_u32::<LittleEndian>().unwrap();
-            let parent_bone_id = file.read_u32::<LittleEndian>().unwrap();
-            file.read_exact(&mut bytes).unwrap();
+            let mut bytes = [0; MAT44_BYTES];
+            let id = file.read_u32::<LittleEndian>()?;
+            let parent_bone_id = file.read_u32::<LittleEndian>()?;
+            file.read_exact(&mut bytes)?;
 
-            let decoded: [[f32; 4]; 4] = bincode::deserialize(&bytes).unwrap();
+            let decoded: [[f32; 4]; 4] = file_encoding().deserialize(&bytes).unwrap();
             let dummy_info = BoneDummyInfo {
                 id,
                 parent_bone_id,
                 mat: lwMatrix44::new(decoded),
             };
 
-            if self.dummy_seq.contains_key(&parent_bone_id) {
-                let dummy = self.dummy_seq.get_mut(&parent_bone_id)?;
-                dummy.push(dummy_info)
-            } else {
-                self.dummy_seq.insert(parent_bone_id, vec![dummy_info]);
-            }
+            self.dummy_seq.entry(parent_bone_id).or_insert_with(Vec::new).push(dummy_info);
+        }
+
+        Ok(())
+    }
+
+    /// some files leave `dummy_num` in the header at zero and store the count as a u32 right before the dummy
+    /// block instead. they share their version with the regular files, so they are told apart by their size:
+    /// the rest of the file holds exactly the inline count, that many dummies and the key data.
+    /// the reader is left after the count if there is one, and where it was otherwise
+    fn read_inline_dummy_count<R: Read + Seek>(&mut self, file: &mut R) -> Result<Option<u32>, LabError> {
+        let position = file.seek(SeekFrom::Current(0))?;
+        let remaining = file.seek(SeekFrom::End(0))? - position;
+        file.seek(SeekFrom::Start(position))?;
+
+        let key_block_size = self.get_key_block_size();
+        if remaining < 4 + key_block_size {
+            return Ok(None);
+        }
+
+        let dummy_num = file.read_u32::<LittleEndian>()?;
+        if remaining == 4 + dummy_num as u64 * DUMMY_BYTES + key_block_size {
+            return Ok(Some(dummy_num));
+        }
+
+        file.seek(SeekFrom::Start(position))?;
+        Ok(None)
+    }
+
+    /// checks the frame count of the header against the size of the file before the keys and the matrices of
+    /// every frame are allocated, so a corrupt header fails the load instead of running out of memory. the
+    /// bones and dummies are read one by one, a count larger than the file ends in `TruncatedBone` or an io
+    /// error. the keys of a single bone must fit, as a file that ends in the key data is still read, and a
+    /// file without keys is given a byte per frame
+    fn check_header_counts<R: Read + Seek>(&self, file: &mut R) -> Result<(), LabError> {
+        let position = file.stream_position()?;
+        let remaining = file.seek(SeekFrom::End(0))? - position;
+        file.seek(SeekFrom::Start(position))?;
+
+        // a file that ends in the bones fails while they are read, with the bone it ends in
+        let remaining = match remaining.checked_sub(self.header.bone_num as u64 * BONE_BYTES) {
+            Some(remaining) => remaining,
+            None => return Ok(()),
+        };
+        if self.header.frame_num as u64 * self.get_key_frame_size().max(1) > remaining {
+            return Err(LabError::SizeMismatch(format!(
+                "the keys of its {} frames don't fit in the {} bytes after the bones",
+                self.header.frame_num, remaining
+            )));
         }
-        Some(())
+
+        Ok(())
+    }
+
+    /// size in bytes of the key of a bone at one frame
+    fn get_key_frame_size(&self) -> u64 {
+        let frame_size = match self.header.key_type {
+            BoneInfoKeyType::BoneKeyTypeMat43 => MAT43_BYTES,
+            BoneInfoKeyType::BoneKeyTypeMat44 => MAT44_BYTES,
+            BoneInfoKeyType::BoneKeyTypeQuaternion => size_of::<Vector3<f32>>() + size_of::<Quaternion<f32>>(),
+            BoneInfoKeyType::BoneKeyTypeInvalid => 0,
+        };
+        frame_size as u64
+    }
+
+    /// size in bytes of the key data of all the bones
+    fn get_key_block_size(&self) -> u64 {
+        self.header.bone_num as u64 * self.header.frame_num as u64 * self.get_key_frame_size()
     }
 
-    fn load_key_seq(&mut self, file: &mut File) {
+    /// size in bytes of the per-frame scales of all the bones, in the quaternion files that have them
+    fn get_scale_block_size(&self) -> u64 {
+        self.header.bone_num as u64 * self.header.frame_num as u64 * size_of::<Vector3<f32>>() as u64
+    }
+
+    fn load_key_seq<R: Read + Seek>(&mut self, file: &mut R) -> Result<(), LabError> {
         let mut keys = vec![BoneKeyInfo::new(); self.header.bone_num as usize];
 
         match self.header.key_type {
             BoneInfoKeyType::BoneKeyTypeMat43 => {
-                for i in 0..self.header.bone_num {
+                'bones: for i in 0..self.header.bone_num {
                     let key = &mut keys[i as usize];
-                    let mut mat43_seq_vec = vec![
-                        lwMatrix43 {
-                            matrix: Default::default()
-                        };
-                        self.header.frame_num as usize
-                    ];
+                    let mut mat43_seq_vec = Vec::with_capacity(self.header.frame_num as usize);
 
-                    let mut mat43_seq_bytes: Vec<u8> = vec![0; self.header.frame_num as usize];
-                    file.read_exact(&mut mat43_seq_bytes).unwrap();
+                    for _ in 0..(self.header.frame_num as usize) {
+                        let mut mat43_seq_bytes = [0; MAT43_BYTES];
+                        if !read_key_bytes(file, &mut mat43_seq_bytes)? {
+                            key.mat43_seq = Some(mat43_seq_vec);
+                            break 'bones;
+                        }
+                        mat43_seq_vec.push(file_encoding().deserialize(&mat43_seq_bytes).unwrap());
+                    }
 
-                    mat43_seq_vec[0] = bincode::deserialize(&mat43_seq_bytes).unwrap();
                     key.mat43_seq = Some(mat43_seq_vec);
                 }
             }
 
             BoneInfoKeyType::BoneKeyTypeMat44 => {
-                for i in 0..self.header.bone_num {
+                'bones: for i in 0..self.header.bone_num {
                     let key = &mut keys[i as usize];
-                    let mut mat44_seq_vec =
-                        vec![lwMatrix44::default(); self.header.frame_num as usize];
+                    let mut mat44_seq_vec = Vec::with_capacity(self.header.frame_num as usize);
 
-                    let mut mat44_seq_bytes: Vec<u8> = vec![0; self.header.frame_num as usize];
-                    file.read_exact(&mut mat44_seq_bytes).unwrap();
+                    for _ in 0..(self.header.frame_num as usize) {
+                        let mut mat44_seq_bytes = [0; MAT44_BYTES];
+                        if !read_key_bytes(file, &mut mat44_seq_bytes)? {
+                            key.mat44_seq = Some(mat44_seq_vec);
+                            break 'bones;
+                        }
+                        let decoded: [[f32; 4]; 4] = file_encoding().deserialize(&mat44_seq_bytes).unwrap();
+                        mat44_seq_vec.push(lwMatrix44::new(decoded));
+                    }
 
-                    let decoded: [[f32; 4]; 4] = bincode::deserialize(&mat44_seq_bytes).unwrap();
-                    mat44_seq_vec[0] = lwMatrix44::new(decoded);
                     key.mat44_seq = Some(mat44_seq_vec);
                 }
             }
 
             BoneInfoKeyType::BoneKeyTypeQuaternion => {
-                for i in 0..self.header.bone_num {
+                // some files store a scale per frame after the rotations of every bone. they share their version
+                // with the files without scale, so they are told apart by the size of the key data
+                let position = file.seek(SeekFrom::Current(0))?;
+                let remaining = file.seek(SeekFrom::End(0))? - position;
+                file.seek(SeekFrom::Start(position))?;
+                let has_scale = remaining > 0
+                    && remaining == self.get_key_block_size() + self.get_scale_block_size();
+
+                // a file that ends inside the key data keeps the keys read so far, the missing frames are
+                // filled in when the matrices are generated
+                'bones: for i in 0..self.header.bone_num {
                     let key = &mut keys[i as usize];
-                    let mut pos_seq_vec =
-                        vec![Vector3::new(0.0, 0.0, 0.0); self.header.frame_num as usize];
-
-                    for j in 0..(self.header.frame_num as usize) {
-                        let mut pos_seq_bytes: Vec<u8> = vec![0; size_of::<Vector3<f32>>()];
-                        file.read_exact(&mut pos_seq_bytes).unwrap();
-                        let deserialized: [f32; 3] = bincode::deserialize(&pos_seq_bytes).unwrap();
-                        pos_seq_vec[j] =
-                            Vector3::new(deserialized[0], deserialized[1], deserialized[2]);
+                    let mut pos_seq_vec = Vec::with_capacity(self.header.frame_num as usize);
+                    let mut quat_seq_vec = Vec::with_capacity(self.header.frame_num as usize);
+
+                    if self.options.interleaved_keys {
+                        for _ in 0..(self.header.frame_num as usize) {
+                            let key_frame = match read_key_position(file)? {
+                                Some(position) => read_key_rotation(file)?.map(|rotation| (position, rotation)),
+                                None => None,
+                            };
+                            match key_frame {
+                                Some((position, rotation)) => {
+                                    pos_seq_vec.push(position);
+                                    quat_seq_vec.push(rotation);
+                                }
+                                None => {
+                                    key.pos_seq = Some(pos_seq_vec);
+                                    key.quat_seq = Some(quat_seq_vec);
+                                    break 'bones;
+                                }
+                            }
+                        }
+                    } else {
+                        for _ in 0..(self.header.frame_num as usize) {
+                            match read_key_position(file)? {
+                                Some(position) => pos_seq_vec.push(position),
+                                None => {
+                                    key.pos_seq = Some(pos_seq_vec);
+                                    key.quat_seq = Some(quat_seq_vec);
+                                    break 'bones;
+                                }
+                            }
+                        }
+
+                        for _ in 0..(self.header.frame_num as usize) {
+                            match read_key_rotation(file)? {
+                                Some(rotation) => quat_seq_vec.push(rotation),
+                                None => break,
+                            }
+                        }
                     }
 
                     key.pos_seq = Some(pos_seq_vec);
+                    key.quat_seq = Some(quat_seq_vec);
 
-                    let mut quat_seq_vec =
-                        vec![Quaternion::new(0.0, 0.0, 0.0, 0.0); self.header.frame_num as usize];
-
-                    for j in 0..(self.header.frame_num as usize) {
-                        let mut quat_seq_bytes: Vec<u8> = vec![0; size_of::<Quaternion<f32>>()];
-                        file.read_exact(&mut quat_seq_bytes).unwrap();
-                        let deserialized: [f32; 4] = bincode::deserialize(&quat_seq_bytes).unwrap();
-                        quat_seq_vec[j] = Quaternion::new(
-                            deserialized[3],
-                            deserialized[0],
-                            deserialized[1],
-                            deserialized[2],
-                        );
+                    if has_scale {
+                        let mut scale_seq_vec = Vec::with_capacity(self.header.frame_num as usize);
+                        for _ in 0..(self.header.frame_num as usize) {
+                            let mut scale_seq_bytes: Vec<u8> = vec![0; size_of::<Vector3<f32>>()];
+                            file.read_exact(&mut scale_seq_bytes)?;
+                            let deserialized: [f32; 3] = file_encoding().deserialize(&scale_seq_bytes).unwrap();
+                            scale_seq_vec.push(Vector3::new(deserialized[0], deserialized[1], deserialized[2]));
+                        }
+                        key.scale_seq = Some(scale_seq_vec);
                     }
-
-                    key.quat_seq = Some(quat_seq_vec);
                 }
             }
 
@@ -312,606 +873,2406 @@ impl<'a> AnimDataBone<'a> {
         };
 
         self.key_seq = keys;
+        Ok(())
     }
 
-    pub fn get_num_bones(&self) -> usize {
-        self.header.bone_num as usize
-    }
-
-    pub fn get_num_frames(&self) -> usize {
-        self.header.frame_num as usize
-    }
-
-    /// goes through all the bones and generates a tree-like structure for the joints of the model
-    ///                           | parent
-    ///                          /\
-    ///                       c1   c2
-    /// so on and so forth.
-    fn generate_joint_structure(&'a mut self) -> String {
-        // create base map, containing all the joints of the skeleton, and as much data about them as is available
-        for i in 0..self.get_num_bones() {
-            let current_bone = &self.base_seq[i];
-            if !self.bone_map.contains_key(&(i as u32)) {
-                let position_matrix = Some(self.position_matrices[current_bone.id as usize]);
-                let mut dummies: Vec<DummyObject> = Vec::new();
-
-                if self.dummy_seq.contains_key(&current_bone.id) {
-                    let dummy_objects = self.dummy_seq.get(&current_bone.id).unwrap();
-
-                    for j in 0..dummy_objects.len() {
-                        dummies.push(DummyObject {
-                            id: dummy_objects[j].id,
-                            parent_id: dummy_objects[j].parent_bone_id,
-                            position_matrix: Some(dummy_objects[j].mat.matrix),
-                        });
-                    }
-                }
-
-                self.bone_map.insert(
-                    i as u32,
-                    RefCell::new(Joint {
-                        bone_id: current_bone.id,
-                        parent: None,
-                        bone_name: current_bone.get_name(),
-                        children: Vec::new(),
-                        parent_id: current_bone.parent_id,
-                        position_matrix,
-                        dummies,
-                        ..Default::default()
-                    }),
-                );
-            }
-        }
-
-        // link all the joints to their parent/children joints
-        for (_, v) in self.bone_map.iter() {
-            let mut current_joint = v.borrow_mut();
-            if current_joint.parent_id != u32::MAX {
-                if self.bone_map.contains_key(&current_joint.parent_id) {
-                    let parent_bone = self.bone_map.get(&current_joint.parent_id).unwrap();
-                    let mut parent_joint = parent_bone.borrow_mut();
-
-                    current_joint.parent = Some(parent_bone);
-                    parent_joint.children.push(v);
-                } else {
-                    println!(
-                        "Parent bone not found in map. Parent Bone ID - {}, Current Bone ID - {}",
-                        current_joint.parent_id, current_joint.bone_id
-                    );
-                    panic!();
-                }
+    /// some files have a block of bytes after the key data, possibly a checksum. its format hasn't been
+    /// identified yet, so it is kept verbatim and written back by `write_lab` rather than validated or
+    /// recomputed. scales are only recognized by the exact size of the key data, so in a quaternion file with
+    /// both they end up in the footer too. with `root_motion_track`, a footer of a translation per frame is
+    /// read as the root motion as well
+    fn load_footer<R: Read + Seek>(&mut self, file: &mut R) -> Result<(), LabError> {
+        file.read_to_end(&mut self.footer)?;
+        if self.options.root_motion_track {
+            self.root_motion = self.read_root_motion();
+            if self.root_motion.is_some() {
+                return Ok(());
             }
+            self.warn(format!(
+                "the file has {} bytes after the key data, which doesn't fit a root motion track of {} frames",
+                self.footer.len(),
+                self.header.frame_num
+            ));
+        }
+        if !self.footer.is_empty() {
+            self.warn(format!(
+                "the file has {} bytes after the key data, they are kept as is",
+                self.footer.len()
+            ));
         }
 
-        let root_joint = self.bone_map.get(&0).unwrap();
-        self.root_joint = Some(root_joint);
-
-        self.write_collada_data()
+        Ok(())
     }
 
-    fn generate_position_matrices_at_rest(&mut self) {
-        let mut position_matrices: Vec<Matrix4<f32>> = Vec::new();
+    /// the footer read as a root motion track: a little-endian `[f32; 3]` translation per frame, right after
+    /// the key data and filling the rest of the file. it stays in the footer too, which `write_lab` leaves out
+    /// once the track is added to the keys of the root
+    fn read_root_motion(&self) -> Option<Vec<Vector3<f32>>> {
+        let frame_size = size_of::<Vector3<f32>>();
+        if self.footer.is_empty() || self.footer.len() != self.header.frame_num as usize * frame_size {
+            return None;
+        }
 
-        for i in 0..self.header.bone_num as usize {
-            let key = &self.key_seq[i];
-            // let current_bone_name
-            
-            let mut current_matrix: Matrix4<f32> = SquareMatrix::identity();
-            
-            match &self.header.key_type {
-                BoneInfoKeyType::BoneKeyTypeQuaternion => {
-                    let frame_quat = match &key.quat_seq {
-                        Some(e) => e,
-                        None => {
-                            panic!("No frame_quat found");
-                        }
-                    };
-                    let frame_pos = match &key.pos_seq {
-                        Some(e) => e,
-                        None => {
-                            panic!("No frame_pos found");
-                        }
-                    };
-                    let quat: Quaternion<f32> = Quaternion::from(frame_quat[0]);
-                    let offset: Vector3<f32> = Vector3::from(frame_pos[0]);
-                    current_matrix = Matrix4::from(quat) * Matrix4::from_translation(offset);
-                },
+        let translations = self
+            .footer
+            .chunks(frame_size)
+            .map(|bytes| {
+                let translation: [f32; 3] = file_encoding().deserialize(bytes).unwrap();
+                Vector3::from(translation)
+            })
+            .collect();
+        Some(translations)
+    }
 
-                BoneInfoKeyType::BoneKeyTypeMat43 => {
-                    let mat43 = match &key.mat43_seq  {
-                        Some(vec) => vec,
-                        None => panic!("No mat43 seq found for bone")
-                    };
-    
-                    current_matrix = mat43[0].get_matrix4(); 
-                },
-    
-                BoneInfoKeyType::BoneKeyTypeMat44 => {
-                    let mat44 = match &key.mat44_seq {
-                        Some(vec) => vec,
-                        None => panic!("No mat44 seq found for bone"),
-                    };
-    
-                    current_matrix = mat44[0].matrix;
-                }
+    /// the translation of the root per frame, read from the footer of a file with a root motion track when
+    /// the export options ask for one. it is already added to the frames of the root joint
+    pub fn root_motion(&self) -> Option<&[Vector3<f32>]> {
+        self.root_motion.as_deref()
+    }
 
-                _ => {}
-            }
+    /// the bytes found after the key data of the loaded file, empty for most files. no known version stores a
+    /// bounding box: the bones start right after the 20 byte header, and the only footer layout known is the root
+    /// motion track. `bounds` computes the box instead
+    pub fn footer(&self) -> &[u8] {
+        &self.footer
+    }
 
-            position_matrices.push(current_matrix);
-        }
+    /// sets the number of frames of an animation that is built from scratch rather than loaded
+    pub fn set_frame_count(&mut self, frame_num: usize) {
+        self.header.frame_num = frame_num as u32;
+    }
 
-        self.position_matrices = position_matrices;
+    /// sets the kind of key data of an animation that is built from scratch. keys pushed with
+    /// `push_*_key` must match it
+    pub fn set_key_kind(&mut self, key_kind: KeyKind) {
+        self.header.key_type = match key_kind {
+            KeyKind::Mat43 => BoneInfoKeyType::BoneKeyTypeMat43,
+            KeyKind::Mat44 => BoneInfoKeyType::BoneKeyTypeMat44,
+            KeyKind::Quaternion => BoneInfoKeyType::BoneKeyTypeQuaternion,
+            KeyKind::Invalid => BoneInfoKeyType::BoneKeyTypeInvalid,
+        };
     }
 
-    fn generate_transformation_matrices_for_all_frames(&mut self) {
-      for i in 0..self.get_num_bones() {
-        let mut finish_matrices: Vec<Matrix4<f32>> = Vec::new();
-        let key = &self.key_seq[i];
+    /// adds a bone without any keys and returns its index. the root bone has a parent id of `u32::MAX`.
+    /// panics if the name doesn't fit in the 64 byte, nul terminated name field of the file
+    pub fn push_bone(&mut self, name: &str, id: u32, parent_id: u32, inverse_bind: Matrix4<f32>) -> usize {
+        let mut bone = BoneBaseInfo::new();
+        if name.len() >= bone.name.len() {
+            panic!("Bone name {} is longer than {} bytes", name, bone.name.len() - 1);
+        }
+        bone.name[..name.len()].copy_from_slice(name.as_bytes());
+        bone.id = id;
+        bone.parent_id = parent_id;
 
-        
-        let mut current_matrix: Matrix4<f32> = SquareMatrix::identity();
-        for j in 0..self.get_num_frames() {
-            match &self.header.key_type {
-                BoneInfoKeyType::BoneKeyTypeQuaternion => {
-                let frame_quat = match &key.quat_seq {
-                  Some(e) => e,
-                  None => {
-                      panic!("No frame_quat found");
-                  }
-                };
-                let frame_pos = match &key.pos_seq {
-                    Some(e) => e,
-                    None => {
-                        panic!("No frame_pos found");
-                    }
-                };
-                let quat: Quaternion<f32> = Quaternion::from(frame_quat[j]);
-                let offset: Vector3<f32> = Vector3::from(frame_pos[j]);
-                current_matrix = Matrix4::from(quat) * Matrix4::from_translation(offset);
-            },
+        self.base_seq.push(bone);
+        self.invmat_seq.push(lwMatrix44 { matrix: inverse_bind });
+        self.key_seq.push(BoneKeyInfo::new());
+        self.header.bone_num += 1;
 
-            BoneInfoKeyType::BoneKeyTypeMat43 => {
-                let mat43 = match &key.mat43_seq  {
-                    Some(vec) => vec,
-                    None => panic!("No mat43 seq found for bone")
-                };
+        self.base_seq.len() - 1
+    }
 
-                current_matrix = mat43[j].get_matrix4(); 
-            },
+    /// adds a dummy object attached to the bone with the given id
+    pub fn push_dummy(&mut self, id: u32, parent_bone_id: u32, matrix: Matrix4<f32>) {
+        self.dummy_seq.entry(parent_bone_id).or_insert_with(Vec::new).push(BoneDummyInfo {
+            id,
+            parent_bone_id,
+            mat: lwMatrix44 { matrix },
+        });
+        self.header.dummy_num += 1;
+    }
 
-            BoneInfoKeyType::BoneKeyTypeMat44 => {
-                let mat44 = match &key.mat44_seq {
-                    Some(vec) => vec,
-                    None => panic!("No mat44 seq found for bone"),
-                };
+    /// appends the key of the next frame to a bone of a quaternion animation
+    pub fn push_quaternion_key(&mut self, bone: usize, position: Vector3<f32>, rotation: Quaternion<f32>) {
+        self.expect_key_kind(KeyKind::Quaternion);
+        let key = &mut self.key_seq[bone];
+        key.pos_seq.get_or_insert_with(Vec::new).push(position);
+        key.quat_seq.get_or_insert_with(Vec::new).push(rotation);
+    }
 
-                current_matrix = mat44[j].matrix;
-            }
+    /// appends the key of the next frame to a bone of a 4x3 matrix animation
+    pub fn push_mat43_key(&mut self, bone: usize, matrix: lwMatrix43) {
+        self.expect_key_kind(KeyKind::Mat43);
+        self.key_seq[bone].mat43_seq.get_or_insert_with(Vec::new).push(matrix);
+    }
 
-            _ => {}
-          }
+    /// appends the key of the next frame to a bone of a 4x4 matrix animation
+    pub fn push_mat44_key(&mut self, bone: usize, matrix: lwMatrix44) {
+        self.expect_key_kind(KeyKind::Mat44);
+        self.key_seq[bone].mat44_seq.get_or_insert_with(Vec::new).push(matrix);
+    }
 
-          finish_matrices.push(current_matrix);
+    fn expect_key_kind(&self, key_kind: KeyKind) {
+        if self.key_kind() != key_kind {
+            panic!("Can't add {:?} keys to an animation of {:?} keys", key_kind, self.key_kind());
         }
-
-        self.transformation_matrices.push(finish_matrices);
-      }
     }
-    
-    pub fn get_transforms_for_frame(&self, frame: usize) -> Vec<Matrix4<f32>> {
-        let mut finish_matrices: Vec<Matrix4<f32>> = Vec::new();
-
-        for i in 0..self.header.bone_num as usize {
-            let key = &self.key_seq[i];
-            let current_bone_name = &self.base_seq[i].get_name();
-            let frame_quat = match &key.quat_seq {
-                Some(e) => e,
-                None => {
-                    panic!("No frame_quat found");
-                }
-            };
-            let frame_pos = match &key.pos_seq {
-                Some(e) => e,
-                None => {
-                    panic!("No frame_pos found");
-                }
-            };
-            let mut current_matrix: Matrix4<f32> = SquareMatrix::identity();
 
-            match &self.header.key_type {
-                BoneInfoKeyType::BoneKeyTypeQuaternion => {
-                    let quat: Quaternion<f32> = Quaternion::from(frame_quat[frame]);
-                    let offset: Vector3<f32> = Vector3::from(frame_pos[frame]);
-                    let mat1 = Matrix4::from(quat);
-                    let mat2 = Matrix4::from_translation(offset);
-                    current_matrix = Matrix4::from(quat) * Matrix4::from_translation(offset);
-                }
+    /// checks that an animation built with the `push_*` methods has a key for every frame of every bone,
+    /// then computes the same data `load` does, so it can be exported or queried like a loaded one
+    pub fn finish(&mut self) -> Result<(), LabError> {
+        if self.header.frame_num == 0 && self.header.key_type != BoneInfoKeyType::BoneKeyTypeInvalid {
+            return Err(LabError::NoFrames);
+        }
+        if self.header.bone_num == 0 {
+            return Err(LabError::NoBones);
+        }
 
-                _ => {}
+        for bone in 0..self.get_num_bones() {
+            if self.get_key_count(bone) != self.get_num_frames() {
+                return Err(LabError::MissingKeys(bone));
             }
-
-            finish_matrices.push(current_matrix);
         }
+        self.check_hierarchy()?;
 
-        finish_matrices
+        self.prepare();
+        self.check_skin_weights()?;
+        if self.options.validate || cfg!(debug_assertions) {
+            self.validate_ids()?;
+        }
+        Ok(())
     }
 
-    pub fn get_transforms_for_frame_and_bone(
-        &self,
-        frame: usize,
-        bone: usize,
-    ) -> Vec<Matrix4<f32>> {
-        let mut finish_matrices: Vec<Matrix4<f32>> = Vec::new();
-        let key = &self.key_seq[bone];
-        let frame_quat = match &key.quat_seq {
-            Some(e) => e,
-            None => {
-                panic!("No frame_quat found");
-            }
-        };
-        let frame_pos = match &key.pos_seq {
-            Some(e) => e,
-            None => {
-                panic!("No frame_pos found");
-            }
+    /// writes the loaded animation and all the matrices computed from it into a compact binary cache, for
+    /// `load_cache` to restore without parsing the file or computing the matrices again
+    pub fn write_cache<W: Write>(&self, writer: &mut W) -> Result<(), LabError> {
+        let mut lab = Vec::new();
+        self.write_lab(&mut lab)?;
+
+        let cache = AnimCache {
+            magic: CACHE_MAGIC,
+            version: CACHE_VERSION,
+            options: self.get_cache_options(),
+            lab,
+            footer: self.footer.clone(),
+            position_matrices: self.position_matrices.iter().map(|&matrix| matrix.into()).collect(),
+            transformation_matrices: self
+                .transformation_matrices
+                .iter()
+                .map(|frames| frames.iter().map(|&matrix| matrix.into()).collect())
+                .collect(),
+            warnings: self.warnings.clone(),
         };
-        let mut current_matrix: Matrix4<f32> = SquareMatrix::identity();
+        // serializing into memory can't fail
+        writer.write_all(&file_encoding().serialize(&cache).unwrap())?;
+        Ok(())
+    }
 
-        match &self.header.key_type {
-            BoneInfoKeyType::BoneKeyTypeQuaternion => {
-                let quat: Quaternion<f32> = Quaternion::from(frame_quat[frame]);
-                let offset: Vector3<f32> = Vector3::from(frame_pos[frame]);
-                let mat1 = Matrix4::from(quat);
-                let mat2 = Matrix4::from_translation(offset);
-                current_matrix = Matrix4::from(quat) * Matrix4::from_translation(offset);
-            }
+    /// restores an animation written by `write_cache`, in place of `load`. fails with `LabError::StaleCache`
+    /// if the cache isn't one, was written by another version of the cache format or with other options that
+    /// change the loaded data, see `CacheOptions`, in which case the animation is left as it was and has to be
+    /// loaded from its file again. the restored animation goes through the same checks as a loaded one
+    pub fn load_cache<R: Read>(&mut self, reader: &mut R) -> Result<(), LabError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+
+        // the limit keeps a corrupt length from allocating more than the cache could hold
+        let cache: AnimCache = match file_encoding().with_limit(bytes.len() as u64).deserialize(&bytes) {
+            Ok(cache) => cache,
+            Err(_) => return Err(LabError::StaleCache),
+        };
+        if cache.magic != CACHE_MAGIC || cache.version != CACHE_VERSION || cache.options != self.get_cache_options() {
+            return Err(LabError::StaleCache);
+        }
 
-            _ => {}
+        let mut file = io::Cursor::new(cache.lab);
+        self.load_header(&mut file)?;
+        self.load_base_seq(&mut file)?;
+        self.load_invmat_seq(&mut file)?;
+        self.load_dummy_seq(&mut file)?;
+        // `write_lab` always writes all the positions of a bone before its rotations
+        let interleaved_keys = std::mem::replace(&mut self.options.interleaved_keys, false);
+        let loaded = self.load_key_seq(&mut file);
+        self.options.interleaved_keys = interleaved_keys;
+        loaded?;
+        self.footer = cache.footer;
+        if self.options.root_motion_track {
+            self.root_motion = self.read_root_motion();
         }
 
-        finish_matrices.push(current_matrix);
+        if let Some(clips) = &self.clips {
+            clips.validate(self.get_exported_animation_frame_count())?;
+        }
 
-        finish_matrices
+        self.position_matrices = cache.position_matrices.into_iter().map(Matrix4::from).collect();
+        self.transformation_matrices = cache
+            .transformation_matrices
+            .into_iter()
+            .map(|frames| frames.into_iter().map(Matrix4::from).collect())
+            .collect();
+        self.warnings = cache.warnings;
+        self.build_bone_index();
+
+        // the same checks as `load`, so a cache of another build or edited by hand can't get past them
+        self.check_hierarchy()?;
+        self.check_skin_weights()?;
+        if self.options.validate || cfg!(debug_assertions) {
+            self.validate_ids()?;
+        }
+        Ok(())
     }
 
-    pub fn apply_transforms(&self, transforms: Vec<Matrix4<f32>>) -> Vec<Vector3<f32>> {
-        let mut positions: Vec<Vector3<f32>> = Vec::with_capacity(self.header.bone_num as usize);
+    /// the options the cache is written with and has to match to be loaded
+    fn get_cache_options(&self) -> CacheOptions {
+        let options = &self.options;
+        CacheOptions {
+            center_root: options.center_root,
+            dummy_space: options.dummy_space,
+            max_frames: options.max_frames,
+            epsilon: options.epsilon,
+            name_encoding: options.name_encoding.map(|encoding| encoding.name().to_string()),
+            auto_handedness: options.auto_handedness,
+            fix_mirror: options.fix_mirror,
+            keep_unnormalized_quats: options.keep_unnormalized_quats,
+            interleaved_keys: options.interleaved_keys,
+            root_motion_track: options.root_motion_track,
+            root_transform: options.root_transform.map(Into::into),
+            axis_map: options.axis_map.map(|axis_map| axis_map.matrix().into()),
+            strict_sizes: options.strict_sizes,
+            ignored_bones: options.ignored_bones.clone(),
+            max_depth: options.max_depth,
+        }
+    }
 
-        for i in 0..self.header.bone_num as usize {
-            let inv_mat = Matrix4::from(self.invmat_seq[i].matrix);
-            let original_mat = inv_mat.invert().unwrap();
-            let start_pos =
-                Vector3::new(original_mat[0][3], original_mat[1][3], original_mat[2][3]);
-            let transformation_matrix = inv_mat * transforms[i];
+    /// writes the animation in the .lab format the game reads. the keys hold the exported frames, with the
+    /// export options that change them already applied, so the written file loads without those options. a
+    /// root motion track is in the keys of the root then, and is left out of the footer. as the rest pose of a
+    /// file is its first frame, the first translation of the track moves the rest pose of the written root
+    pub fn write_lab<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_u32::<LittleEndian>(self.header.version)?;
+        writer.write_u32::<LittleEndian>(self.header.bone_num)?;
+        writer.write_u32::<LittleEndian>(self.header.frame_num)?;
+        writer.write_u32::<LittleEndian>(self.header.dummy_num)?;
+        writer.write_u32::<LittleEndian>(match self.header.key_type {
+            BoneInfoKeyType::BoneKeyTypeMat43 => 1,
+            BoneInfoKeyType::BoneKeyTypeMat44 => 2,
+            BoneInfoKeyType::BoneKeyTypeQuaternion => 3,
+            BoneInfoKeyType::BoneKeyTypeInvalid => 4,
+        })?;
+
+        for bone in self.base_seq.iter() {
+            writer.write_all(&bone.name)?;
+            writer.write_u32::<LittleEndian>(bone.id)?;
+            writer.write_u32::<LittleEndian>(bone.parent_id)?;
+        }
 
-            // if i >= 8 {
-            //   println!("{:?}", transformation_matrix);
-            // }
-            positions.push(transformation_matrix.transform_vector(start_pos));
+        for invmat in self.invmat_seq.iter() {
+            write_rows(writer, &invmat.to_rows())?;
         }
 
-        positions
-    }
-
-    fn write_collada_data(&self) -> String {
-        let options = Options {
-            use_single_quote: false,
-            ..Default::default()
-        };
-
-        let mut writer = XmlWriter::new(options);
-        writer.start_element("COLLADA");
-        writer.write_attribute("xmlns", "http://www.collada.org/2005/11/COLLADASchema");
-        writer.write_attribute("version", "1.4.1");
-
-        self.write_asset_data(&mut writer);
-        self.write_visual_scene_data(&mut writer);
-        self.write_animation_data(&mut writer);
-        self.write_scene_element(&mut writer);
+        // dummies are grouped by the bone they are attached to, which is the order they are written in
+        for bone in self.base_seq.iter() {
+            for dummy in self.dummy_seq.get(&bone.id).into_iter().flatten() {
+                writer.write_u32::<LittleEndian>(dummy.id)?;
+                writer.write_u32::<LittleEndian>(dummy.parent_bone_id)?;
+                write_rows(writer, &dummy.mat.to_rows())?;
+            }
+        }
 
-        writer.end_element();
+        for bone in 0..self.get_num_bones() {
+            match self.bone_keys(bone) {
+                BoneKeys::Mat43(keys) => {
+                    for key in keys.iter() {
+                        for value in key.matrix.iter().flatten() {
+                            writer.write_f32::<LittleEndian>(*value)?;
+                        }
+                    }
+                }
+                BoneKeys::Mat44(keys) => {
+                    for key in keys.iter() {
+                        write_rows(writer, &key.to_rows())?;
+                    }
+                }
+                BoneKeys::Quaternion { positions, rotations } => {
+                    for position in positions.iter() {
+                        writer.write_f32::<LittleEndian>(position.x)?;
+                        writer.write_f32::<LittleEndian>(position.y)?;
+                        writer.write_f32::<LittleEndian>(position.z)?;
+                    }
+                    for rotation in rotations.iter() {
+                        writer.write_f32::<LittleEndian>(rotation.v.x)?;
+                        writer.write_f32::<LittleEndian>(rotation.v.y)?;
+                        writer.write_f32::<LittleEndian>(rotation.v.z)?;
+                        writer.write_f32::<LittleEndian>(rotation.s)?;
+                    }
+                    for scale in self.bone_scales(bone).unwrap_or(&[]).iter() {
+                        writer.write_f32::<LittleEndian>(scale.x)?;
+                        writer.write_f32::<LittleEndian>(scale.y)?;
+                        writer.write_f32::<LittleEndian>(scale.z)?;
+                    }
+                }
+                BoneKeys::None => {}
+            }
+        }
 
-        let content = writer.end_document();
-        content
+        if self.root_motion.is_none() {
+            writer.write_all(&self.footer)?;
+        }
+        Ok(())
     }
 
-    fn write_asset_data(&self, writer: &mut XmlWriter) {
-        // asset tag
-        writer.start_element("asset");
+    pub fn get_num_bones(&self) -> usize {
+        self.header.bone_num as usize
+    }
 
-        writer.start_element("contributor");
-        writer.start_element("author");
-        writer.write_text("Perseus");
-        writer.end_element();
-        writer.end_element();
+    pub fn get_num_frames(&self) -> usize {
+        self.header.frame_num as usize
+    }
 
-        // created-at tag
-        writer.start_element("created");
-        writer.write_text(&chrono::Utc::now().to_string());
-        writer.end_element();
+    /// the kind of key data stored in the file
+    ///
+    /// ```
+    /// use lab_parser::anim::bone::{AnimDataBone, KeyKind};
+    /// use std::fs::File;
+    ///
+    /// let mut file = File::open("src/tests/anim-quat.lab").unwrap();
+    /// let mut anim_data = AnimDataBone::new();
+    /// anim_data.load(&mut file).unwrap();
+    ///
+    /// assert_eq!(anim_data.key_kind(), KeyKind::Quaternion);
+    /// ```
+    pub fn key_kind(&self) -> KeyKind {
+        match self.header.key_type {
+            BoneInfoKeyType::BoneKeyTypeMat43 => KeyKind::Mat43,
+            BoneInfoKeyType::BoneKeyTypeMat44 => KeyKind::Mat44,
+            BoneInfoKeyType::BoneKeyTypeQuaternion => KeyKind::Quaternion,
+            BoneInfoKeyType::BoneKeyTypeInvalid => KeyKind::Invalid,
+        }
+    }
 
-        // up-axis
-        writer.start_element("up_axis");
-        writer.write_text("Z_UP");
-        writer.end_element();
-        
-        // end asset
-        writer.end_element();
+    /// a one line description of the file, e.g. `v4101 quat 35 bones × 228 frames, 2 dummies`
+    pub fn summary(&self) -> String {
+        format!(
+            "v{} {} {} bones × {} frames, {} dummies",
+            self.header.version,
+            self.key_kind().name(),
+            self.get_num_bones(),
+            self.get_num_frames(),
+            self.header.dummy_num
+        )
     }
 
-    fn write_visual_scene_data(&self, writer: &mut XmlWriter) {
-        writer.start_element("library_visual_scenes");
+    /// the per-frame key data of a bone, in the form it is stored in the file.
+    /// returns `BoneKeys::None` if the bone index is out of range or the bone has no keys
+    ///
+    /// ```
+    /// use lab_parser::anim::bone::{AnimDataBone, BoneKeys};
+    /// use std::fs::File;
+    ///
+    /// let mut file = File::open("src/tests/anim-quat.lab").unwrap();
+    /// let mut anim_data = AnimDataBone::new();
+    /// anim_data.load(&mut file).unwrap();
+    ///
+    /// match anim_data.bone_keys(0) {
+    ///     BoneKeys::Quaternion { positions, rotations } => {
+    ///         assert_eq!(positions.len(), anim_data.get_num_frames());
+    ///         assert_eq!(rotations.len(), anim_data.get_num_frames());
+    ///     }
+    ///     _ => panic!("expected quaternion keys"),
+    /// }
+    /// ```
+    pub fn bone_keys(&self, bone: usize) -> BoneKeys<'_> {
+        let key = match self.key_seq.get(bone) {
+            Some(key) => key,
+            None => return BoneKeys::None,
+        };
 
-        // start the visual scene tag
-        writer.start_element("visual_scene");
-        writer.write_attribute("id", "Scene");
-        writer.write_attribute("name", "Scene");
+        match self.header.key_type {
+            BoneInfoKeyType::BoneKeyTypeMat43 => match &key.mat43_seq {
+                Some(seq) => BoneKeys::Mat43(seq),
+                None => BoneKeys::None,
+            },
+            BoneInfoKeyType::BoneKeyTypeMat44 => match &key.mat44_seq {
+                Some(seq) => BoneKeys::Mat44(seq),
+                None => BoneKeys::None,
+            },
+            BoneInfoKeyType::BoneKeyTypeQuaternion => match (&key.pos_seq, &key.quat_seq) {
+                (Some(positions), Some(rotations)) => BoneKeys::Quaternion {
+                    positions,
+                    rotations,
+                },
+                _ => BoneKeys::None,
+            },
+            BoneInfoKeyType::BoneKeyTypeInvalid => BoneKeys::None,
+        }
+    }
 
-        // base skeleton node
-        writer.start_element("node");
-        writer.write_attribute("id", "Skeleton");
-        writer.write_attribute("name", "Skeleton");
-        writer.write_attribute("type", "NODE");
+    /// the per-frame rotations of a bone in a quaternion file, `None` for other files or an out of range bone
+    pub fn quaternion_track(&self, bone: usize) -> Option<&[Quaternion<f32>]> {
+        match self.bone_keys(bone) {
+            BoneKeys::Quaternion { rotations, .. } => Some(rotations),
+            _ => None,
+        }
+    }
 
-        self.write_joint_node(writer, self.root_joint.unwrap());
+    /// the per-frame positions of a bone in a quaternion file, `None` for other files or an out of range bone
+    pub fn position_track(&self, bone: usize) -> Option<&[Vector3<f32>]> {
+        match self.bone_keys(bone) {
+            BoneKeys::Quaternion { positions, .. } => Some(positions),
+            _ => None,
+        }
+    }
 
-        writer.end_element();
-        writer.end_element();
+    /// the per-frame scale of a bone, for the quaternion files that store one
+    pub fn bone_scales(&self, bone: usize) -> Option<&[Vector3<f32>]> {
+        self.key_seq.get(bone)?.scale_seq.as_deref()
+    }
 
-        writer.end_element();
+    /// the tolerance of the float comparisons of the export options, `DEFAULT_EPSILON` unless it is set
+    pub fn get_epsilon(&self) -> f32 {
+        self.options.epsilon.unwrap_or(DEFAULT_EPSILON)
     }
 
-    fn write_joint_node(&self, writer: &mut XmlWriter, joint: &RefCell<Joint>) {
-        let joint_data = joint.borrow();
+    /// the time of the first and last exported frame, in seconds: the first and last of `frame_times`, or the
+    /// time offset twice when no frame is exported
+    pub fn time_extent(&self) -> (f32, f32) {
+        let frame_times = self.frame_times();
+        match (frame_times.first(), frame_times.last()) {
+            (Some(&start), Some(&end)) => (start, end),
+            _ => (self.get_time_offset(), self.get_time_offset()),
+        }
+    }
 
-        writer.start_element("node");
-        writer.write_attribute("id", &joint_data.bone_name.replace(" ", "_"));
-        writer.write_attribute("sid", &joint_data.bone_name.replace(" ", "_"));
-        writer.write_attribute("name", &joint_data.bone_name);
-        writer.write_attribute("type", "JOINT");
+    /// the TIME values the exporter writes for the keyframes, in seconds: `i / get_frames_per_second()` for every
+    /// exported frame, including the injected bind pose, shifted by the time offset of the export options
+    pub fn frame_times(&self) -> Vec<f32> {
+        let frames_per_second = self.get_frames_per_second();
+        let time_offset = self.get_time_offset();
+        (0..self.get_timed_frame_count())
+            .map(|frame| time_offset + frame as f32 / frames_per_second)
+            .collect()
+    }
 
-        self.write_matrix(writer, joint_data.position_matrix.unwrap());
+    /// the time the first frame is exported at, in seconds
+    fn get_time_offset(&self) -> f32 {
+        self.options.time_offset.unwrap_or(0.0)
+    }
 
-        if joint_data.dummies.len() > 0 {
-            for i in 0..joint_data.dummies.len() {
-                self.write_dummy_node(writer, &joint_data.dummies[i]);
-            }
+    /// the playback rate of the export: the game's `FRAMES_PER_SECOND`, or with a `target_duration` the rate
+    /// that puts the last exported frame at that time. the frames themselves are kept as they are
+    pub fn get_frames_per_second(&self) -> f32 {
+        let frame_count = self.get_timed_frame_count();
+        match self.options.target_duration {
+            Some(target_duration) if frame_count > 1 => (frame_count - 1) as f32 / target_duration,
+            _ => FRAMES_PER_SECOND,
         }
+    }
 
-        for i in 0..joint_data.children.len() {
-            self.write_joint_node(writer, joint_data.children[i]);
+    /// number of frames the animation sources have a time for, the injected bind pose included
+    fn get_timed_frame_count(&self) -> usize {
+        let frame_count = self.get_exported_animation_frame_count();
+        if self.options.inject_bind_pose {
+            frame_count + 1
+        } else {
+            frame_count
         }
-
-        writer.end_element();
     }
 
-    fn write_dummy_node(&self, writer: &mut XmlWriter, dummy: &DummyObject) {
-        writer.start_element("node");
-        writer.write_attribute("id", &format!("Dummy_{}", dummy.id));
-        writer.write_attribute("name", &format!("Dummy {}", dummy.id));
-        writer.write_attribute("type", "NODE");
-        self.write_matrix(writer, dummy.position_matrix.unwrap());
-
-        writer.end_element();
+    /// each bone's name paired with its rest-pose matrix, in file order
+    pub fn bind_pose(&self) -> Vec<(String, Matrix4<f32>)> {
+        self.position_matrices
+            .iter()
+            .enumerate()
+            .map(|(index, matrix)| (self.get_bone_name(index), *matrix))
+            .collect()
     }
 
-    fn write_animation_data(&self, writer: &mut XmlWriter) {
-      writer.start_element("library_animations");
-
-      for i in 0..self.get_num_bones() {
-        self.write_animation_element(writer, i);
-      }
+    /// every dummy of the animation, grouped by the bone they hang off in bone order. dummies whose parent
+    /// doesn't exist come last
+    pub fn dummies(&self) -> impl Iterator<Item = DummyView> {
+        let mut dummies = Vec::with_capacity(self.header.dummy_num as usize);
+        for parent_id in self.get_dummy_parent_ids() {
+            dummies.extend(self.dummies_for_bone(parent_id));
+        }
 
-      writer.end_element();
+        dummies.into_iter()
     }
 
-    fn write_animation_element(&self, writer: &mut XmlWriter, bone_index: usize) {
-      let bone_data = &self.base_seq[bone_index];
-      let sanitized_bone_name = bone_data.get_name().replace(" ", "_");
+    /// the dummies hanging off the bone with the given id, in file order. empty for a bone without dummies
+    pub fn dummies_for_bone(&self, bone_id: u32) -> Vec<DummyView> {
+        let parent_bone_name = self
+            .base_seq
+            .iter()
+            .position(|bone| bone.id == bone_id)
+            .map(|index| self.get_bone_name(index));
+
+        self.dummy_seq
+            .get(&bone_id)
+            .into_iter()
+            .flatten()
+            .map(|dummy| DummyView {
+                id: dummy.id,
+                parent_bone_name: parent_bone_name.clone(),
+                matrix: dummy.mat.matrix,
+            })
+            .collect()
+    }
 
-      writer.start_element("animation");
-      writer.write_attribute("id", &format!("{}_pose_matrix", sanitized_bone_name));
-      writer.write_attribute("name", &format!("{}_pose_matrix", sanitized_bone_name));
-    
-      writer.start_element("source");
-      writer.write_attribute("id", &format!("{}_pose_matrix-input", sanitized_bone_name));
+    /// number of dummies attached to the skeleton
+    pub fn dummy_count(&self) -> usize {
+        self.dummy_seq.values().map(Vec::len).sum()
+    }
 
-      writer.start_element("float_array");
-      writer.write_attribute("id", &format!("{}_pose_matrix-input-array",sanitized_bone_name)); 
-      writer.write_attribute("count", &self.header.frame_num);
-      for i in 0..self.header.frame_num as usize {
-        writer.write_text(&(i as f32 / 25.00).to_string());
-      }
-      writer.end_element();
+    /// ids of the dummies, in the same order as `dummies`
+    pub fn dummy_ids(&self) -> Vec<u32> {
+        self.get_dummy_parent_ids()
+            .iter()
+            .flat_map(|parent_id| self.dummy_seq.get(parent_id).into_iter().flatten())
+            .map(|dummy| dummy.id)
+            .collect()
+    }
 
-      writer.start_element("technique_common");
-      writer.start_element("accessor");
-      writer.write_attribute("source", &format!("#{}_pose_matrix-input-array", sanitized_bone_name));
-      writer.write_attribute("count", &self.header.frame_num);
-      writer.write_attribute("stride", &1);
-      writer.start_element("param");
-      writer.write_attribute("name", "TIME");
-      writer.write_attribute("type", "float");
-      writer.end_element();
-      writer.end_element();
-      writer.end_element();
-      writer.end_element();
+    /// ids of the bones the dummies are grouped under, in bone order followed by the ids that don't match
+    /// any bone
+    fn get_dummy_parent_ids(&self) -> Vec<u32> {
+        let mut parent_ids: Vec<u32> = self.base_seq.iter().map(|bone| bone.id).collect();
+        let mut orphan_parent_ids: Vec<u32> = self
+            .dummy_seq
+            .keys()
+            .filter(|parent_id| !parent_ids.contains(parent_id))
+            .copied()
+            .collect();
+        orphan_parent_ids.sort_unstable();
+        parent_ids.extend(orphan_parent_ids);
+        parent_ids
+    }
 
-      writer.start_element("source");
-      writer.write_attribute("id", &format!("{}_pose_matrix-output", sanitized_bone_name));
-      writer.start_element("float_array");
-      writer.write_attribute("id", &format!("{}_pose_matrix-output-array", sanitized_bone_name));
-      writer.write_attribute("count", &(16 * self.header.frame_num));
-      AnimDataBone::write_all_matrices(writer, &self.transformation_matrices[bone_index]);
-      writer.end_element();
-      writer.start_element("technique_common");
-      writer.start_element("accessor");
-      writer.write_attribute("source", &format!("#{}_pose_matrix-output-array", sanitized_bone_name));
-      writer.write_attribute("count", &self.header.frame_num);
-      writer.write_attribute("stride", &16);
-      writer.start_element("param");
-      writer.write_attribute("name", "TRANSFORM");
-      writer.write_attribute("type", "float4x4");
-      writer.end_element();
-      writer.end_element();
-      writer.end_element();
-      writer.end_element();
+    /// the inverse bind matrix of every bone as a json object keyed by bone name. the matrices are written
+    /// row by row in the d3d layout they are stored in, translation in the last row
+    pub fn write_inverse_bind_json(&self) -> String {
+        let matrices: BTreeMap<String, [[f32; 4]; 4]> = self
+            .invmat_seq
+            .iter()
+            .enumerate()
+            .map(|(index, invmat)| (self.get_bone_name(index), invmat.to_rows()))
+            .collect();
+
+        serde_json::to_string_pretty(&matrices).unwrap()
+    }
 
-      writer.start_element("source");
-      writer.write_attribute("id", &format!("{}_pose_matrix-interpolation", sanitized_bone_name));
-      writer.start_element("Name_array");
-      writer.write_attribute("id", &format!("{}_pose_matrix-interpolation-array", sanitized_bone_name));
-      writer.write_attribute("count", &self.header.frame_num);
-      for _ in 0..self.header.frame_num {
-        writer.write_text("LINEAR");
-      }
-      writer.end_element();
-      writer.start_element("technique_common");
-      writer.start_element("accessor");
-      writer.write_attribute("source", &format!("#{}_pose_matrix-interpolation-array", sanitized_bone_name));
-      writer.write_attribute("count", &self.header.frame_num);
-      writer.write_attribute("stride", &1);
-      writer.start_element("param");
-      writer.write_attribute("name", "INTERPOLATION");
-      writer.write_attribute("type", "name");
-      writer.end_element();
-      writer.end_element();
-      writer.end_element();
-      writer.end_element();
+    /// renames every bone after the bone at the same index in the reference, so animations whose source files
+    /// spell the names differently all export onto the reference's skeleton. the names are copied as they are
+    /// stored, so they are decoded with this animation's encoding. only the names change, the hierarchy and the
+    /// matrices are kept
+    pub fn take_bone_names_from(&mut self, reference: &AnimDataBone) -> Result<(), LabError> {
+        if reference.get_num_bones() != self.get_num_bones() {
+            return Err(LabError::BoneCountMismatch(reference.get_num_bones(), self.get_num_bones()));
+        }
 
-      writer.start_element("sampler");
-      writer.write_attribute("id", &format!("{}_pose_matrix-sampler", sanitized_bone_name));
-      writer.start_element("input");
-      writer.write_attribute("semantic", "INPUT");
-      writer.write_attribute("source", &format!("#{}_pose_matrix-input", sanitized_bone_name));
-      writer.end_element();
-      writer.start_element("input");
-      writer.write_attribute("semantic", "OUTPUT");
-      writer.write_attribute("source", &format!("#{}_pose_matrix-output", sanitized_bone_name));
-      writer.end_element();
-      writer.start_element("input");
-      writer.write_attribute("semantic", "INTERPOLATION");
-      writer.write_attribute("source", &format!("#{}_pose_matrix-interpolation", sanitized_bone_name));
-      writer.end_element();
-      writer.end_element();
-      
-      writer.start_element("channel");
-      writer.write_attribute("source", &format!("#{}_pose_matrix-sampler", sanitized_bone_name));
-      writer.write_attribute("target", &format!("{}/transform", sanitized_bone_name));
-      writer.end_element();
+        for (bone, reference_bone) in self.base_seq.iter_mut().zip(reference.base_seq.iter()) {
+            bone.name = reference_bone.name;
+        }
+        self.build_bone_index();
+        self.validate_ids()
+    }
 
-      writer.end_element();
+    /// name of the bone at the given index, decoded with the encoding set in the export options
+    pub fn get_bone_name(&self, bone_index: usize) -> String {
+        let bone = &self.base_seq[bone_index];
+        match self.options.name_encoding {
+            Some(encoding) => bone.get_name_with_encoding(encoding),
+            None => bone.get_name(),
+        }
     }
 
-    fn write_scene_element(&self, writer: &mut XmlWriter) {
-        writer.start_element("scene");
-        writer.start_element("instance_visual_scene");
-        writer.write_attribute("url", "#Scene");
-        writer.end_element();
-        writer.end_element();
+    /// name of the bone at the given index, strictly decoded as utf-8, for callers that want to report names
+    /// in another encoding rather than get replacement characters
+    pub fn try_get_bone_name(&self, bone_index: usize) -> Result<String, Utf8Error> {
+        self.base_seq[bone_index].try_get_name()
     }
 
-    pub fn write_matrix(&self, ele: &mut XmlWriter, matrix: Matrix4<f32>) {
-        ele.start_element("matrix");
-        ele.write_attribute("sid", "transform");
-        ele.write_text_fmt(format_args!(
-            "{} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} ",
-            matrix[0][0],
-            matrix[0][1],
-            matrix[0][2],
-            matrix[0][3],
-            matrix[1][0],
-            matrix[1][1],
-            matrix[1][2],
-            matrix[1][3],
-            matrix[2][0],
-            matrix[2][1],
-            matrix[2][2],
-            matrix[2][3],
-            matrix[3][0],
-            matrix[3][1],
-            matrix[3][2],
-            matrix[3][3]
-        ));
-        ele.end_element();
+    /// id of the bone at the given index, which is what dummies and child bones refer to it by
+    pub fn get_bone_id(&self, bone_index: usize) -> u32 {
+        self.base_seq[bone_index].id
     }
 
-    pub fn write_all_matrices(ele: &mut XmlWriter, matrix: &Vec<Matrix4<f32>>) {
-      for i in 0..matrix.len() {
-        let mat = matrix[i];
-        ele.write_text_fmt(format_args!("{} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {}", mat[0][0], mat[0][1], mat[0][2], mat[0][3], mat[1][0], mat[1][1], mat[1][2], mat[1][3], mat[2][0], mat[2][1], mat[2][2], mat[2][3], mat[3][0], mat[3][1], mat[3][2], mat[3][3]));
-      }
+    /// id of the parent of the bone at the given index, `u32::MAX` for the root
+    pub fn get_bone_parent_id(&self, bone_index: usize) -> u32 {
+        self.base_seq[bone_index].parent_id
     }
 
+    /// the matrices computed for every frame of the animation, relative to each bone's parent, as the export
+    /// writes them. the outer index is the bone and the inner one the frame, e.g. `transforms()[bone][frame]`.
+    /// they are empty until the animation has been loaded
+    pub fn transforms(&self) -> &[Vec<Matrix4<f32>>] {
+        &self.transformation_matrices
+    }
 
-    pub fn load_data_from_collada_skeleton(&mut self, doc: &ColladaDocument) {
-        let skeletons = doc.get_skeletons().unwrap();
+    pub(crate) fn get_inverse_bind_matrices(&self) -> Vec<Matrix4<f32>> {
+        self.invmat_seq.iter().map(|invmat| invmat.matrix).collect()
+    }
 
-        // we support only one skeleton in an animation for ToP lab files
-        if skeletons.len() > 1 {
-            panic!("More than one skeleton found. Invalid file format.");
-        }
-    
-        let main_skeleton = &skeletons[0];
-        
-        println!("{:?}", main_skeleton.joints);
-        // println!("{:?}", doc.get_bind_data_set().unwrap().bind_data[0]);
+    /// checks the loaded animation for the problems that make a file untrustworthy: broken hierarchies,
+    /// duplicate names and ids, NaN matrices, missing frames and bind matrices that can't be inverted
+    pub fn validate(&self) -> ValidationReport {
+        validation::validate(self)
     }
 
+    /// index of the bone which has no parent, if there is one
+    pub fn get_root_bone_index(&self) -> Option<usize> {
+        self.base_seq
+            .iter()
+            .position(|bone| bone.parent_id == u32::MAX)
+    }
 
+    /// goes through all the bones and generates a tree-like structure for the joints of the model
+    ///                           | parent
+    ///                          /\
+    ///                       c1   c2
+    /// so on and so forth.
+    /// `load` and `finish` check that there is a root and that every parent is in the file. the root is `None`
+    /// and bones with a missing parent are left out of the tree on data that hasn't gone through them
+    fn generate_joint_structure(&self) -> (Vec<Joint>, Option<usize>) {
+        let parents = self.get_parent_indices();
+
+        // a joint per bone, stored at the bone's index in the file and linked to the other joints by index.
+        // parent ids are resolved once, when the bone index is built
+        let mut joints: Vec<Joint> = (0..self.get_num_bones())
+            .map(|i| {
+                let current_bone = &self.base_seq[i];
+                let mut dummies: Vec<DummyObject> = Vec::new();
 
+                // dummies are still parsed when they are stripped, they are only left out of the joint tree
+                if !self.options.strip_dummies {
+                    for dummy in self.dummy_seq.get(&current_bone.id).into_iter().flatten() {
+                        dummies.push(DummyObject {
+                            id: dummy.id,
+                            parent_id: dummy.parent_bone_id,
+                            position_matrix: Some(self.get_dummy_local_matrix(dummy)),
+                        });
+                    }
+                }
 
+                Joint {
+                    parent: parents.get(i).copied().flatten(),
+                    children: Vec::new(),
+                    bone_name: self.get_bone_name(i),
+                    position_matrix: self.position_matrices.get(i).copied(),
+                    dummies,
+                }
+            })
+            .collect();
 
+        // link all the joints to their children, in the order of the bones in the file
+        for i in 0..joints.len() {
+            if let Some(parent_index) = joints[i].parent {
+                joints[parent_index].children.push(i);
+            }
+        }
 
+        (joints, self.get_root_bone_index())
+    }
 
+    /// checks that every id of the collada document is unique. the ids are built from the bone and clip names
+    /// with their spaces replaced, so different names can still end up with the same id, which importers
+    /// reject or silently resolve to the wrong element
+    pub fn validate_ids(&self) -> Result<(), LabError> {
+        let mut ids = self.get_animation_ids("");
+        ids.extend(self.get_skeleton_ids());
+        if let Some(clips) = &self.clips {
+            for clip in clips.clips.iter() {
+                ids.push(format!("{}-clip", clip.name.replace(" ", "_")));
+            }
+        }
 
+        check_unique_ids(ids)
+    }
 
-}
+    /// the ids of the scene, the skin and the nodes of the bones and dummies
+    fn get_skeleton_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = vec!["Scene".to_string(), "Skeleton".to_string()];
+        if self.skin_weights.is_some() {
+            let skin_ids = [
+                "Skin",
+                "Skin-joints",
+                "Skin-joints-array",
+                "Skin-bind_poses",
+                "Skin-bind_poses-array",
+                "Skin-weights",
+                "Skin-weights-array",
+            ];
+            ids.extend(skin_ids.iter().map(|id| id.to_string()));
+        }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        for bone_index in 0..self.get_num_bones() {
+            if !self.options.strip_dummies {
+                for dummy in self.dummy_seq.get(&self.base_seq[bone_index].id).into_iter().flatten() {
+                    ids.push(format!("Dummy_{}", dummy.id));
+                }
+            }
+            ids.push(self.get_bone_name(bone_index).replace(" ", "_"));
+        }
 
-    #[test]
-    fn it_loads_header_info_correctly() {
-        let mut bone = AnimDataBone::new();
-        let path = Path::new("./src/tests/anim-quat.lab");
-        let display = path.display();
+        ids
+    }
 
-        let mut file = match File::open(&path) {
-            Err(why) => panic!("Couldn\'t open {}: {}", display, why),
-            Ok(file) => file,
+    /// the ids of the animations of the bones and of their sources and samplers, each starting with `id_prefix`
+    fn get_animation_ids(&self, id_prefix: &str) -> Vec<String> {
+        let mut ids = Vec::new();
+        // a sampled animation's own id and the ids of its sources and sampler
+        let push_sampled_animation_ids = |ids: &mut Vec<String>, id: String| {
+            let mut suffixes = vec!["-input", "-input-array", "-output", "-output-array", "-sampler"];
+            if !self.options.no_interpolation_source {
+                suffixes.extend_from_slice(&["-interpolation", "-interpolation-array"]);
+            }
+            ids.extend(suffixes.iter().map(|suffix| format!("{}{}", id, suffix)));
+            ids.push(id);
         };
 
-        bone.load_header(&mut file);
-        assert_eq!(bone.header.bone_num, 35);
-        assert_eq!(bone.header.frame_num, 228);
-        assert_eq!(bone.header.dummy_num, 2);
-        assert_eq!(bone.header.key_type, BoneInfoKeyType::BoneKeyTypeQuaternion);
+        for bone_index in 0..self.get_num_bones() {
+            let id = format!("{}{}", id_prefix, self.get_bone_name(bone_index).replace(" ", "_"));
+            if self.options.euler_order.is_some() {
+                ids.push(format!("{}_euler", id));
+                push_sampled_animation_ids(&mut ids, format!("{}_location", id));
+                for axis_name in EULER_AXIS_NAMES.iter() {
+                    push_sampled_animation_ids(&mut ids, format!("{}_rotation{}", id, axis_name));
+                }
+            } else {
+                push_sampled_animation_ids(&mut ids, format!("{}_pose_matrix", id));
+            }
+        }
+
+        ids
     }
 
-    #[test]
-    fn it_loads_base_bone_info_correctly() {
-        let mut bone = AnimDataBone::new();
-        let path = Path::new("./src/tests/anim-quat.lab");
-        let display = path.display();
+    /// the matrix of a dummy relative to the bone it hangs off. a world-space matrix is brought into the
+    /// space of the bone with the bone's inverse bind matrix
+    fn get_dummy_local_matrix(&self, dummy: &BoneDummyInfo) -> Matrix4<f32> {
+        match self.options.dummy_space {
+            DummySpace::Local => dummy.mat.matrix,
+            DummySpace::World => match self.bone_index.index_by_id.get(&dummy.parent_bone_id) {
+                Some(&bone_index) => dummy.mat.matrix * self.invmat_seq[bone_index].matrix,
+                None => dummy.mat.matrix,
+            },
+        }
+    }
 
-        let mut file = match File::open(&path) {
-            Err(why) => panic!("Couldn\'t open {}: {}", display, why),
-            Ok(file) => file,
-        };
+    fn generate_position_matrices_at_rest(&mut self) {
+        let position_matrices = (0..self.get_num_bones())
+            .map(|i| self.get_key_matrix(i, 0))
+            .collect();
 
-        bone.load_header(&mut file);
-        bone.load_base_seq(&mut file);
+        self.position_matrices = position_matrices;
+    }
 
-        struct BoneBaseTestInfo {
-            pub id: u32,
-            pub parent_id: u32,
-            pub name: String,
+    fn generate_transformation_matrices_for_all_frames(&mut self) {
+        for i in 0..self.get_num_bones() {
+            let key_count = self.get_key_count(i);
+            if key_count < self.get_num_frames() {
+                self.warn(format!(
+                    "bone {} only has keys for {} of {} frames, the missing frames repeat its last key",
+                    self.get_bone_name(i),
+                    key_count,
+                    self.get_num_frames()
+                ));
+            }
         }
 
-        let actual_bones = vec![
+        // every bone's frames only depend on its own keys, so the bones can be generated in any order
+        #[cfg(feature = "parallel")]
+        let transformation_matrices = (0..self.get_num_bones())
+            .into_par_iter()
+            .map(|i| self.get_key_matrices(i))
+            .collect();
+        #[cfg(not(feature = "parallel"))]
+        let transformation_matrices = (0..self.get_num_bones()).map(|i| self.get_key_matrices(i)).collect();
+        self.transformation_matrices = transformation_matrices;
+    }
+
+    /// the matrices of every frame of a bone's keys
+    fn get_key_matrices(&self, bone_index: usize) -> Vec<Matrix4<f32>> {
+        (0..self.get_num_frames())
+            .map(|frame| self.get_key_matrix(bone_index, frame))
+            .collect()
+    }
+
+    /// number of frames the bone has keys for, which is less than the frame count in a malformed file
+    fn get_key_count(&self, bone_index: usize) -> usize {
+        match self.bone_keys(bone_index) {
+            BoneKeys::Mat43(keys) => keys.len(),
+            BoneKeys::Mat44(keys) => keys.len(),
+            BoneKeys::Quaternion { positions, rotations } => positions.len().min(rotations.len()),
+            BoneKeys::None => 0,
+        }
+    }
+
+    /// the matrix of a bone's key at the given frame. frames past the bone's last key use that last key,
+    /// and a bone without any keys stays at the identity
+    fn get_key_matrix(&self, bone_index: usize, frame: usize) -> Matrix4<f32> {
+        let key_count = self.get_key_count(bone_index);
+        if key_count == 0 {
+            return Matrix4::identity();
+        }
+        let frame = frame.min(key_count - 1);
+
+        match self.bone_keys(bone_index) {
+            BoneKeys::Quaternion { positions, rotations } => {
+                // quantized files store slightly non-unit quaternions, which would scale the bone
+                let rotation = rotations[frame];
+                let rotation = if self.options.keep_unnormalized_quats || rotation.magnitude2() == 0.0 {
+                    rotation
+                } else {
+                    rotation.normalize()
+                };
+                let matrix = Matrix4::from(rotation) * Matrix4::from_translation(positions[frame]);
+                match self.bone_scales(bone_index).and_then(|scales| scales.get(frame)) {
+                    Some(scale) => Matrix4::from_nonuniform_scale(scale.x, scale.y, scale.z) * matrix,
+                    None => matrix,
+                }
+            }
+            BoneKeys::Mat43(keys) => keys[frame].get_matrix4(),
+            BoneKeys::Mat44(keys) => keys[frame].matrix,
+            BoneKeys::None => Matrix4::identity(),
+        }
+    }
+
+    /// the handedness of the skeleton in the file. a bind matrix with a negative determinant mirrors
+    /// its bone, and the skeleton is left-handed when most of its bones are mirrored. a few mirrored
+    /// bones are common in right-handed skeletons too, e.g. the nubs of the 3ds max bipeds
+    pub fn handedness(&self) -> Handedness {
+        let mirrored_count = self
+            .invmat_seq
+            .iter()
+            .filter(|invmat| invmat.matrix.determinant() < 0.0)
+            .count();
+
+        if mirrored_count * 2 > self.invmat_seq.len() {
+            Handedness::Left
+        } else {
+            Handedness::Right
+        }
+    }
+
+    /// removes the bones named in `ignored_bones` and the ones deeper than `max_depth`, and reparents their
+    /// children and dummies to the closest ancestor that is kept. the local matrices of the reparented joints
+    /// absorb the ones of the bones removed in between, so every remaining joint keeps its world transform.
+    /// their keys are rewritten from those matrices, so `write_lab` saves the same animation as the export
+    fn prune_ignored_bones(&mut self) {
+        let root_index = self.get_root_bone_index();
+        let mut ignored = vec![false; self.get_num_bones()];
+        for name in self.options.ignored_bones.clone() {
+            match self.find_bone(&name) {
+                Some(index) if Some(index) == root_index => {
+                    self.warn(format!("bone {} is the root of the skeleton, it can't be ignored", name))
+                }
+                Some(index) => ignored[index] = true,
+                None => self.warn(format!("there is no bone named {} to ignore", name)),
+            }
+        }
+        if let Some(max_depth) = self.options.max_depth {
+            for (bone_index, ignored) in ignored.iter_mut().enumerate() {
+                *ignored |= self.bone_depth(bone_index) > max_depth;
+            }
+        }
+        if !ignored.contains(&true) {
+            return;
+        }
+
+        // the ignored bones from the given one up to the closest kept ancestor, along with that ancestor
+        let parents = self.get_parent_indices().to_vec();
+        let ignored_chain = |start: Option<usize>| -> (Vec<usize>, Option<usize>) {
+            let mut chain = Vec::new();
+            let mut bone = start;
+            while let Some(bone_index) = bone {
+                if !ignored[bone_index] || chain.len() > parents.len() {
+                    break;
+                }
+                chain.push(bone_index);
+                bone = parents[bone_index];
+            }
+            (chain, bone)
+        };
+        let fold = |matrices: &[Matrix4<f32>], chain: &[usize]| {
+            chain.iter().fold(Matrix4::identity(), |matrix, &bone_index| matrix * matrices[bone_index])
+        };
+
+        for bone_index in 0..self.get_num_bones() {
+            let (chain, ancestor) = ignored_chain(parents[bone_index]);
+            if ignored[bone_index] || chain.is_empty() {
+                continue;
+            }
+
+            let ignored_matrices: Vec<Matrix4<f32>> = (0..self.get_num_frames())
+                .map(|frame| {
+                    chain.iter().fold(Matrix4::identity(), |matrix, &ignored_index| {
+                        matrix * self.transformation_matrices[ignored_index][frame]
+                    })
+                })
+                .collect();
+            for (matrix, ignored_matrix) in self.transformation_matrices[bone_index].iter_mut().zip(ignored_matrices) {
+                *matrix = *matrix * ignored_matrix;
+            }
+            self.position_matrices[bone_index] = self.position_matrices[bone_index] * fold(&self.position_matrices, &chain);
+
+            if let Some(ancestor) = ancestor {
+                self.base_seq[bone_index].parent_id = self.base_seq[ancestor].id;
+            }
+            self.set_keys_from_matrices(bone_index);
+        }
+
+        // world-space dummies only change bone, local ones take the rest pose of the bones they are moved past
+        for bone_index in (0..self.get_num_bones()).filter(|&bone_index| ignored[bone_index]) {
+            let (chain, ancestor) = ignored_chain(Some(bone_index));
+            let dummies = self.dummy_seq.remove(&self.base_seq[bone_index].id).unwrap_or_default();
+            if let Some(ancestor) = ancestor {
+                let rest_matrix = fold(&self.position_matrices, &chain);
+                let ancestor_id = self.base_seq[ancestor].id;
+                for mut dummy in dummies {
+                    dummy.parent_bone_id = ancestor_id;
+                    if self.options.dummy_space == DummySpace::Local {
+                        dummy.mat.matrix = dummy.mat.matrix * rest_matrix;
+                    }
+                    self.dummy_seq.entry(ancestor_id).or_insert_with(Vec::new).push(dummy);
+                }
+            }
+        }
+
+        let mut kept = ignored.iter().map(|ignored| !ignored);
+        self.base_seq.retain(|_| kept.next().unwrap());
+        let mut kept = ignored.iter().map(|ignored| !ignored);
+        self.invmat_seq.retain(|_| kept.next().unwrap());
+        let mut kept = ignored.iter().map(|ignored| !ignored);
+        self.key_seq.retain(|_| kept.next().unwrap());
+        let mut kept = ignored.iter().map(|ignored| !ignored);
+        self.transformation_matrices.retain(|_| kept.next().unwrap());
+        let mut kept = ignored.iter().map(|ignored| !ignored);
+        self.position_matrices.retain(|_| kept.next().unwrap());
+        self.header.bone_num = self.base_seq.len() as u32;
+
+        self.build_bone_index();
+    }
+
+    /// removes the mirror of every bone whose rest matrix is mirrored, which flips the normals of the mesh
+    /// it skins in most importers. decomposing a mirrored matrix gives it a negative x scale, so its x axis
+    /// is flipped in its rest pose, its frames and its inverse bind matrix. the matrices of its children and
+    /// of its dummies are given in that axis, so they are flipped too and no joint moves nor does the skin
+    /// deform any differently. parents are fixed before their children, whose mirror may cancel out with
+    /// their parent's
+    fn fix_mirrored_bones(&mut self) {
+        let flip = Matrix4::from_nonuniform_scale(-1.0, 1.0, 1.0);
+        let mut bones: Vec<usize> = (0..self.get_num_bones()).collect();
+        bones.sort_by_key(|&bone| self.bone_depth(bone));
+
+        for bone in bones {
+            if self.position_matrices[bone].determinant() >= 0.0 {
+                continue;
+            }
+
+            self.position_matrices[bone] = flip * self.position_matrices[bone];
+            for matrix in self.transformation_matrices[bone].iter_mut() {
+                *matrix = flip * *matrix;
+            }
+            self.invmat_seq[bone].matrix = self.invmat_seq[bone].matrix * flip;
+
+            for &child in self.bone_index.children[bone].iter() {
+                self.position_matrices[child] = self.position_matrices[child] * flip;
+                for matrix in self.transformation_matrices[child].iter_mut() {
+                    *matrix = *matrix * flip;
+                }
+            }
+            self.set_keys_from_matrices(bone);
+            for child in self.bone_index.children[bone].clone() {
+                self.set_keys_from_matrices(child);
+            }
+            // dummies given in model space go through the inverse bind matrix, which is already flipped
+            if self.options.dummy_space == DummySpace::Local {
+                for dummy in self.dummy_seq.get_mut(&self.base_seq[bone].id).into_iter().flatten() {
+                    dummy.mat.matrix = dummy.mat.matrix * flip;
+                }
+            }
+
+            let name = self.get_bone_name(bone);
+            self.warn(format!("bone {} is mirrored, its x axis was flipped to remove the mirror", name));
+        }
+    }
+
+    /// replaces the keys of a bone with its matrices of every frame, in the key kind of the file. every step of
+    /// `prepare` that changes the frames of a bone after they are generated calls this, so the keys `write_lab`
+    /// writes always hold the exported frames. quaternion keys only hold a scale in the files that have a scale
+    /// block, the scale is dropped with a warning otherwise
+    fn set_keys_from_matrices(&mut self, bone_index: usize) {
+        let matrices = &self.transformation_matrices[bone_index];
+        let mut dropped_scale = false;
+        let key = &mut self.key_seq[bone_index];
+
+        match self.header.key_type {
+            BoneInfoKeyType::BoneKeyTypeMat43 => {
+                let keys = matrices
+                    .iter()
+                    .map(|matrix| {
+                        let rows = lwMatrix44 { matrix: *matrix }.to_rows();
+                        let mut key = lwMatrix43 { matrix: [[0.0; 3]; 4] };
+                        for (key_row, row) in key.matrix.iter_mut().zip(rows.iter()) {
+                            key_row.copy_from_slice(&row[..3]);
+                        }
+                        key
+                    })
+                    .collect();
+                key.mat43_seq = Some(keys);
+            }
+            BoneInfoKeyType::BoneKeyTypeMat44 => {
+                key.mat44_seq = Some(matrices.iter().map(|matrix| lwMatrix44 { matrix: *matrix }).collect());
+            }
+            BoneInfoKeyType::BoneKeyTypeQuaternion => {
+                let decomposed: Vec<_> = matrices.iter().map(decompose).collect();
+                key.pos_seq = Some(decomposed.iter().map(|(position, _, _)| *position).collect());
+                key.quat_seq = Some(decomposed.iter().map(|(_, rotation, _)| *rotation).collect());
+
+                let scales: Vec<Vector3<f32>> = decomposed.iter().map(|(_, _, scale)| *scale).collect();
+                if key.scale_seq.is_some() {
+                    key.scale_seq = Some(scales);
+                } else {
+                    let epsilon = self.options.epsilon.unwrap_or(DEFAULT_EPSILON);
+                    let one = Vector3::new(1.0, 1.0, 1.0);
+                    dropped_scale = scales.iter().any(|scale| (scale - one).magnitude() > epsilon);
+                }
+            }
+            BoneInfoKeyType::BoneKeyTypeInvalid => {}
+        }
+
+        if dropped_scale {
+            let name = self.get_bone_name(bone_index);
+            self.warn(format!(
+                "bone {} is scaled by the export options, which its quaternion keys can't hold",
+                name
+            ));
+        }
+    }
+
+    /// adds the translations of the root motion track to the frames of the root joint, and so to its keys. the
+    /// rest pose is left as it is, it holds the pose of the keys alone
+    fn apply_root_motion(&mut self) {
+        let (root_index, root_motion) = match (self.get_root_bone_index(), &self.root_motion) {
+            (Some(index), Some(root_motion)) => (index, root_motion),
+            _ => return,
+        };
+
+        // translation is stored in the last row, following the d3d convention of the game
+        for (matrix, translation) in self.transformation_matrices[root_index].iter_mut().zip(root_motion.iter()) {
+            matrix[0][3] += translation.x;
+            matrix[1][3] += translation.y;
+            matrix[2][3] += translation.z;
+        }
+        self.set_keys_from_matrices(root_index);
+    }
+
+    /// subtracts the root's frame-0 translation from the root joint's rest pose and from all of its frames.
+    /// only the root is offset, the child joints follow it through the hierarchy
+    fn center_root_motion(&mut self) {
+        let root_index = match self.get_root_bone_index() {
+            Some(index) => index,
+            None => return,
+        };
+
+        let root_frames = &mut self.transformation_matrices[root_index];
+        if root_frames.is_empty() {
+            return;
+        }
+
+        // translation is stored in the last row, following the d3d convention of the game
+        let offset = get_translation(&root_frames[0]);
+        for matrix in root_frames.iter_mut() {
+            matrix[0][3] -= offset.x;
+            matrix[1][3] -= offset.y;
+            matrix[2][3] -= offset.z;
+        }
+
+        let rest_matrix = &mut self.position_matrices[root_index];
+        rest_matrix[0][3] -= offset.x;
+        rest_matrix[1][3] -= offset.y;
+        rest_matrix[2][3] -= offset.z;
+        self.set_keys_from_matrices(root_index);
+    }
+
+    /// caches the id and name lookups and the parent/child links of the bones, so queries don't have to
+    /// scan the base sequence every time
+    fn build_bone_index(&mut self) {
+        let mut bone_index = BoneIndex::default();
+
+        for (index, bone) in self.base_seq.iter().enumerate() {
+            bone_index.index_by_id.insert(bone.id, index);
+            bone_index.index_by_name.insert(self.get_bone_name(index), index);
+        }
+
+        bone_index.children = vec![Vec::new(); self.base_seq.len()];
+        for (index, bone) in self.base_seq.iter().enumerate() {
+            let parent = bone_index.index_by_id.get(&bone.parent_id).copied();
+            if let Some(parent_index) = parent {
+                bone_index.children[parent_index].push(index);
+            }
+            bone_index.parents.push(parent);
+        }
+
+        // walks up to the closest ancestor whose depth is known, or to the root. on a loop the topmost bone
+        // of the walk is given a depth of 0
+        let mut depths: Vec<Option<usize>> = vec![None; self.base_seq.len()];
+        for index in 0..self.base_seq.len() {
+            let mut chain = vec![index];
+            let mut parent = bone_index.parents[index];
+            while let Some(parent_index) = parent {
+                if depths[parent_index].is_some() || chain.contains(&parent_index) {
+                    break;
+                }
+                chain.push(parent_index);
+                parent = bone_index.parents[parent_index];
+            }
+
+            let mut depth = parent.and_then(|parent_index| depths[parent_index]).map(|depth| depth + 1).unwrap_or(0);
+            for &bone in chain.iter().rev() {
+                if depths[bone].is_none() {
+                    depths[bone] = Some(depth);
+                }
+                depth = depths[bone].unwrap() + 1;
+            }
+        }
+        bone_index.depths = depths.into_iter().map(|depth| depth.unwrap_or(0)).collect();
+
+        self.bone_index = bone_index;
+    }
+
+    /// the number of ancestors of the bone, 0 for the root. it is computed along with the hierarchy, and
+    /// stays finite on a malformed hierarchy where the parents loop back
+    pub fn bone_depth(&self, bone: usize) -> usize {
+        self.bone_index.depths[bone]
+    }
+
+    /// index of every bone's parent, `None` for the root or when the parent id doesn't match any bone
+    fn get_parent_indices(&self) -> &[Option<usize>] {
+        &self.bone_index.parents
+    }
+
+    /// index of the bone with the given name
+    pub fn find_bone(&self, name: &str) -> Option<usize> {
+        self.bone_index.index_by_name.get(name).copied()
+    }
+
+    /// indices of the bones directly parented to the given bone
+    pub fn get_bone_children(&self, bone_index: usize) -> &[usize] {
+        match self.bone_index.children.get(bone_index) {
+            Some(children) => children,
+            None => &[],
+        }
+    }
+
+    /// indices of the bone and its ancestors, from the bone itself up to the root. on a malformed hierarchy
+    /// where the parents loop back, the chain stops before the first bone that would repeat
+    pub fn ancestors(&self, bone_index: usize) -> Vec<usize> {
+        let parents = self.get_parent_indices();
+        let mut chain = vec![bone_index];
+
+        let mut parent = parents.get(bone_index).copied().flatten();
+        while let Some(parent_index) = parent {
+            if chain.contains(&parent_index) {
+                break;
+            }
+            chain.push(parent_index);
+            parent = parents[parent_index];
+        }
+
+        chain
+    }
+
+    /// world-space matrices of all the bones at the given frame. following the d3d convention of the game,
+    /// a joint's world matrix is its local matrix multiplied by its parent's world matrix
+    pub fn get_world_transforms_for_frame(&self, frame: usize) -> Vec<Matrix4<f32>> {
+        let parents = self.get_parent_indices();
+        let mut world_matrices: Vec<Option<Matrix4<f32>>> = vec![None; self.get_num_bones()];
+
+        for i in 0..self.get_num_bones() {
+            // walk up to the closest ancestor whose world matrix is known, bailing out on cycles
+            let mut chain = vec![i];
+            let mut parent = parents[i];
+            while let Some(parent_index) = parent {
+                if world_matrices[parent_index].is_some() || chain.len() > parents.len() {
+                    break;
+                }
+                chain.push(parent_index);
+                parent = parents[parent_index];
+            }
+
+            let mut parent_matrix = parent.and_then(|parent_index| world_matrices[parent_index]);
+            for &bone_index in chain.iter().rev() {
+                if world_matrices[bone_index].is_some() {
+                    parent_matrix = world_matrices[bone_index];
+                    continue;
+                }
+
+                let local_matrix = self.transformation_matrices[bone_index][frame];
+                let world_matrix = match parent_matrix {
+                    Some(parent_matrix) => local_matrix * parent_matrix,
+                    None => local_matrix,
+                };
+                world_matrices[bone_index] = Some(world_matrix);
+                parent_matrix = Some(world_matrix);
+            }
+        }
+
+        world_matrices
+            .into_iter()
+            .map(|matrix| matrix.unwrap_or_else(Matrix4::identity))
+            .collect()
+    }
+
+    /// world-space matrices of all the bones at the given frame, expressed relative to the reference bone
+    /// instead of the root, i.e. each world matrix multiplied by the inverse of the reference's world matrix.
+    /// the reference bone itself gets the identity. `None` if the frame or the reference bone is out of range
+    pub fn transforms_relative_to(&self, frame: usize, reference_bone: usize) -> Option<Vec<Matrix4<f32>>> {
+        if frame >= self.get_num_frames() || reference_bone >= self.get_num_bones() {
+            return None;
+        }
+
+        let world_matrices = self.get_world_transforms_for_frame(frame);
+        let reference_inverse = world_matrices[reference_bone]
+            .invert()
+            .unwrap_or_else(Matrix4::identity);
+
+        let relative_matrices = world_matrices
+            .iter()
+            .map(|world_matrix| world_matrix * reference_inverse)
+            .collect();
+        Some(relative_matrices)
+    }
+
+    /// min and max corners of the box containing every joint over the whole animation
+    pub fn bounds(&self) -> (Vector3<f32>, Vector3<f32>) {
+        let mut min = Vector3::new(f32::MAX, f32::MAX, f32::MAX);
+        let mut max = Vector3::new(f32::MIN, f32::MIN, f32::MIN);
+
+        for frame in 0..self.get_num_frames() {
+            for matrix in self.get_world_transforms_for_frame(frame) {
+                let position = get_translation(&matrix);
+                min = Vector3::new(min.x.min(position.x), min.y.min(position.y), min.z.min(position.z));
+                max = Vector3::new(max.x.max(position.x), max.y.max(position.y), max.z.max(position.z));
+            }
+        }
+
+        if min.x > max.x {
+            return (Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 0.0));
+        }
+
+        (min, max)
+    }
+
+    /// multiplies the transform into the root joint's rest pose and frames, which carries it over to the
+    /// whole skeleton
+    fn apply_root_transform(&mut self, root_transform: Matrix4<f32>) {
+        let root_index = match self.get_root_bone_index() {
+            Some(index) => index,
+            None => return,
+        };
+
+        for matrix in self.transformation_matrices[root_index].iter_mut() {
+            *matrix = *matrix * root_transform;
+        }
+        self.position_matrices[root_index] = self.position_matrices[root_index] * root_transform;
+        self.set_keys_from_matrices(root_index);
+    }
+
+    /// local matrices of all the bones at the given frame, in file order. they are computed once when the
+    /// animation is loaded, see `transforms`
+    pub fn get_transforms_for_frame(&self, frame: usize) -> Vec<Matrix4<f32>> {
+        self.transformation_matrices.iter().map(|frames| frames[frame]).collect()
+    }
+
+    /// the local matrix of a bone at the given frame, as the only element of the vector
+    pub fn get_transforms_for_frame_and_bone(&self, frame: usize, bone: usize) -> Vec<Matrix4<f32>> {
+        vec![self.transformation_matrices[bone][frame]]
+    }
+
+    /// world-space positions of the joints posed by the given local matrices, one per bone in file order such
+    /// as a frame of `transforms`. each joint's matrix is composed with its ancestors' so
+    /// children follow their parents
+    pub fn apply_transforms(&self, transforms: Vec<Matrix4<f32>>) -> Vec<Vector3<f32>> {
+        (0..self.get_num_bones())
+            .map(|i| get_translation(&self.get_world_matrix(i, |j| transforms[j])))
+            .collect()
+    }
+
+    fn write_collada_data(&self, joints: &[Joint], root_index: Option<usize>) -> String {
+        let mut writer = self.start_collada_document();
+        self.write_skeleton_libraries(&mut writer, joints, root_index);
+        self.write_animation_libraries(&mut writer);
+        self.write_scene_element(&mut writer);
+
+        writer.end_element();
+
+        let content = writer.end_document();
+        content
+    }
+
+    /// the collada document of the skeleton alone: the joint hierarchy in its bind pose and the skin, without
+    /// any animation. see `to_animation_collada` for the other half
+    pub fn to_skeleton_collada(&self) -> String {
+        let (joints, root_index) = self.generate_joint_structure();
+
+        let mut writer = self.start_collada_document();
+        self.write_skeleton_libraries(&mut writer, &joints, root_index);
+        self.write_scene_element(&mut writer);
+
+        writer.end_element();
+
+        writer.end_document()
+    }
+
+    /// the collada document of the animation alone, for engines that bind animations to a separately loaded
+    /// skeleton. its channels target the joints of `to_skeleton_collada` by their ids
+    pub fn to_animation_collada(&self) -> String {
+        let mut writer = self.start_collada_document();
+        self.write_animation_libraries(&mut writer);
+
+        writer.end_element();
+
+        writer.end_document()
+    }
+
+    /// writes the xml prolog and the collada document of the skeleton alone, see `to_skeleton_collada`
+    pub fn write_skeleton_collada<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(XML_PROLOG.as_bytes())?;
+        writer.write_all(self.to_skeleton_collada().as_bytes())
+    }
+
+    /// writes the xml prolog and the collada document of the animation alone, see `to_animation_collada`
+    pub fn write_animation_collada<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(XML_PROLOG.as_bytes())?;
+        writer.write_all(self.to_animation_collada().as_bytes())
+    }
+
+    /// the skin controller, if there is one, and the visual scene holding the joints
+    fn write_skeleton_libraries(&self, writer: &mut XmlWriter, joints: &[Joint], root_index: Option<usize>) {
+        if let Some(skin_weights) = &self.skin_weights {
+            self.write_controller_data(writer, skin_weights);
+        }
+        self.write_visual_scene_data(writer, joints, root_index);
+    }
+
+    /// the animations of the joints and the clips splitting them into actions, if there are any
+    fn write_animation_libraries(&self, writer: &mut XmlWriter) {
+        self.write_animation_data(writer);
+        if let Some(clips) = &self.clips {
+            self.write_animation_clips_data(writer, clips);
+        }
+    }
+
+    /// writes the animations of all the joints nested in a single animation with the given id, which prefixes
+    /// their own ids, for documents holding several animations of the same skeleton
+    fn write_animation_group(&self, writer: &mut XmlWriter, id: &str) {
+        writer.start_element("animation");
+        writer.write_attribute("id", id);
+        writer.write_attribute("name", id);
+        for i in 0..self.get_num_bones() {
+            self.write_animation_element(writer, i, &format!("{}_", id));
+        }
+        writer.end_element();
+    }
+
+    /// writes a minimal collada document containing only the given bone's node and its animation
+    pub fn write_bone_collada_data(&self, bone_index: usize) -> String {
+        let mut writer = self.start_collada_document();
+
+        writer.start_element("library_visual_scenes");
+        writer.start_element("visual_scene");
+        writer.write_attribute("id", "Scene");
+        writer.write_attribute("name", "Scene");
+
+        writer.start_element("node");
+        writer.write_attribute("id", "Skeleton");
+        writer.write_attribute("name", "Skeleton");
+        writer.write_attribute("type", "NODE");
+
+        let bone_name = self.get_bone_name(bone_index);
+        writer.start_element("node");
+        writer.write_attribute("id", &bone_name.replace(" ", "_"));
+        writer.write_attribute("sid", &bone_name.replace(" ", "_"));
+        writer.write_attribute("name", &bone_name);
+        writer.write_attribute("type", "JOINT");
+        let bind_matrix = if self.options.world_space {
+            self.get_world_matrix(bone_index, |i| self.position_matrices[i])
+        } else {
+            self.position_matrices
+                .get(bone_index)
+                .copied()
+                .unwrap_or_else(Matrix4::identity)
+        };
+        self.write_node_transform(&mut writer, bind_matrix);
+        writer.end_element();
+
+        writer.end_element();
+        writer.end_element();
+        writer.end_element();
+
+        writer.start_element("library_animations");
+        self.write_animation_element(&mut writer, bone_index, "");
+        writer.end_element();
+
+        self.write_scene_element(&mut writer);
+
+        writer.end_element();
+
+        writer.end_document()
+    }
+
+    /// a writer with the `COLLADA` root element and the asset data written, for the libraries of a
+    /// document to be written into
+    fn start_collada_document(&self) -> XmlWriter {
+        let options = Options {
+            use_single_quote: false,
+            ..Default::default()
+        };
+
+        let mut writer = XmlWriter::new(options);
+        writer.start_element("COLLADA");
+        writer.write_attribute("xmlns", "http://www.collada.org/2005/11/COLLADASchema");
+        writer.write_attribute("version", "1.4.1");
+
+        self.write_asset_data(&mut writer);
+        writer
+    }
+
+    fn write_asset_data(&self, writer: &mut XmlWriter) {
+        // asset tag
+        writer.start_element("asset");
+
+        writer.start_element("contributor");
+        writer.start_element("author");
+        writer.write_text("Perseus");
+        writer.end_element();
+        writer.start_element("authoring_tool");
+        writer.write_text(&self.get_authoring_tool());
+        writer.end_element();
+        writer.end_element();
+
+        // created-at tag
+        writer.start_element("created");
+        writer.write_text(&chrono::Utc::now().to_string());
+        writer.end_element();
+
+        // up-axis, the game is z up unless the axes are remapped
+        let up_axis = self.options.axis_map.and_then(|axis_map| axis_map.up_axis()).unwrap_or("Z_UP");
+        writer.start_element("up_axis");
+        writer.write_text(up_axis);
+        writer.end_element();
+        
+        // end asset
+        writer.end_element();
+    }
+
+    /// the program the documents are credited to, for tools tracking where a file came from
+    fn get_authoring_tool(&self) -> String {
+        match &self.options.authoring_tool {
+            Some(authoring_tool) => authoring_tool.clone(),
+            None => format!("lab-parser {}", env!("CARGO_PKG_VERSION")),
+        }
+    }
+
+    /// writes a skin controller binding the joints to the vertices of the mesh with the id `Mesh`,
+    /// which has to be merged into the document alongside it
+    fn write_controller_data(&self, writer: &mut XmlWriter, skin_weights: &SkinWeights) {
+        let bone_count = self.get_num_bones();
+        // weights are written once each and referenced by their index from the vertex weights
+        let weights: Vec<f32> = skin_weights
+            .vertices
+            .iter()
+            .flatten()
+            .map(|(_, weight)| *weight)
+            .collect();
+
+        writer.start_element("library_controllers");
+        writer.start_element("controller");
+        writer.write_attribute("id", "Skin");
+        writer.write_attribute("name", "Skin");
+        writer.start_element("skin");
+        writer.write_attribute("source", "#Mesh");
+
+        writer.start_element("bind_shape_matrix");
+        writer.write_text("1 0 0 0 0 1 0 0 0 0 1 0 0 0 0 1");
+        writer.end_element();
+
+        writer.start_element("source");
+        writer.write_attribute("id", "Skin-joints");
+        writer.start_element("Name_array");
+        writer.write_attribute("id", "Skin-joints-array");
+        writer.write_attribute("count", &bone_count);
+        let joint_names: Vec<String> = (0..bone_count)
+            .map(|i| self.get_bone_name(i).replace(" ", "_"))
+            .collect();
+        writer.write_text(&joint_names.join(" "));
+        writer.end_element();
+        writer.start_element("technique_common");
+        writer.start_element("accessor");
+        writer.write_attribute("source", "#Skin-joints-array");
+        writer.write_attribute("count", &bone_count);
+        writer.write_attribute("stride", &1);
+        writer.start_element("param");
+        writer.write_attribute("name", "JOINT");
+        writer.write_attribute("type", "name");
+        writer.end_element();
+        writer.end_element();
+        writer.end_element();
+        writer.end_element();
+
+        writer.start_element("source");
+        writer.write_attribute("id", "Skin-bind_poses");
+        writer.start_element("float_array");
+        writer.write_attribute("id", "Skin-bind_poses-array");
+        writer.write_attribute("count", &(16 * bone_count));
+        self.write_all_matrices(writer, &self.get_inverse_bind_matrices());
+        writer.end_element();
+        writer.start_element("technique_common");
+        writer.start_element("accessor");
+        writer.write_attribute("source", "#Skin-bind_poses-array");
+        writer.write_attribute("count", &bone_count);
+        writer.write_attribute("stride", &16);
+        writer.start_element("param");
+        writer.write_attribute("name", "TRANSFORM");
+        writer.write_attribute("type", "float4x4");
+        writer.end_element();
+        writer.end_element();
+        writer.end_element();
+        writer.end_element();
+
+        writer.start_element("source");
+        writer.write_attribute("id", "Skin-weights");
+        writer.start_element("float_array");
+        writer.write_attribute("id", "Skin-weights-array");
+        writer.write_attribute("count", &weights.len());
+        let weight_values: Vec<String> = weights.iter().map(|weight| weight.to_string()).collect();
+        writer.write_text(&weight_values.join(" "));
+        writer.end_element();
+        writer.start_element("technique_common");
+        writer.start_element("accessor");
+        writer.write_attribute("source", "#Skin-weights-array");
+        writer.write_attribute("count", &weights.len());
+        writer.write_attribute("stride", &1);
+        writer.start_element("param");
+        writer.write_attribute("name", "WEIGHT");
+        writer.write_attribute("type", "float");
+        writer.end_element();
+        writer.end_element();
+        writer.end_element();
+        writer.end_element();
+
+        writer.start_element("joints");
+        writer.start_element("input");
+        writer.write_attribute("semantic", "JOINT");
+        writer.write_attribute("source", "#Skin-joints");
+        writer.end_element();
+        writer.start_element("input");
+        writer.write_attribute("semantic", "INV_BIND_MATRIX");
+        writer.write_attribute("source", "#Skin-bind_poses");
+        writer.end_element();
+        writer.end_element();
+
+        writer.start_element("vertex_weights");
+        writer.write_attribute("count", &skin_weights.vertices.len());
+        writer.start_element("input");
+        writer.write_attribute("semantic", "JOINT");
+        writer.write_attribute("source", "#Skin-joints");
+        writer.write_attribute("offset", &0);
+        writer.end_element();
+        writer.start_element("input");
+        writer.write_attribute("semantic", "WEIGHT");
+        writer.write_attribute("source", "#Skin-weights");
+        writer.write_attribute("offset", &1);
+        writer.end_element();
+
+        writer.start_element("vcount");
+        let vcounts: Vec<String> = skin_weights.vertices.iter()
+            .map(|influences| influences.len().to_string())
+            .collect();
+        writer.write_text(&vcounts.join(" "));
+        writer.end_element();
+
+        writer.start_element("v");
+        let mut weight_index = 0;
+        let mut indices = Vec::new();
+        for influences in skin_weights.vertices.iter() {
+            for (bone_index, _) in influences.iter() {
+                indices.push(format!("{} {}", bone_index, weight_index));
+                weight_index += 1;
+            }
+        }
+        writer.write_text(&indices.join(" "));
+        writer.end_element();
+
+        writer.end_element();
+        writer.end_element();
+        writer.end_element();
+        writer.end_element();
+    }
+
+    fn write_visual_scene_data(&self, writer: &mut XmlWriter, joints: &[Joint], root_index: Option<usize>) {
+        writer.start_element("library_visual_scenes");
+
+        // start the visual scene tag
+        writer.start_element("visual_scene");
+        writer.write_attribute("id", "Scene");
+        writer.write_attribute("name", "Scene");
+
+        // base skeleton node
+        writer.start_element("node");
+        writer.write_attribute("id", "Skeleton");
+        writer.write_attribute("name", "Skeleton");
+        writer.write_attribute("type", "NODE");
+
+        if self.options.world_space {
+            for i in 0..self.get_num_bones() {
+                self.write_world_joint_node(writer, joints, i);
+            }
+        } else if let Some(root_index) = root_index {
+            self.write_joint_node(writer, joints, root_index);
+        }
+
+        writer.end_element();
+        writer.end_element();
+
+        writer.end_element();
+    }
+
+    /// writes a joint directly under the skeleton node with its world-space bind matrix, for the flat
+    /// hierarchy of a world-space export. its dummies stay attached to it
+    fn write_world_joint_node(&self, writer: &mut XmlWriter, joints: &[Joint], bone_index: usize) {
+        let bone_name = self.get_bone_name(bone_index);
+        let joint = &joints[bone_index];
+
+        writer.start_element("node");
+        writer.write_attribute("id", &bone_name.replace(" ", "_"));
+        writer.write_attribute("sid", &bone_name.replace(" ", "_"));
+        writer.write_attribute("name", &bone_name);
+        writer.write_attribute("type", "JOINT");
+
+        let world_matrix =
+            self.get_world_matrix(bone_index, |i| joints[i].position_matrix.unwrap_or_else(Matrix4::identity));
+        self.write_node_transform(writer, world_matrix);
+
+        for dummy in joint.dummies.iter() {
+            self.write_dummy_node(writer, dummy);
+        }
+
+        write_extra_technique(writer, "layer", "bone");
+        writer.end_element();
+    }
+
+    fn write_joint_node(&self, writer: &mut XmlWriter, joints: &[Joint], joint_index: usize) {
+        let joint_data = &joints[joint_index];
+        writer.start_element("node");
+        writer.write_attribute("id", &joint_data.bone_name.replace(" ", "_"));
+        writer.write_attribute("sid", &joint_data.bone_name.replace(" ", "_"));
+        writer.write_attribute("name", &joint_data.bone_name);
+        writer.write_attribute("type", "JOINT");
+
+        self.write_node_transform(
+            writer,
+            joint_data.position_matrix.unwrap_or_else(Matrix4::identity),
+        );
+
+        // dummies are children of the joint node, so they follow its animation with their matrix as an
+        // offset from it
+        if joint_data.dummies.len() > 0 {
+            for i in 0..joint_data.dummies.len() {
+                self.write_dummy_node(writer, &joint_data.dummies[i]);
+            }
+        }
+
+        for child_index in joint_data.children.iter() {
+            self.write_joint_node(writer, joints, *child_index);
+        }
+
+        write_extra_technique(writer, "layer", "bone");
+        writer.end_element();
+    }
+
+    fn write_dummy_node(&self, writer: &mut XmlWriter, dummy: &DummyObject) {
+        writer.start_element("node");
+        writer.write_attribute("id", &format!("Dummy_{}", dummy.id));
+        writer.write_attribute("name", &format!("Dummy {}", dummy.id));
+        writer.write_attribute("type", "NODE");
+        self.write_matrix(writer, dummy.position_matrix.unwrap());
+
+        write_extra_technique(writer, "layer", "attachment");
+        writer.end_element();
+    }
+
+    fn write_animation_data(&self, writer: &mut XmlWriter) {
+      writer.start_element("library_animations");
+
+      for i in 0..self.get_num_bones() {
+        self.write_animation_element(writer, i, "");
+      }
+
+      writer.end_element();
+    }
+
+    /// writes the animation of a bone. `id_prefix` is prepended to the ids of its elements, to tell apart the
+    /// animations of the same bone in a merged document
+    fn write_animation_element(&self, writer: &mut XmlWriter, bone_index: usize, id_prefix: &str) {
+      if let Some(euler_order) = self.options.euler_order {
+        self.write_euler_animation_element(writer, bone_index, euler_order, id_prefix);
+        return;
+      }
+
+      let sanitized_bone_name = self.get_bone_name(bone_index).replace(" ", "_");
+      let frame_matrices = self.get_exported_frame_matrices(bone_index);
+      let values: Vec<Vec<f32>> = frame_matrices.iter().map(get_matrix_values).collect();
+
+      self.write_sampled_animation(
+        writer,
+        &format!("{}{}_pose_matrix", id_prefix, sanitized_bone_name),
+        &format!("{}/{}", sanitized_bone_name, MATRIX_SID),
+        &values,
+        &[("TRANSFORM", "float4x4")],
+      );
+    }
+
+    /// writes the animation of a bone as a translation and a rotation angle per axis, each in its own
+    /// animation nested in the bone's. the angles are unwrapped across frames so the curves stay continuous
+    fn write_euler_animation_element(
+      &self,
+      writer: &mut XmlWriter,
+      bone_index: usize,
+      euler_order: EulerOrder,
+      id_prefix: &str,
+    ) {
+      let sanitized_bone_name = self.get_bone_name(bone_index).replace(" ", "_");
+      let id = format!("{}{}", id_prefix, sanitized_bone_name);
+      let frame_matrices = self.get_exported_frame_matrices(bone_index);
+      let mut frame_angles: Vec<[f32; 3]> = frame_matrices
+        .iter()
+        .map(|matrix| to_euler_angles(matrix, euler_order))
+        .collect();
+      unwrap_euler_angles(&mut frame_angles, euler_order);
+
+      writer.start_element("animation");
+      writer.write_attribute("id", &format!("{}_euler", id));
+      writer.write_attribute("name", &format!("{}_euler", id));
+
+      let locations: Vec<Vec<f32>> = frame_matrices
+        .iter()
+        .map(|matrix| {
+          let translation = get_translation(matrix);
+          vec![translation.x, translation.y, translation.z]
+        })
+        .collect();
+      self.write_sampled_animation(
+        writer,
+        &format!("{}_location", id),
+        &format!("{}/{}", sanitized_bone_name, LOCATION_SID),
+        &locations,
+        &[("X", "float"), ("Y", "float"), ("Z", "float")],
+      );
+
+      for &axis in euler_order.axes().iter().rev() {
+        let axis_name = EULER_AXIS_NAMES[axis];
+        let angles: Vec<Vec<f32>> = frame_angles.iter().map(|angles| vec![angles[axis]]).collect();
+        self.write_sampled_animation(
+          writer,
+          &format!("{}_rotation{}", id, axis_name),
+          &format!("{}/{}.ANGLE", sanitized_bone_name, get_rotation_sid(axis)),
+          &angles,
+          &[("ANGLE", "float")],
+        );
+      }
+
+      writer.end_element();
+    }
+
+    /// writes an animation sampling a value at every exported frame with linear interpolation. each of the
+    /// `values` is one frame's value, made of as many numbers as there are `params`, except for a matrix
+    /// param which takes 16. with `reduce_keys` only the frames where the value changes are written, and with
+    /// `no_interpolation_source` the sampler leaves the interpolation to the importer's default
+    fn write_sampled_animation(
+      &self,
+      writer: &mut XmlWriter,
+      id: &str,
+      target: &str,
+      values: &[Vec<f32>],
+      params: &[(&str, &str)],
+    ) {
+      let frame_times = self.frame_times();
+      let kept_frames = if self.options.reduce_keys {
+        get_changing_frames(values, self.get_epsilon())
+      } else {
+        (0..frame_times.len()).collect()
+      };
+      let frame_times: Vec<f32> = kept_frames.iter().map(|&frame| frame_times[frame]).collect();
+      let values: Vec<String> = kept_frames.iter().map(|&frame| self.format_values(&values[frame])).collect();
+      let frame_count = frame_times.len();
+      let stride: usize = params
+        .iter()
+        .map(|(_, param_type)| if *param_type == "float4x4" { 16 } else { 1 })
+        .sum();
+
+      writer.start_element("animation");
+      writer.write_attribute("id", id);
+      writer.write_attribute("name", id);
+
+      writer.start_element("source");
+      writer.write_attribute("id", &format!("{}-input", id));
+
+      writer.start_element("float_array");
+      writer.write_attribute("id", &format!("{}-input-array", id));
+      writer.write_attribute("count", &frame_count);
+      let time_values: Vec<String> = frame_times.iter().map(|time| time.to_string()).collect();
+      writer.write_text(&time_values.join(" "));
+      writer.end_element();
+
+      writer.start_element("technique_common");
+      writer.start_element("accessor");
+      writer.write_attribute("source", &format!("#{}-input-array", id));
+      writer.write_attribute("count", &frame_count);
+      writer.write_attribute("stride", &1);
+      writer.start_element("param");
+      writer.write_attribute("name", "TIME");
+      writer.write_attribute("type", "float");
+      writer.end_element();
+      writer.end_element();
+      writer.end_element();
+      writer.end_element();
+
+      writer.start_element("source");
+      writer.write_attribute("id", &format!("{}-output", id));
+      writer.start_element("float_array");
+      writer.write_attribute("id", &format!("{}-output-array", id));
+      writer.write_attribute("count", &(stride * frame_count));
+      writer.write_text(&values.join(" "));
+      writer.end_element();
+      writer.start_element("technique_common");
+      writer.start_element("accessor");
+      writer.write_attribute("source", &format!("#{}-output-array", id));
+      writer.write_attribute("count", &frame_count);
+      writer.write_attribute("stride", &stride);
+      for (param_name, param_type) in params.iter() {
+        writer.start_element("param");
+        writer.write_attribute("name", param_name);
+        writer.write_attribute("type", param_type);
+        writer.end_element();
+      }
+      writer.end_element();
+      writer.end_element();
+      writer.end_element();
+
+      if !self.options.no_interpolation_source {
+        writer.start_element("source");
+        writer.write_attribute("id", &format!("{}-interpolation", id));
+        writer.start_element("Name_array");
+        writer.write_attribute("id", &format!("{}-interpolation-array", id));
+        writer.write_attribute("count", &frame_count);
+        writer.write_text(&vec!["LINEAR"; frame_count].join(" "));
+        writer.end_element();
+        writer.start_element("technique_common");
+        writer.start_element("accessor");
+        writer.write_attribute("source", &format!("#{}-interpolation-array", id));
+        writer.write_attribute("count", &frame_count);
+        writer.write_attribute("stride", &1);
+        writer.start_element("param");
+        writer.write_attribute("name", "INTERPOLATION");
+        writer.write_attribute("type", "name");
+        writer.end_element();
+        writer.end_element();
+        writer.end_element();
+        writer.end_element();
+      }
+
+      writer.start_element("sampler");
+      writer.write_attribute("id", &format!("{}-sampler", id));
+      writer.start_element("input");
+      writer.write_attribute("semantic", "INPUT");
+      writer.write_attribute("source", &format!("#{}-input", id));
+      writer.end_element();
+      writer.start_element("input");
+      writer.write_attribute("semantic", "OUTPUT");
+      writer.write_attribute("source", &format!("#{}-output", id));
+      writer.end_element();
+      if !self.options.no_interpolation_source {
+        writer.start_element("input");
+        writer.write_attribute("semantic", "INTERPOLATION");
+        writer.write_attribute("source", &format!("#{}-interpolation", id));
+        writer.end_element();
+      }
+      writer.end_element();
+
+      writer.start_element("channel");
+      writer.write_attribute("source", &format!("#{}-sampler", id));
+      writer.write_attribute("target", target);
+      writer.end_element();
+
+      writer.end_element();
+    }
+
+    /// writes an animation clip per action, each playing the animations of all the bones over the
+    /// action's time range
+    fn write_animation_clips_data(&self, writer: &mut XmlWriter, clips: &AnimationClips) {
+      // the injected bind pose shifts the animation by one frame
+      let frame_offset = if self.options.inject_bind_pose { 1 } else { 0 };
+      let frames_per_second = self.get_frames_per_second();
+
+      writer.start_element("library_animation_clips");
+      for clip in clips.clips.iter() {
+        let sanitized_clip_name = clip.name.replace(" ", "_");
+        writer.start_element("animation_clip");
+        writer.write_attribute("id", &format!("{}-clip", sanitized_clip_name));
+        writer.write_attribute("name", &clip.name);
+        let time_offset = self.get_time_offset();
+        writer.write_attribute("start", &(time_offset + (clip.start + frame_offset) as f32 / frames_per_second));
+        writer.write_attribute("end", &(time_offset + (clip.end + frame_offset) as f32 / frames_per_second));
+
+        for i in 0..self.get_num_bones() {
+          let sanitized_bone_name = self.get_bone_name(i).replace(" ", "_");
+          writer.start_element("instance_animation");
+          let animation_id = match self.options.euler_order {
+            Some(_) => format!("#{}_euler", sanitized_bone_name),
+            None => format!("#{}_pose_matrix", sanitized_bone_name),
+          };
+          writer.write_attribute("url", &animation_id);
+          writer.end_element();
+        }
+
+        writer.end_element();
+      }
+      writer.end_element();
+    }
+
+    /// number of frames of the animation that are exported, which `max_frames` can cap
+    pub(crate) fn get_exported_animation_frame_count(&self) -> usize {
+        match self.options.max_frames {
+            Some(max_frames) => self.get_num_frames().min(max_frames),
+            None => self.get_num_frames(),
+        }
+    }
+
+    /// the matrices of the exported keyframes of a bone, preceded by its rest pose if it is being injected.
+    /// they are baked into world space for a world-space export
+    fn get_exported_frame_matrices(&self, bone_index: usize) -> Vec<Matrix4<f32>> {
+        let mut matrices = Vec::with_capacity(self.get_num_frames() + 1);
+        let frame_count = self.get_exported_animation_frame_count();
+
+        if self.options.world_space {
+            if self.options.inject_bind_pose {
+                matrices.push(self.get_world_matrix(bone_index, |i| self.position_matrices[i]));
+            }
+            for frame in 0..frame_count {
+                matrices.push(self.get_world_matrix(bone_index, |i| self.transformation_matrices[i][frame]));
+            }
+            return matrices;
+        }
+
+        if self.options.inject_bind_pose {
+            matrices.push(self.position_matrices[bone_index]);
+        }
+        matrices.extend_from_slice(&self.transformation_matrices[bone_index][..frame_count]);
+
+        matrices
+    }
+
+    /// multiplies the local matrices of a bone and its ancestors into its world-space matrix
+    fn get_world_matrix<F: Fn(usize) -> Matrix4<f32>>(&self, bone_index: usize, local_matrix: F) -> Matrix4<f32> {
+        self.ancestors(bone_index)
+            .into_iter()
+            .fold(Matrix4::identity(), |world_matrix, i| world_matrix * local_matrix(i))
+    }
+
+    fn write_scene_element(&self, writer: &mut XmlWriter) {
+        writer.start_element("scene");
+        writer.start_element("instance_visual_scene");
+        writer.write_attribute("url", "#Scene");
+        writer.end_element();
+        writer.end_element();
+    }
+
+    /// writes the transform of a joint node: its matrix, or for an euler export the translation and
+    /// rotations its animation targets, the last rotation applied first as collada composes them
+    fn write_node_transform(&self, writer: &mut XmlWriter, matrix: Matrix4<f32>) {
+        let euler_order = match self.options.euler_order {
+            Some(euler_order) => euler_order,
+            None => return self.write_matrix(writer, matrix),
+        };
+
+        let translation = get_translation(&matrix);
+        writer.start_element("translate");
+        writer.write_attribute("sid", LOCATION_SID);
+        writer.write_text_fmt(format_args!(
+            "{} {} {}",
+            format_float(translation.x, self.options.precision),
+            format_float(translation.y, self.options.precision),
+            format_float(translation.z, self.options.precision)
+        ));
+        writer.end_element();
+
+        let angles = to_euler_angles(&matrix, euler_order);
+        for &axis in euler_order.axes().iter().rev() {
+            let mut axis_vector = ["0", "0", "0"];
+            axis_vector[axis] = "1";
+            writer.start_element("rotate");
+            writer.write_attribute("sid", &get_rotation_sid(axis));
+            writer.write_text_fmt(format_args!(
+                "{} {}",
+                axis_vector.join(" "),
+                format_float(angles[axis], self.options.precision)
+            ));
+            writer.end_element();
+        }
+    }
+
+    pub fn write_matrix(&self, ele: &mut XmlWriter, matrix: Matrix4<f32>) {
+        ele.start_element("matrix");
+        ele.write_attribute("sid", MATRIX_SID);
+        ele.write_text_fmt(format_args!("{}", self.format_matrix(&matrix)));
+        ele.end_element();
+    }
+
+    /// writes the matrices as a single space-separated text node, so consecutive values never run
+    /// into each other whatever the writer does between text nodes
+    pub fn write_all_matrices(&self, ele: &mut XmlWriter, matrix: &Vec<Matrix4<f32>>) {
+      let values: Vec<String> = matrix.iter().map(|matrix| self.format_matrix(matrix)).collect();
+      ele.write_text(&values.join(" "));
+    }
+
+    /// the 16 values of a matrix separated by spaces, in the precision set in the export options
+    fn format_matrix(&self, matrix: &Matrix4<f32>) -> String {
+        self.format_values(&get_matrix_values(matrix))
+    }
+
+    /// the values separated by spaces, in the precision set in the export options
+    fn format_values(&self, values: &[f32]) -> String {
+        let values: Vec<String> = values.iter().map(|value| format_float(*value, self.options.precision)).collect();
+        values.join(" ")
+    }
+
+    pub fn load_data_from_collada_skeleton(&mut self, doc: &ColladaDocument) -> Result<(), LabError> {
+        let skeletons = match doc.get_skeletons() {
+            Some(skeletons) => skeletons,
+            None => return Err(LabError::NoSkeleton),
+        };
+
+        // we support only one skeleton in an animation for ToP lab files
+        match skeletons.len() {
+            0 => Err(LabError::NoSkeleton),
+            1 => Ok(()),
+            count => Err(LabError::MultipleSkeletons(count)),
+        }
+    }
+}
+
+/// profile of the `<extra>` data this library writes, which importers can look for
+pub const EXTRA_TECHNIQUE_PROFILE: &str = "lab-parser";
+
+/// writes the xml prolog and a collada document holding several animations of the same skeleton, see
+/// `to_merged_collada`
+pub fn write_merged_collada<W: Write>(animations: &[(&str, &AnimDataBone)], writer: &mut W) -> Result<(), LabError> {
+    let xml_content = to_merged_collada(animations)?;
+    writer.write_all(XML_PROLOG.as_bytes())?;
+    writer.write_all(xml_content.as_bytes())?;
+    Ok(())
+}
+
+/// a single collada document holding several animations of the same skeleton, e.g. all the actions of a
+/// character, each given with its name. the skeleton is written once, from the first animation, and the
+/// channels of each animation are grouped in an animation of their own, played by a clip of the same name.
+/// every animation must have the same bones as the first one, in the same order
+pub fn to_merged_collada(animations: &[(&str, &AnimDataBone)]) -> Result<String, LabError> {
+    let (_, skeleton) = animations.first().ok_or(LabError::NoAnimations)?;
+    let bone_names: Vec<String> = (0..skeleton.get_num_bones()).map(|i| skeleton.get_bone_name(i)).collect();
+
+    // the groups share the skeleton, and their ids and the ids of their animations go in the same document
+    let mut ids = Vec::new();
+    let mut animation_ids = Vec::new();
+    for (name, anim_data) in animations.iter() {
+        let names_match = anim_data.get_num_bones() == bone_names.len()
+            && bone_names.iter().enumerate().all(|(i, bone_name)| anim_data.get_bone_name(i) == *bone_name);
+        if !names_match {
+            return Err(LabError::MismatchedSkeleton(name.to_string()));
+        }
+        let id = name.replace(" ", "_");
+        animation_ids.extend(anim_data.get_animation_ids(&format!("{}_", id)));
+        ids.push(id.clone());
+        ids.push(format!("{}-clip", id));
+    }
+    ids.extend(animation_ids);
+    ids.extend(skeleton.get_skeleton_ids());
+    check_unique_ids(ids)?;
+
+    let (joints, root_index) = skeleton.generate_joint_structure();
+    let mut writer = skeleton.start_collada_document();
+    skeleton.write_skeleton_libraries(&mut writer, &joints, root_index);
+
+    writer.start_element("library_animations");
+    for (name, anim_data) in animations.iter() {
+        anim_data.write_animation_group(&mut writer, &name.replace(" ", "_"));
+    }
+    writer.end_element();
+
+    writer.start_element("library_animation_clips");
+    for (name, anim_data) in animations.iter() {
+        let id = name.replace(" ", "_");
+        let frame_times = anim_data.frame_times();
+        let start = frame_times.first().copied().unwrap_or(0.0);
+        let end = frame_times.last().copied().unwrap_or(start);
+        writer.start_element("animation_clip");
+        writer.write_attribute("id", &format!("{}-clip", id));
+        writer.write_attribute("name", name);
+        writer.write_attribute("start", &start);
+        writer.write_attribute("end", &end);
+        writer.start_element("instance_animation");
+        writer.write_attribute("url", &format!("#{}", id));
+        writer.end_element();
+        writer.end_element();
+    }
+    writer.end_element();
+
+    skeleton.write_scene_element(&mut writer);
+    writer.end_element();
+
+    Ok(writer.end_document())
+}
+
+/// reads the header of a file without loading the rest of it, for listing many files quickly. the dummy count
+/// of the files that store it inline is found by seeking over the bones, so nothing past the header is read
+/// but that count
+pub fn probe<R: Read + Seek>(file: &mut R) -> Result<LabInfo, LabError> {
+    let mut anim_data = AnimDataBone::new();
+    anim_data.load_header(file)?;
+
+    if anim_data.header.dummy_num == 0 {
+        file.seek(SeekFrom::Current((anim_data.header.bone_num as u64 * (BONE_BYTES + MAT44_BYTES as u64)) as i64))?;
+        if let Some(dummy_num) = anim_data.read_inline_dummy_count(file)? {
+            anim_data.header.dummy_num = dummy_num;
+        }
+    }
+
+    Ok(LabInfo {
+        version: anim_data.header.version,
+        key_kind: anim_data.key_kind(),
+        bone_count: anim_data.get_num_bones(),
+        frame_count: anim_data.get_num_frames(),
+        dummy_count: anim_data.header.dummy_num as usize,
+    })
+}
+
+/// returns `DuplicateId` with the first id that was already in `ids` before it
+fn check_unique_ids(ids: Vec<String>) -> Result<(), LabError> {
+    let mut seen = HashSet::new();
+    for id in ids {
+        if !seen.insert(id.clone()) {
+            return Err(LabError::DuplicateId(id));
+        }
+    }
+
+    Ok(())
+}
+
+/// writes an `<extra>` block holding a single value under this library's technique profile, for data collada
+/// has no element for. it has to be the last child of the element it extends
+fn write_extra_technique(writer: &mut XmlWriter, name: &str, value: &str) {
+    writer.start_element("extra");
+    writer.start_element("technique");
+    writer.write_attribute("profile", EXTRA_TECHNIQUE_PROFILE);
+    writer.start_element(name);
+    writer.write_text(value);
+    writer.end_element();
+    writer.end_element();
+    writer.end_element();
+}
+
+/// the 16 values of a matrix in the order collada reads them
+fn get_matrix_values(matrix: &Matrix4<f32>) -> Vec<f32> {
+    (0..4).flat_map(|i| (0..4).map(move |j| matrix[i][j])).collect()
+}
+
+/// sid of the rotation around an axis of a joint node in an euler export, 0 being x
+fn get_rotation_sid(axis: usize) -> String {
+    format!("rotation{}", EULER_AXIS_NAMES[axis])
+}
+
+/// the frames a sampled value has to keep for linear interpolation to give back every frame: the first and
+/// the last frame, and every frame next to a change of more than `epsilon`. a value that holds still for a
+/// stretch of frames only keeps the two ends of the stretch
+fn get_changing_frames(values: &[Vec<f32>], epsilon: f32) -> Vec<usize> {
+    let same = |a: &Vec<f32>, b: &Vec<f32>| a.iter().zip(b.iter()).all(|(a, b)| (a - b).abs() <= epsilon);
+
+    (0..values.len())
+        .filter(|&frame| {
+            frame == 0
+                || frame == values.len() - 1
+                || !same(&values[frame - 1], &values[frame])
+                || !same(&values[frame], &values[frame + 1])
+        })
+        .collect()
+}
+
+/// formats a float rounded to the given number of significant digits, without trailing zeros.
+/// without a precision it is written with as many digits as it takes to read it back exactly
+fn format_float(value: f32, precision: Option<usize>) -> String {
+    let precision = match precision {
+        Some(precision) if value != 0.0 && value.is_finite() => precision.max(1) as i32,
+        _ => return value.to_string(),
+    };
+
+    let magnitude = value.abs().log10().floor() as i32;
+    let decimals = precision - 1 - magnitude;
+    let formatted = if decimals > 0 {
+        let formatted = format!("{:.*}", decimals as usize, value);
+        formatted.trim_end_matches('0').trim_end_matches('.').to_string()
+    } else {
+        let scale = 10f32.powi(-decimals);
+        format!("{}", (value / scale).round() * scale)
+    };
+
+    // values that round to zero keep their sign, which is just noise in the output
+    if formatted == "-0" {
+        "0".to_string()
+    } else {
+        formatted
+    }
+}
+
+/// fills the buffer from the reader, returning `false` instead of an error if the reader ends first
+fn read_key_bytes<R: Read>(file: &mut R, bytes: &mut [u8]) -> Result<bool, LabError> {
+    match file.read_exact(bytes) {
+        Ok(()) => Ok(true),
+        Err(ref error) if error.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// reads the position of a key, `None` if the reader ends first
+fn read_key_position<R: Read>(file: &mut R) -> Result<Option<Vector3<f32>>, LabError> {
+    let mut bytes = [0; size_of::<Vector3<f32>>()];
+    if !read_key_bytes(file, &mut bytes)? {
+        return Ok(None);
+    }
+    let deserialized: [f32; 3] = file_encoding().deserialize(&bytes).unwrap();
+    Ok(Some(Vector3::new(deserialized[0], deserialized[1], deserialized[2])))
+}
+
+/// reads the rotation of a key, stored as x, y, z and w, `None` if the reader ends first
+fn read_key_rotation<R: Read>(file: &mut R) -> Result<Option<Quaternion<f32>>, LabError> {
+    let mut bytes = [0; size_of::<Quaternion<f32>>()];
+    if !read_key_bytes(file, &mut bytes)? {
+        return Ok(None);
+    }
+    let deserialized: [f32; 4] = file_encoding().deserialize(&bytes).unwrap();
+    Ok(Some(Quaternion::new(deserialized[3], deserialized[0], deserialized[1], deserialized[2])))
+}
+
+fn write_rows<W: Write>(writer: &mut W, rows: &[[f32; 4]; 4]) -> io::Result<()> {
+    for value in rows.iter().flatten() {
+        writer.write_f32::<LittleEndian>(*value)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anim::diff::{diff_rest_pose, LabDifference};
+    use crate::anim::options::{parse_root_transform, AxisMap};
+    use crate::anim::validation::ValidationIssue;
+    use cgmath::{Rad, Rotation3};
+    use std::f32::consts::FRAC_PI_2;
+    use std::fs::File;
+
+    #[test]
+    fn it_loads_header_info_correctly() {
+        let mut bone = AnimDataBone::new();
+        let path = Path::new("./src/tests/anim-quat.lab");
+        let display = path.display();
+
+        let mut file = match File::open(&path) {
+            Err(why) => panic!("Couldn\'t open {}: {}", display, why),
+            Ok(file) => file,
+        };
+
+        bone.load_header(&mut file).unwrap();
+        assert_eq!(bone.header.bone_num, 35);
+        assert_eq!(bone.header.frame_num, 228);
+        assert_eq!(bone.header.dummy_num, 2);
+        assert_eq!(bone.header.key_type, BoneInfoKeyType::BoneKeyTypeQuaternion);
+    }
+
+    #[test]
+    fn it_summarizes_the_header() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        bone.load(&mut file).unwrap();
+
+        assert_eq!(bone.summary(), "v4101 quat 35 bones × 228 frames, 2 dummies");
+    }
+
+    #[test]
+    fn it_probes_the_header_without_loading_the_file() {
+        let mut file = File::open("./src/tests/anim-mat44.lab").unwrap();
+        let mut bone = AnimDataBone::new();
+        bone.load(&mut file).unwrap();
+
+        let info = probe(&mut file).unwrap();
+        assert_eq!(info.key_kind, KeyKind::Mat44);
+        assert_eq!(info.bone_count, bone.get_num_bones());
+        assert_eq!(info.frame_count, bone.get_num_frames());
+        assert_eq!(info.dummy_count, bone.dummy_count());
+
+        let info = probe(&mut File::open("./src/tests/anim-inline-dummies.lab").unwrap()).unwrap();
+        assert_eq!(info.dummy_count, 1);
+    }
+
+    #[test]
+    fn it_exposes_the_matrices_of_every_bone_and_frame() {
+        let mut bone = AnimDataBone::new();
+        assert!(bone.transforms().is_empty());
+
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        bone.load_from_file(&mut file).unwrap();
+
+        let transforms = bone.transforms();
+        assert_eq!(transforms.len(), bone.header.bone_num as usize);
+        assert!(transforms.iter().all(|frames| frames.len() == bone.header.frame_num as usize));
+        assert_eq!(transforms[1][50], bone.get_key_matrix(1, 50));
+    }
+
+    #[test]
+    fn it_gives_the_matrices_of_a_frame_for_every_key_kind() {
+        for path in ["./src/tests/anim-quat.lab", "./src/tests/anim-mat43.lab", "./src/tests/anim-mat44.lab"].iter() {
+            let mut bone = AnimDataBone::new();
+            bone.load(&mut File::open(path).unwrap()).unwrap();
+
+            let frame = bone.get_transforms_for_frame(10);
+            assert_eq!(frame.len(), bone.get_num_bones());
+            for (bone_index, matrix) in frame.iter().enumerate() {
+                assert_eq!(*matrix, bone.get_key_matrix(bone_index, 10));
+            }
+            assert_eq!(bone.get_transforms_for_frame_and_bone(10, 3), vec![frame[3]]);
+        }
+    }
+
+    #[test]
+    fn it_rounds_floats_to_significant_digits() {
+        assert_eq!(format_float(0.0000013766586, Some(3)), "0.00000138");
+        assert_eq!(format_float(-1.0, Some(3)), "-1");
+        assert_eq!(format_float(-0.00001, Some(1)), "-0.00001");
+        assert_eq!(format_float(123.456, Some(4)), "123.5");
+        assert_eq!(format_float(123.456, Some(2)), "120");
+        assert_eq!(format_float(0.25, None), "0.25");
+    }
+
+    #[test]
+    fn it_loads_base_bone_info_correctly() {
+        let mut bone = AnimDataBone::new();
+        let path = Path::new("./src/tests/anim-quat.lab");
+        let display = path.display();
+
+        let mut file = match File::open(&path) {
+            Err(why) => panic!("Couldn\'t open {}: {}", display, why),
+            Ok(file) => file,
+        };
+
+        bone.load_header(&mut file).unwrap();
+        bone.load_base_seq(&mut file).unwrap();
+
+        struct BoneBaseTestInfo {
+            pub id: u32,
+            pub parent_id: u32,
+            pub name: String,
+        }
+
+        let actual_bones = vec![
             BoneBaseTestInfo {
                 id: 0,
                 parent_id: 4294967295,
@@ -939,12 +3300,1825 @@ mod tests {
             },
         ];
 
-        // test some random bones against the base seq
-        for i in 0..actual_bones.len() {
-            let bone_id = actual_bones[i].id as usize;
-            assert_eq!(actual_bones[i].id, bone.base_seq[bone_id].id);
-            assert_eq!(actual_bones[i].parent_id, bone.base_seq[bone_id].parent_id);
-            assert_eq!(actual_bones[i].name, bone.base_seq[bone_id].get_name());
+        // test some random bones against the base seq
+        for i in 0..actual_bones.len() {
+            let bone_id = actual_bones[i].id as usize;
+            assert_eq!(actual_bones[i].id, bone.base_seq[bone_id].id);
+            assert_eq!(actual_bones[i].parent_id, bone.base_seq[bone_id].parent_id);
+            assert_eq!(actual_bones[i].name, bone.base_seq[bone_id].get_name());
+        }
+    }
+
+    #[test]
+    fn it_centers_the_root_at_the_origin() {
+        let mut bone = AnimDataBone::with_options(ExportOptions {
+            center_root: true,
+            ..Default::default()
+        });
+        let path = Path::new("./src/tests/anim-quat.lab");
+        let display = path.display();
+
+        let mut file = match File::open(&path) {
+            Err(why) => panic!("Couldn\'t open {}: {}", display, why),
+            Ok(file) => file,
+        };
+
+        bone.load(&mut file).unwrap();
+
+        let root_index = bone.get_root_bone_index().unwrap();
+        let root_frame = bone.transformation_matrices[root_index][0];
+        assert!(root_frame[0][3].abs() < 1e-5);
+        assert!(root_frame[1][3].abs() < 1e-5);
+        assert!(root_frame[2][3].abs() < 1e-5);
+
+        // children keep their local offsets from the root
+        let mut uncentered = AnimDataBone::new();
+        let mut file = File::open(&path).unwrap();
+        uncentered.load(&mut file).unwrap();
+        assert_eq!(
+            uncentered.transformation_matrices[2],
+            bone.transformation_matrices[2]
+        );
+    }
+
+    #[test]
+    fn it_builds_the_joint_tree_for_sparse_bone_ids() {
+        let mut bone = AnimDataBone::new();
+        let path = Path::new("./src/tests/anim-sparse-ids.lab");
+        let display = path.display();
+
+        let mut file = match File::open(&path) {
+            Err(why) => panic!("Couldn\'t open {}: {}", display, why),
+            Ok(file) => file,
+        };
+
+        let xml_content = bone.load_from_file(&mut file).unwrap();
+
+        let root_position = xml_content.find("id=\"Root\"").unwrap();
+        let spine_position = xml_content.find("id=\"Spine\"").unwrap();
+        let head_position = xml_content.find("id=\"Head\"").unwrap();
+        assert!(root_position < spine_position);
+        assert!(spine_position < head_position);
+    }
+
+    #[test]
+    fn it_returns_the_bind_pose_for_every_bone() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        bone.load(&mut file).unwrap();
+
+        let bind_pose = bone.bind_pose();
+        assert_eq!(bind_pose.len(), 35);
+        assert_eq!(bind_pose[0].0, "Bip01");
+        assert_eq!(bind_pose[2].1, bone.position_matrices[2]);
+    }
+
+    #[test]
+    fn it_reports_rest_poses_that_moved_away_from_frame_0() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-sparse-ids.lab").unwrap();
+        bone.load(&mut file).unwrap();
+
+        bone.position_matrices[1][3][1] += 0.5;
+        bone.position_matrices[2][3][1] += DEFAULT_EPSILON / 2.0;
+        assert_eq!(
+            diff_rest_pose(&bone),
+            vec![LabDifference::RestPose { bone: 1, name: "Spine".to_string(), delta: 0.5 }]
+        );
+    }
+
+    #[test]
+    fn it_lists_the_dummies_with_their_parent_bone_names() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        bone.load(&mut file).unwrap();
+
+        let dummies: Vec<DummyView> = bone.dummies().collect();
+        assert_eq!(dummies.len(), 2);
+        assert_eq!(dummies.iter().map(|dummy| dummy.id).collect::<Vec<u32>>(), vec![2, 0]);
+        for dummy in dummies.iter() {
+            assert_eq!(dummy.parent_bone_name.as_deref(), Some("Bip01 Spine"));
+            assert!((get_translation(&dummy.matrix).z - 3.5301018).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn it_counts_the_dummies_and_lists_their_ids() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        bone.load(&mut file).unwrap();
+
+        assert_eq!(bone.dummy_count(), 2);
+        assert_eq!(bone.dummy_ids(), vec![2, 0]);
+    }
+
+    #[test]
+    fn it_normalizes_the_quaternions_of_the_keys() {
+        let build = |options: ExportOptions| {
+            let mut bone = AnimDataBone::with_options(options);
+            bone.set_key_kind(KeyKind::Quaternion);
+            bone.set_frame_count(1);
+            let root = bone.push_bone("Root", 0, u32::MAX, Matrix4::identity());
+            let rotation = Quaternion::from_angle_z(Rad(0.5)) * 1.1;
+            bone.push_quaternion_key(root, Vector3::new(1.0, 2.0, 3.0), rotation);
+            bone.finish().unwrap();
+            bone.transformation_matrices[0][0]
+        };
+        let is_orthonormal = |matrix: Matrix4<f32>| {
+            let rows: Vec<Vector3<f32>> =
+                (0..3).map(|row| Vector3::new(matrix[0][row], matrix[1][row], matrix[2][row])).collect();
+            (0..3).all(|a| {
+                (0..3).all(|b| (rows[a].dot(rows[b]) - if a == b { 1.0 } else { 0.0 }).abs() < 1e-5)
+            })
+        };
+
+        let normalized = build(ExportOptions::default());
+        assert!(is_orthonormal(normalized));
+        assert_eq!(get_translation(&normalized), Vector3::new(1.0, 2.0, 3.0));
+
+        let raw = build(ExportOptions { keep_unnormalized_quats: true, ..Default::default() });
+        assert!(!is_orthonormal(raw));
+    }
+
+    #[test]
+    fn it_lists_the_dummies_of_each_bone() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        bone.load(&mut file).unwrap();
+
+        let spine = bone.find_bone("Bip01 Spine").unwrap();
+        let dummies = bone.dummies_for_bone(bone.get_bone_id(spine));
+        assert_eq!(dummies.iter().map(|dummy| dummy.id).collect::<Vec<_>>(), vec![2, 0]);
+        assert!(dummies.iter().all(|dummy| dummy.parent_bone_name.as_deref() == Some("Bip01 Spine")));
+
+        let others: usize = (0..bone.get_num_bones())
+            .filter(|&index| index != spine)
+            .map(|index| bone.dummies_for_bone(bone.get_bone_id(index)).len())
+            .sum();
+        assert_eq!(others, 0);
+    }
+
+    /// parses the text content of the element with the given id as a list of floats
+    fn read_float_array(xml_content: &str, id: &str) -> Vec<f32> {
+        let element_start = xml_content.find(&format!("id=\"{}\"", id)).unwrap();
+        let text_start = element_start + xml_content[element_start..].find('>').unwrap() + 1;
+        let text_end = text_start + xml_content[text_start..].find('<').unwrap();
+
+        xml_content[text_start..text_end]
+            .split_whitespace()
+            .map(|value| value.parse().unwrap())
+            .collect()
+    }
+
+    /// parses the `<matrix>` of the node with the given id, its values in the order they are written
+    fn read_node_matrix(xml_content: &str, id: &str) -> Vec<f32> {
+        let node_start = xml_content.find(&format!("<node id=\"{}\"", id)).unwrap();
+        let matrix_start = node_start + xml_content[node_start..].find("<matrix").unwrap();
+        let text_start = matrix_start + xml_content[matrix_start..].find('>').unwrap() + 1;
+        let text_end = text_start + xml_content[text_start..].find('<').unwrap();
+
+        xml_content[text_start..text_end]
+            .split_whitespace()
+            .map(|value| value.parse().unwrap())
+            .collect()
+    }
+
+    /// the channel targets of `animations` that don't name the sid of an element of a node of `nodes`, as a
+    /// strict importer resolves them. the elements of a node are the ones before its first child node
+    fn get_unresolved_targets(animations: &str, nodes: &str) -> Vec<String> {
+        let mut sids_by_node: HashMap<&str, Vec<&str>> = HashMap::new();
+        for node in nodes.split("<node ").skip(1) {
+            let (tag, elements) = node.split_at(node.find('>').unwrap());
+            let id = tag.split("id=\"").nth(1).unwrap().split('"').next().unwrap();
+            let elements = elements.split("</node>").next().unwrap();
+            let sids = elements.split("sid=\"").skip(1).map(|rest| rest.split('"').next().unwrap());
+            sids_by_node.insert(id, sids.collect());
+        }
+
+        animations
+            .split("<channel ")
+            .skip(1)
+            .map(|channel| channel.split("target=\"").nth(1).unwrap().split('"').next().unwrap())
+            .filter(|target| {
+                let (node, address) = target.split_at(target.find('/').unwrap());
+                let sid = address[1..].split(['.', '(']).next().unwrap();
+                !matches!(sids_by_node.get(node), Some(sids) if sids.contains(&sid))
+            })
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn it_resolves_every_channel_target_to_a_sid_of_its_node() {
+        let load = |path: &str, options: ExportOptions| {
+            let mut bone = AnimDataBone::with_options(options);
+            bone.load(&mut File::open(path).unwrap()).unwrap();
+            bone
+        };
+        let exports = [
+            ExportOptions::default(),
+            ExportOptions { euler_order: Some(EulerOrder::Zyx), ..Default::default() },
+            ExportOptions { world_space: true, ..Default::default() },
+            ExportOptions { inject_bind_pose: true, reduce_keys: true, ..Default::default() },
+        ];
+        for options in exports.iter() {
+            for path in ["./src/tests/anim-quat.lab", "./src/tests/anim-scale.lab"].iter() {
+                let bone = load(path, options.clone());
+                let xml_content = bone.to_collada();
+                assert!(xml_content.contains("<channel "));
+                assert_eq!(get_unresolved_targets(&xml_content, &xml_content), Vec::<String>::new(), "{}", path);
+
+                let (skeleton, animation) = (bone.to_skeleton_collada(), bone.to_animation_collada());
+                assert_eq!(get_unresolved_targets(&animation, &skeleton), Vec::<String>::new(), "{}", path);
+            }
+        }
+
+        let walk = load("./src/tests/anim-sparse-ids.lab", ExportOptions::default());
+        let xml_content = to_merged_collada(&[("walk", &walk), ("idle", &walk)]).unwrap();
+        assert_eq!(get_unresolved_targets(&xml_content, &xml_content), Vec::<String>::new());
+
+        // a target naming an element the node doesn't have is caught
+        let broken = xml_content.replacen("Spine/transform", "Spine/rotationX.ANGLE", 1);
+        assert_eq!(get_unresolved_targets(&broken, &broken), vec!["Spine/rotationX.ANGLE"]);
+    }
+
+    #[test]
+    fn it_writes_time_values_matching_the_time_extent() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        bone.load(&mut file).unwrap();
+        let (start, end) = bone.time_extent();
+        assert_eq!(start, 0.0);
+        assert!((end - 227.0 / 25.0).abs() < 1e-6);
+
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        let xml_content = bone.load_from_file(&mut file).unwrap();
+        let times = read_float_array(&xml_content, "Bip01_pose_matrix-input-array");
+        assert_eq!(times.len(), 228);
+        assert_eq!(times[0], start);
+        assert_eq!(times[227], end);
+    }
+
+    #[test]
+    fn it_exposes_the_time_of_every_exported_frame() {
+        let mut bone = AnimDataBone::with_options(ExportOptions {
+            max_frames: Some(50),
+            ..Default::default()
+        });
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        bone.load(&mut file).unwrap();
+
+        let frame_times = bone.frame_times();
+        assert_eq!(frame_times.len(), 50);
+        assert_eq!(frame_times[0], 0.0);
+        assert_eq!(frame_times[25], 1.0);
+        assert_eq!(frame_times[49], 49.0 / 25.0);
+
+        let xml_content = bone.to_collada();
+        assert_eq!(read_float_array(&xml_content, "Bip01_pose_matrix-input-array"), frame_times);
+    }
+
+    #[test]
+    fn it_bounds_the_time_extent_by_the_exported_frames() {
+        let mut bone = AnimDataBone::with_options(ExportOptions {
+            max_frames: Some(50),
+            inject_bind_pose: true,
+            target_duration: Some(2.0),
+            ..Default::default()
+        });
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        let xml_content = bone.load_from_file(&mut file).unwrap();
+
+        // the bind pose comes on top of the 50 frames, and the last of the 51 lands on the target duration
+        let frame_times = bone.frame_times();
+        assert_eq!(frame_times.len(), 51);
+        assert_eq!(bone.time_extent(), (frame_times[0], frame_times[50]));
+        assert_eq!(bone.time_extent(), (0.0, 2.0));
+        let times = read_float_array(&xml_content, "Bip01_pose_matrix-input-array");
+        assert_eq!((times[0], times[times.len() - 1]), bone.time_extent());
+    }
+
+    #[test]
+    fn it_shifts_the_keys_and_clips_by_the_time_offset() {
+        let mut bone = AnimDataBone::with_options(ExportOptions {
+            time_offset: Some(1.5),
+            ..Default::default()
+        });
+        bone.set_clips(AnimationClips::parse("0,99,walk\n").unwrap());
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        let xml_content = bone.load_from_file(&mut file).unwrap();
+
+        let (start, end) = bone.time_extent();
+        assert_eq!(start, 1.5);
+        assert!((end - (1.5 + 227.0 / 25.0)).abs() < 1e-5);
+        let times = read_float_array(&xml_content, "Bip01_pose_matrix-input-array");
+        assert_eq!(times.len(), 228);
+        assert_eq!(times[0], start);
+        assert_eq!(times[227], end);
+        assert!(xml_content.contains("<animation_clip id=\"walk-clip\" name=\"walk\" start=\"1.5\" end=\"5.46\">"));
+
+        let xml_content = to_merged_collada(&[("walk", &bone)]).unwrap();
+        assert!(xml_content.contains(&format!("start=\"1.5\" end=\"{}\"", end)));
+    }
+
+    #[test]
+    fn it_retimes_the_animation_to_the_target_duration() {
+        let mut bone = AnimDataBone::new();
+        bone.load(&mut File::open("./src/tests/anim-quat.lab").unwrap()).unwrap();
+        let mut retimed = AnimDataBone::with_options(ExportOptions {
+            target_duration: Some(4.54),
+            ..Default::default()
+        });
+        retimed.load(&mut File::open("./src/tests/anim-quat.lab").unwrap()).unwrap();
+
+        assert!((retimed.get_frames_per_second() - 50.0).abs() < 1e-3);
+        let xml_content = retimed.to_collada();
+        let times = read_float_array(&xml_content, "Bip01_pose_matrix-input-array");
+        assert_eq!(times.len(), 228);
+        assert!((times[227] - 4.54).abs() < 1e-5);
+
+        // the poses stay the same, only their times change
+        let original = bone.to_collada();
+        assert_eq!(
+            read_float_array(&xml_content, "Bip01_pose_matrix-output-array"),
+            read_float_array(&original, "Bip01_pose_matrix-output-array")
+        );
+    }
+
+    #[test]
+    fn it_separates_every_value_of_the_animation_arrays() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        let xml_content = bone.load_from_file(&mut file).unwrap();
+
+        let times = read_float_array(&xml_content, "Bip01_pose_matrix-input-array");
+        assert_eq!(times.len(), 228);
+        assert!(times.windows(2).all(|pair| pair[0] < pair[1]));
+
+        let matrices = read_float_array(&xml_content, "Bip01_pose_matrix-output-array");
+        assert_eq!(matrices.len(), 16 * 228);
+    }
+
+    #[test]
+    fn it_injects_the_bind_pose_as_the_first_frame() {
+        let mut bone = AnimDataBone::with_options(ExportOptions {
+            inject_bind_pose: true,
+            ..Default::default()
+        });
+        let mut file = File::open("./src/tests/anim-sparse-ids.lab").unwrap();
+        let xml_content = bone.load_from_file(&mut file).unwrap();
+
+        let times = read_float_array(&xml_content, "Spine_pose_matrix-input-array");
+        assert_eq!(times, vec![0.0, 1.0 / 25.0, 2.0 / 25.0]);
+
+        let matrices = read_float_array(&xml_content, "Spine_pose_matrix-output-array");
+        assert_eq!(matrices.len(), 16 * 3);
+        assert!(xml_content.contains("<float_array id=\"Spine_pose_matrix-output-array\" count=\"48\">"));
+        assert!(xml_content.contains("<Name_array id=\"Spine_pose_matrix-interpolation-array\" count=\"3\">"));
+    }
+
+    #[test]
+    fn it_leaves_out_the_interpolation_source() {
+        let mut bone = AnimDataBone::with_options(ExportOptions {
+            no_interpolation_source: true,
+            ..Default::default()
+        });
+        let mut file = File::open("./src/tests/anim-sparse-ids.lab").unwrap();
+        let xml_content = bone.load_from_file(&mut file).unwrap();
+
+        let mut file = File::open("./src/tests/anim-sparse-ids.lab").unwrap();
+        let full_content = AnimDataBone::new().load_from_file(&mut file).unwrap();
+
+        assert!(!xml_content.contains("interpolation"));
+        assert!(!xml_content.contains("INTERPOLATION"));
+        assert!(full_content.contains("INTERPOLATION"));
+        assert!(xml_content.contains("<input semantic=\"OUTPUT\" source=\"#Spine_pose_matrix-output\"/>"));
+        assert_eq!(
+            read_float_array(&xml_content, "Spine_pose_matrix-output-array"),
+            read_float_array(&full_content, "Spine_pose_matrix-output-array")
+        );
+        assert!(xml_content.len() < full_content.len());
+    }
+
+    #[test]
+    fn it_places_joints_at_their_bind_positions() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        bone.load(&mut file).unwrap();
+
+        // the first frame of this file is the bind pose, so the world matrices should undo the inverse bind matrices.
+        // the footsteps helper (bone 1) is the exception, it is not bound where it starts
+        let world_matrices = bone.get_world_transforms_for_frame(0);
+        for i in (0..bone.get_num_bones()).filter(|&i| i != 1) {
+            let bind_position = get_translation(&bone.invmat_seq[i].matrix.invert().unwrap());
+            let position = get_translation(&world_matrices[i]);
+            assert!((bind_position - position).magnitude() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn it_computes_a_non_degenerate_bounding_box() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        bone.load(&mut file).unwrap();
+
+        let (min, max) = bone.bounds();
+        assert!(max.x > min.x);
+        assert!(max.y > min.y);
+        assert!(max.z > min.z);
+    }
+
+    #[test]
+    fn it_strips_dummies_from_the_output() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        let xml_content = bone.load_from_file(&mut file).unwrap();
+        assert!(xml_content.contains("Dummy_"));
+
+        let mut bone = AnimDataBone::with_options(ExportOptions {
+            strip_dummies: true,
+            ..Default::default()
+        });
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        let xml_content = bone.load_from_file(&mut file).unwrap();
+        assert!(!xml_content.contains("Dummy_"));
+    }
+
+    #[test]
+    fn it_exports_dummies_relative_to_their_bone() {
+        let read_dummy_translation = |dummy_space: DummySpace| {
+            let mut bone = AnimDataBone::with_options(ExportOptions {
+                dummy_space,
+                ..Default::default()
+            });
+            let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+            let xml_content = bone.load_from_file(&mut file).unwrap();
+
+            let values = read_node_matrix(&xml_content, "Dummy_2");
+            Vector3::new(values[3], values[7], values[11])
+        };
+
+        // the dummies of the sample file are in model space, close to the spine they hang off
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        bone.load(&mut file).unwrap();
+        let spine = bone.find_bone("Bip01 Spine").unwrap();
+        let dummy = bone.dummies().find(|dummy| dummy.id == 2).unwrap();
+        assert_eq!(read_dummy_translation(DummySpace::Local), get_translation(&dummy.matrix));
+
+        let local_matrix = Matrix4::from_translation(read_dummy_translation(DummySpace::World));
+        let world_translation = get_translation(&(local_matrix * bone.get_world_transforms_for_frame(0)[spine]));
+        assert!((world_translation - get_translation(&dummy.matrix)).magnitude() < 1e-3);
+    }
+
+    #[test]
+    fn it_nests_dummies_in_their_bone_so_they_follow_its_animation() {
+        let mut bone = AnimDataBone::new();
+        bone.set_key_kind(KeyKind::Quaternion);
+        bone.set_frame_count(2);
+        let root = bone.push_bone("Root", 0, u32::MAX, Matrix4::identity());
+        let arm = bone.push_bone("Arm", 1, 0, Matrix4::from_translation(Vector3::new(0.0, -1.0, 0.0)));
+        // a model-space attachment point a unit above the arm
+        bone.push_dummy(5, 1, Matrix4::from_translation(Vector3::new(0.0, 2.0, 0.0)));
+        for frame in 0..2 {
+            let rotation = Quaternion::from_angle_z(Rad(FRAC_PI_2 * frame as f32));
+            bone.push_quaternion_key(root, Vector3::new(0.0, 0.0, 0.0), Quaternion::from_angle_z(Rad(0.0)));
+            bone.push_quaternion_key(arm, Vector3::new(0.0, 1.0, 0.0), rotation);
+        }
+        bone.finish().unwrap();
+
+        let xml_content = bone.to_collada();
+        let arm_start = xml_content.find("<node id=\"Arm\"").unwrap();
+        let dummy_start = xml_content.find("<node id=\"Dummy_5\"").unwrap();
+        assert!(dummy_start > arm_start);
+        assert_eq!(xml_content[arm_start..dummy_start].matches("</node>").count(), 0);
+
+        // the dummy's matrix is its offset from the arm, which moves it along with the arm's animation
+        let values = read_node_matrix(&xml_content, "Dummy_5");
+        let local_translation = Vector3::new(values[3], values[7], values[11]);
+        assert!((local_translation - Vector3::new(0.0, 1.0, 0.0)).magnitude() < 1e-5);
+
+        let local_matrix = Matrix4::from_translation(local_translation);
+        let posed = get_translation(&(local_matrix * bone.get_world_transforms_for_frame(1)[arm]));
+        assert!((posed - Vector3::new(-1.0, 1.0, 0.0)).magnitude() < 1e-5, "{:?}", posed);
+    }
+
+    #[test]
+    fn it_poses_the_joints_through_their_parents() {
+        let mut bone = AnimDataBone::new();
+        bone.set_key_kind(KeyKind::Quaternion);
+        bone.set_frame_count(1);
+        let root = bone.push_bone("Root", 0, u32::MAX, Matrix4::identity());
+        let child = bone.push_bone("Child", 1, 0, Matrix4::identity());
+        bone.push_quaternion_key(root, Vector3::new(1.0, 0.0, 0.0), Quaternion::from_angle_z(Rad(FRAC_PI_2)));
+        bone.push_quaternion_key(child, Vector3::new(0.0, 2.0, 0.0), Quaternion::from_angle_z(Rad(0.0)));
+        bone.finish().unwrap();
+
+        let transforms: Vec<Matrix4<f32>> = (0..2).map(|i| bone.transforms()[i][0]).collect();
+        let positions = bone.apply_transforms(transforms);
+        assert!((positions[root] - Vector3::new(1.0, 0.0, 0.0)).magnitude() < 1e-5);
+        // the child's offset is turned by the root's rotation before the root's translation is added
+        assert!((positions[child] - Vector3::new(-1.0, 0.0, 0.0)).magnitude() < 1e-5, "{:?}", positions[child]);
+    }
+
+    #[test]
+    fn it_collapses_frames_where_a_bone_holds_still() {
+        let mut bone = AnimDataBone::with_options(ExportOptions { reduce_keys: true, ..Default::default() });
+        bone.set_key_kind(KeyKind::Quaternion);
+        bone.set_frame_count(6);
+        let root = bone.push_bone("Root", 0, u32::MAX, Matrix4::identity());
+        let still = bone.push_bone("Still", 1, 0, Matrix4::identity());
+        let pausing = bone.push_bone("Pausing", 2, 0, Matrix4::identity());
+        for frame in 0..6 {
+            let rotation = Quaternion::from_angle_z(Rad(0.0));
+            bone.push_quaternion_key(root, Vector3::new(frame as f32, 0.0, 0.0), rotation);
+            bone.push_quaternion_key(still, Vector3::new(0.0, 1.0, 0.0), rotation);
+            // moves on the first two frames, holds still and moves again on the last one
+            let height = [0.0, 1.0, 2.0, 2.0, 2.0, 3.0][frame];
+            bone.push_quaternion_key(pausing, Vector3::new(0.0, 0.0, height), rotation);
+        }
+        bone.finish().unwrap();
+
+        let xml_content = bone.to_collada();
+        assert_eq!(read_float_array(&xml_content, "Root_pose_matrix-input-array").len(), 6);
+        assert_eq!(read_float_array(&xml_content, "Still_pose_matrix-input-array"), vec![0.0, 0.2]);
+        assert_eq!(read_float_array(&xml_content, "Still_pose_matrix-output-array").len(), 2 * 16);
+        assert!(xml_content.contains("id=\"Still_pose_matrix-interpolation-array\" count=\"2\""));
+
+        let times = read_float_array(&xml_content, "Pausing_pose_matrix-input-array");
+        assert_eq!(times, vec![0.0, 0.04, 0.08, 0.16, 0.2]);
+        let heights: Vec<f32> = read_float_array(&xml_content, "Pausing_pose_matrix-output-array")
+            .chunks(16)
+            .map(|matrix| matrix[11])
+            .collect();
+        assert_eq!(heights, vec![0.0, 1.0, 2.0, 2.0, 3.0]);
+
+        // with a tolerance above the steps of the animation, it holds still from the first frame to the last
+        let mut tolerant = bone.clone();
+        tolerant.options.epsilon = Some(1.5);
+        let xml_content = tolerant.to_collada();
+        assert_eq!(read_float_array(&xml_content, "Pausing_pose_matrix-input-array"), vec![0.0, 0.2]);
+    }
+
+    #[test]
+    fn it_splits_the_skeleton_from_the_animation() {
+        let mut bone = AnimDataBone::new();
+        bone.load(&mut File::open("./src/tests/anim-quat.lab").unwrap()).unwrap();
+
+        let skeleton = bone.to_skeleton_collada();
+        let animation = bone.to_animation_collada();
+        assert!(skeleton.contains("<library_visual_scenes>") && !skeleton.contains("<library_animations>"));
+        assert!(animation.contains("<library_animations>") && !animation.contains("<library_visual_scenes>"));
+
+        // every channel of the animation targets a joint of the skeleton
+        let targets: Vec<&str> = animation
+            .split("target=\"")
+            .skip(1)
+            .map(|rest| rest.split('/').next().unwrap())
+            .collect();
+        assert_eq!(targets.len(), bone.get_num_bones());
+        for target in targets {
+            assert!(skeleton.contains(&format!("id=\"{}\"", target)), "{} is not in the skeleton", target);
+        }
+    }
+
+    #[test]
+    fn it_exports_a_frame_as_a_static_pose() {
+        let mut bone = AnimDataBone::new();
+        bone.load(&mut File::open("./src/tests/anim-quat.lab").unwrap()).unwrap();
+
+        let forearm = bone.find_bone("Bip01 L Forearm").unwrap();
+        let pose = bone.to_pose_collada(50).unwrap();
+        assert!(!pose.contains("<library_animations>"));
+        let pose_matrix = read_node_matrix(&pose, "Bip01_L_Forearm");
+        assert_eq!(pose_matrix, get_matrix_values(&bone.transformation_matrices[forearm][50]));
+        assert_ne!(pose_matrix, read_node_matrix(&bone.to_collada(), "Bip01_L_Forearm"));
+        assert!(matches!(bone.to_pose_collada(228), Err(LabError::FrameOutOfRange(228, 228))));
+    }
+
+    #[test]
+    fn it_exports_a_clone_on_another_thread() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        bone.load(&mut file).unwrap();
+
+        let clone = bone.clone();
+        let cloned_xml_content = std::thread::spawn(move || clone.to_collada()).join().unwrap();
+        let xml_content = bone.to_collada();
+
+        // the documents only differ in their creation time
+        let without_asset = |xml_content: &str| xml_content[xml_content.find("</asset>").unwrap()..].to_string();
+        assert_eq!(without_asset(&xml_content), without_asset(&cloned_xml_content));
+        assert_eq!(without_asset(&bone.to_collada()), without_asset(&xml_content));
+    }
+
+    #[test]
+    fn it_exports_every_joint_through_a_shared_reference() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        bone.load(&mut file).unwrap();
+
+        let shared: &AnimDataBone = &bone;
+        let xml_content = shared.to_collada();
+        assert_eq!(xml_content.matches("type=\"JOINT\"").count(), shared.get_num_bones());
+
+        // a child's node is written inside its parent's
+        let pelvis = xml_content.find("id=\"Bip01_Pelvis\"").unwrap();
+        let spine = xml_content.find("id=\"Bip01_Spine\"").unwrap();
+        assert!(pelvis < spine);
+        assert!(!xml_content[pelvis..spine].contains("<layer>bone"));
+    }
+
+    #[test]
+    fn it_writes_a_document_for_a_single_bone() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-sparse-ids.lab").unwrap();
+        bone.load(&mut file).unwrap();
+
+        let xml_content = bone.write_bone_collada_data(1);
+        assert!(xml_content.contains("id=\"Spine\""));
+        assert!(xml_content.contains("id=\"Spine_pose_matrix\""));
+        assert!(!xml_content.contains("id=\"Root\""));
+        assert!(!xml_content.contains("id=\"Head_pose_matrix\""));
+    }
+
+    #[test]
+    fn it_rejects_skin_weights_of_bones_the_animation_doesnt_have() {
+        let mut bone = AnimDataBone::new();
+        bone.set_skin_weights(SkinWeights::parse("0:1\n1:0.5 3:0.5\n").unwrap());
+        let mut file = File::open("./src/tests/anim-sparse-ids.lab").unwrap();
+
+        match bone.load(&mut file) {
+            Err(LabError::SkinWeightsOutOfRange(3, 3)) => {}
+            result => panic!("expected LabError::SkinWeightsOutOfRange, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn it_writes_a_skin_controller_for_vertex_weights() {
+        let mut bone = AnimDataBone::new();
+        bone.set_skin_weights(SkinWeights::parse("0:1\n1:0.5 2:0.5\n").unwrap());
+        let mut file = File::open("./src/tests/anim-sparse-ids.lab").unwrap();
+        let xml_content = bone.load_from_file(&mut file).unwrap();
+
+        assert!(xml_content.contains("<vertex_weights count=\"2\">"));
+        assert_eq!(read_float_array(&xml_content, "Skin-weights-array"), vec![1.0, 0.5, 0.5]);
+        assert_eq!(read_float_array(&xml_content, "Skin-bind_poses-array").len(), 16 * 3);
+
+        let vcount_start = xml_content.find("<vcount>").unwrap() + "<vcount>".len();
+        let vcount_end = vcount_start + xml_content[vcount_start..].find('<').unwrap();
+        let vcount: Vec<&str> = xml_content[vcount_start..vcount_end].split_whitespace().collect();
+        assert_eq!(vcount, vec!["1", "2"]);
+
+        let v_start = xml_content.find("<v>").unwrap() + "<v>".len();
+        let v_end = v_start + xml_content[v_start..].find('<').unwrap();
+        let v: Vec<&str> = xml_content[v_start..v_end].split_whitespace().collect();
+        assert_eq!(v, vec!["0", "0", "1", "1", "2", "2"]);
+    }
+
+    #[test]
+    fn it_decodes_names_that_are_not_utf8() {
+        let mut bone_info = BoneBaseInfo::new();
+        // "テスト" in Shift-JIS
+        let name = [0x83, 0x65, 0x83, 0x58, 0x83, 0x67];
+        bone_info.name[..name.len()].copy_from_slice(&name);
+
+        assert_eq!(bone_info.get_name_bytes(), &name);
+        assert!(bone_info.get_name().contains('\u{FFFD}'));
+        assert!(bone_info.try_get_name().is_err());
+
+        let mut ascii_info = BoneBaseInfo::new();
+        ascii_info.name[..5].copy_from_slice(b"Bip01");
+        assert_eq!(ascii_info.try_get_name().unwrap(), "Bip01");
+        assert_eq!(bone_info.get_name_with_encoding(encoding_rs::SHIFT_JIS), "テスト");
+    }
+
+    #[test]
+    fn it_indexes_bones_by_name_and_hierarchy() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-sparse-ids.lab").unwrap();
+        bone.load(&mut file).unwrap();
+
+        assert_eq!(bone.find_bone("Spine"), Some(1));
+        assert_eq!(bone.find_bone("Tail"), None);
+        assert_eq!(bone.get_bone_children(0), &[1]);
+        assert_eq!(bone.get_bone_children(1), &[2]);
+        assert!(bone.get_bone_children(2).is_empty());
+        assert_eq!(bone.get_parent_indices(), &[None, Some(0), Some(1)]);
+    }
+
+    /// a skeleton of a root and two bones `First` and `Second` that name each other as parent, returned along with
+    /// the indices of the two
+    fn build_looped_skeleton() -> (AnimDataBone, usize, usize) {
+        let mut looped = AnimDataBone::new();
+        looped.set_key_kind(KeyKind::Quaternion);
+        looped.set_frame_count(1);
+        // the loop hangs apart from the root, a skeleton without one isn't loaded at all
+        let root = looped.push_bone("Root", 2, u32::MAX, Matrix4::identity());
+        let first = looped.push_bone("First", 0, 1, Matrix4::identity());
+        let second = looped.push_bone("Second", 1, 0, Matrix4::identity());
+        for bone_index in [root, first, second].iter() {
+            looped.push_quaternion_key(*bone_index, Vector3::new(0.0, 0.0, 0.0), Quaternion::new(1.0, 0.0, 0.0, 0.0));
+        }
+        looped.finish().unwrap();
+        (looped, first, second)
+    }
+
+    #[test]
+    fn it_lists_the_ancestors_of_a_bone() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        bone.load(&mut file).unwrap();
+
+        let finger_nub = bone.find_bone("Bip01 L Finger0Nub").unwrap();
+        assert_eq!(bone.ancestors(finger_nub), vec![14, 13, 12, 11, 10, 9, 5, 4, 3, 2, 0]);
+        assert_eq!(bone.ancestors(0), vec![0]);
+
+        let (looped, first, second) = build_looped_skeleton();
+        assert_eq!(looped.ancestors(first), vec![first, second]);
+    }
+
+    #[test]
+    fn it_gives_the_depth_of_every_bone() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        bone.load(&mut file).unwrap();
+
+        assert_eq!(bone.bone_depth(0), 0);
+        assert_eq!(bone.bone_depth(bone.find_bone("Bip01 Pelvis").unwrap()), 1);
+        assert_eq!(bone.bone_depth(bone.find_bone("Bip01 Spine").unwrap()), 2);
+        assert_eq!(bone.bone_depth(bone.find_bone("Bip01 L Finger0Nub").unwrap()), 10);
+        for bone_index in 0..bone.get_num_bones() {
+            assert_eq!(bone.bone_depth(bone_index), bone.ancestors(bone_index).len() - 1);
+        }
+
+        let (looped, first, second) = build_looped_skeleton();
+        // the walk from First ends at Second, the topmost bone of the loop, which counts as a root
+        assert_eq!(looped.bone_depth(second), 0);
+        assert_eq!(looped.bone_depth(first), 1);
+    }
+
+    #[test]
+    fn it_expresses_transforms_relative_to_a_reference_bone() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        bone.load(&mut file).unwrap();
+        let pelvis = bone.find_bone("Bip01 Pelvis").unwrap();
+
+        let world_matrices = bone.get_world_transforms_for_frame(10);
+        let relative_matrices = bone.transforms_relative_to(10, pelvis).unwrap();
+        assert_eq!(relative_matrices.len(), bone.get_num_bones());
+
+        for (index, relative_matrix) in relative_matrices.iter().enumerate() {
+            let world_matrix = relative_matrix * world_matrices[pelvis];
+            for i in 0..4 {
+                assert!((world_matrix[i] - world_matrices[index][i]).magnitude() < 1e-3);
+            }
+        }
+        let reference_matrix = relative_matrices[pelvis];
+        for column in 0..4 {
+            assert!((reference_matrix[column] - Matrix4::<f32>::identity()[column]).magnitude() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn it_rejects_a_reference_bone_out_of_range() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        bone.load(&mut file).unwrap();
+        assert_eq!(bone.transforms_relative_to(0, 99), None);
+        assert_eq!(bone.transforms_relative_to(228, 0), None);
+    }
+
+    #[test]
+    fn it_applies_the_root_transform_to_the_whole_skeleton() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        bone.load(&mut file).unwrap();
+
+        let root_transform = parse_root_transform("x-90").unwrap();
+        let mut rotated = AnimDataBone::with_options(ExportOptions {
+            root_transform: Some(root_transform),
+            ..Default::default()
+        });
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        rotated.load(&mut file).unwrap();
+
+        let world_matrices = bone.get_world_transforms_for_frame(10);
+        let rotated_world_matrices = rotated.get_world_transforms_for_frame(10);
+        for i in 0..bone.get_num_bones() {
+            let expected = get_translation(&(world_matrices[i] * root_transform));
+            let position = get_translation(&rotated_world_matrices[i]);
+            assert!((expected - position).magnitude() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn it_converts_the_skeleton_into_the_remapped_axes() {
+        let mut bone = AnimDataBone::new();
+        bone.load(&mut File::open("./src/tests/anim-quat.lab").unwrap()).unwrap();
+
+        let axis_map = AxisMap::parse("+X+Z-Y").unwrap();
+        let mut remapped = AnimDataBone::with_options(ExportOptions {
+            axis_map: Some(axis_map),
+            ..Default::default()
+        });
+        remapped.load(&mut File::open("./src/tests/anim-quat.lab").unwrap()).unwrap();
+
+        let world_matrices = bone.get_world_transforms_for_frame(10);
+        let remapped_world_matrices = remapped.get_world_transforms_for_frame(10);
+        for i in 0..bone.get_num_bones() {
+            let position = get_translation(&world_matrices[i]);
+            let remapped_position = get_translation(&remapped_world_matrices[i]);
+            assert!((remapped_position - Vector3::new(position.x, position.z, -position.y)).magnitude() < 1e-3);
+        }
+        let read_up_axis = |xml_content: &str| {
+            let text_start = xml_content.find("<up_axis>").unwrap() + "<up_axis>".len();
+            let text_end = text_start + xml_content[text_start..].find('<').unwrap();
+            xml_content[text_start..text_end].trim().to_string()
+        };
+        assert_eq!(read_up_axis(&remapped.to_collada()), "Y_UP");
+        assert_eq!(read_up_axis(&bone.to_collada()), "Z_UP");
+    }
+
+    #[test]
+    fn it_credits_the_authoring_tool() {
+        let mut bone = AnimDataBone::new();
+        bone.load(&mut File::open("./src/tests/anim-sparse-ids.lab").unwrap()).unwrap();
+        let expected = format!("lab-parser {}", env!("CARGO_PKG_VERSION"));
+        assert!(bone.to_collada().contains(&expected));
+
+        let mut bone = AnimDataBone::with_options(ExportOptions {
+            authoring_tool: Some("asset-pipeline 2.1".to_string()),
+            ..Default::default()
+        });
+        bone.load(&mut File::open("./src/tests/anim-sparse-ids.lab").unwrap()).unwrap();
+        let xml_content = bone.to_collada();
+        assert!(xml_content.contains("asset-pipeline 2.1") && !xml_content.contains(&expected));
+    }
+
+    #[test]
+    fn it_rejects_an_animation_without_frames() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-no-frames.lab").unwrap();
+
+        match bone.load(&mut file) {
+            Err(LabError::NoFrames) => {}
+            result => panic!("expected LabError::NoFrames, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn it_rejects_a_skeleton_without_a_root_or_with_a_missing_parent() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-no-root.lab").unwrap();
+        match bone.load(&mut file) {
+            Err(LabError::NoRoot) => {}
+            result => panic!("expected LabError::NoRoot, got {:?}", result),
+        }
+
+        let mut built = AnimDataBone::new();
+        built.set_key_kind(KeyKind::Quaternion);
+        built.set_frame_count(1);
+        let root = built.push_bone("Root", 0, u32::MAX, Matrix4::identity());
+        let orphan = built.push_bone("Orphan", 1, 7, Matrix4::identity());
+        for bone_index in [root, orphan].iter() {
+            built.push_quaternion_key(*bone_index, Vector3::new(0.0, 0.0, 0.0), Quaternion::new(1.0, 0.0, 0.0, 0.0));
+        }
+        match built.finish() {
+            Err(LabError::DanglingParent(bone)) => assert_eq!(bone, orphan),
+            result => panic!("expected LabError::DanglingParent, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn it_rejects_header_counts_the_file_cant_hold() {
+        let load = |offset: usize| {
+            let mut bytes = std::fs::read("./src/tests/anim-sparse-ids.lab").unwrap();
+            bytes[offset..offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+            AnimDataBone::new().load(&mut std::io::Cursor::new(bytes))
+        };
+
+        match load(4) {
+            Err(LabError::TruncatedBone(_)) => {}
+            result => panic!("expected LabError::TruncatedBone, got {:?}", result),
+        }
+        match load(8) {
+            Err(LabError::SizeMismatch(_)) => {}
+            result => panic!("expected LabError::SizeMismatch, got {:?}", result),
+        }
+        assert!(load(12).is_err());
+    }
+
+    #[test]
+    fn it_rejects_an_animation_without_bones() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-no-bones.lab").unwrap();
+
+        match bone.load(&mut file) {
+            Err(LabError::NoBones) => {}
+            result => panic!("expected LabError::NoBones, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn it_writes_the_inverse_bind_matrices_as_json() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        bone.load(&mut file).unwrap();
+
+        let json_content = bone.write_inverse_bind_json();
+        let matrices: BTreeMap<String, [[f32; 4]; 4]> = serde_json::from_str(&json_content).unwrap();
+        assert_eq!(matrices.len(), bone.get_num_bones());
+
+        let root_index = bone.get_root_bone_index().unwrap();
+        let root_name = bone.get_bone_name(root_index);
+        assert_eq!(
+            lwMatrix44::new(matrices[&root_name]).matrix,
+            bone.invmat_seq[root_index].matrix
+        );
+    }
+
+    #[test]
+    fn it_writes_back_the_file_it_loaded() {
+        let original = std::fs::read("./src/tests/anim-quat.lab").unwrap();
+        let mut bone = AnimDataBone::new();
+        bone.load(&mut std::io::Cursor::new(&original)).unwrap();
+
+        let mut written = Vec::new();
+        bone.write_lab(&mut written).unwrap();
+        assert!(written == original);
+    }
+
+    #[test]
+    fn it_keeps_the_bytes_after_the_key_data() {
+        let mut original = std::fs::read("./src/tests/anim-quat.lab").unwrap();
+        original.extend_from_slice(&[0xde, 0xad, 0xbe, 0xef, 0x01, 0x02]);
+        let mut bone = AnimDataBone::new();
+        bone.load(&mut std::io::Cursor::new(&original)).unwrap();
+        assert_eq!(bone.footer(), &[0xde, 0xad, 0xbe, 0xef, 0x01, 0x02]);
+        assert_eq!(bone.warnings().len(), 1);
+
+        let mut written = Vec::new();
+        bone.write_lab(&mut written).unwrap();
+        assert!(written == original);
+    }
+
+    #[test]
+    fn it_adds_the_root_motion_track_to_the_root() {
+        let mut plain = AnimDataBone::new();
+        plain.load(&mut File::open("./src/tests/anim-sparse-ids.lab").unwrap()).unwrap();
+
+        // the same file with a translation of the root per frame after the keys
+        let mut bone = AnimDataBone::with_options(ExportOptions { root_motion_track: true, ..Default::default() });
+        bone.load(&mut File::open("./src/tests/anim-root-motion.lab").unwrap()).unwrap();
+        let root_motion = [Vector3::new(0.0, 0.0, 0.5), Vector3::new(2.0, 0.0, 0.5)];
+        assert_eq!(bone.root_motion(), Some(&root_motion[..]));
+        assert!(bone.warnings().is_empty());
+
+        let root = bone.find_bone("Root").unwrap();
+        for (frame, translation) in root_motion.iter().enumerate() {
+            let moved = get_translation(&bone.transforms()[root][frame]);
+            let still = get_translation(&plain.transforms()[root][frame]);
+            assert!((moved - (still + translation)).magnitude() < 1e-6);
+        }
+        let spine = bone.find_bone("Spine").unwrap();
+        assert_eq!(bone.transforms()[spine], plain.transforms()[spine]);
+        assert_eq!(bone.bind_pose(), plain.bind_pose());
+
+        // without the option the track is only kept as the footer
+        let mut unread = AnimDataBone::new();
+        unread.load(&mut File::open("./src/tests/anim-root-motion.lab").unwrap()).unwrap();
+        assert_eq!(unread.root_motion(), None);
+        assert_eq!(unread.footer().len(), 24);
+        assert_eq!(unread.transforms(), plain.transforms());
+
+        // a footer of another size isn't read as one
+        let mut bytes = std::fs::read("./src/tests/anim-root-motion.lab").unwrap();
+        bytes.pop();
+        let mut misfit = AnimDataBone::with_options(ExportOptions { root_motion_track: true, ..Default::default() });
+        misfit.load(&mut std::io::Cursor::new(bytes)).unwrap();
+        assert_eq!(misfit.root_motion(), None);
+        assert_eq!(misfit.warnings().len(), 2);
+    }
+
+    #[test]
+    fn it_writes_the_keys_of_the_exported_frames() {
+        let mut bone = AnimDataBone::with_options(ExportOptions {
+            root_motion_track: true,
+            center_root: true,
+            root_transform: Some(Matrix4::from_angle_x(Rad(-std::f32::consts::FRAC_PI_2))),
+            ..Default::default()
+        });
+        bone.load(&mut File::open("./src/tests/anim-root-motion.lab").unwrap()).unwrap();
+
+        // the track is in the keys of the root now, reading it from the footer again would add it twice
+        let mut written = Vec::new();
+        bone.write_lab(&mut written).unwrap();
+        let mut reloaded = AnimDataBone::new();
+        reloaded.load(&mut std::io::Cursor::new(written)).unwrap();
+        assert!(reloaded.footer().is_empty());
+        assert!(reloaded.warnings().is_empty());
+        for (frames, reloaded_frames) in bone.transforms().iter().zip(reloaded.transforms()) {
+            for (matrix, reloaded_matrix) in frames.iter().zip(reloaded_frames) {
+                for column in 0..4 {
+                    assert!((matrix[column] - reloaded_matrix[column]).magnitude() < 1e-5, "{:?}", reloaded_matrix);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn it_builds_an_animation_from_scratch() {
+        let mut bone = AnimDataBone::new();
+        bone.set_key_kind(KeyKind::Quaternion);
+        bone.set_frame_count(2);
+        let root = bone.push_bone("Root", 0, u32::MAX, Matrix4::identity());
+        let child = bone.push_bone("Child", 1, 0, Matrix4::from_translation(Vector3::new(0.0, -1.0, 0.0)));
+        bone.push_dummy(0, 1, Matrix4::identity());
+
+        let rotation = Quaternion::new(1.0, 0.0, 0.0, 0.0);
+        for frame in 0..2 {
+            bone.push_quaternion_key(root, Vector3::new(frame as f32, 0.0, 0.0), rotation);
+            bone.push_quaternion_key(child, Vector3::new(0.0, 1.0, 0.0), rotation);
+        }
+        bone.finish().unwrap();
+        assert_eq!(bone.get_world_transforms_for_frame(1)[child][0][3], 1.0);
+
+        let mut written = Vec::new();
+        bone.write_lab(&mut written).unwrap();
+        let mut loaded = AnimDataBone::new();
+        loaded.load(&mut std::io::Cursor::new(written)).unwrap();
+        assert_eq!(loaded.get_num_bones(), 2);
+        assert_eq!(loaded.get_bone_name(child), "Child");
+        assert_eq!(loaded.get_bone_parent_id(child), 0);
+        assert!(crate::anim::diff::diff(&bone, &loaded, Some(0.0)).is_empty());
+    }
+
+    #[test]
+    fn it_keeps_the_bone_order_of_the_generated_matrices() {
+        let mut bone = AnimDataBone::new();
+        bone.load(&mut File::open("./src/tests/anim-quat.lab").unwrap()).unwrap();
+
+        let matrices = bone.transformation_matrices.clone();
+        assert_eq!(matrices.len(), bone.get_num_bones());
+        for (bone_index, frames) in matrices.iter().enumerate() {
+            assert_eq!(frames.len(), bone.get_num_frames());
+            for (frame, matrix) in frames.iter().enumerate() {
+                assert_eq!(*matrix, bone.get_key_matrix(bone_index, frame));
+            }
+        }
+    }
+
+    #[test]
+    fn it_rejects_bone_names_that_sanitize_to_the_same_id() {
+        let mut bone = AnimDataBone::with_options(ExportOptions { validate: true, ..Default::default() });
+        bone.set_key_kind(KeyKind::Quaternion);
+        bone.set_frame_count(1);
+        let root = bone.push_bone("Bip01 Head", 0, u32::MAX, Matrix4::identity());
+        let child = bone.push_bone("Bip01_Head", 1, 0, Matrix4::identity());
+        for &index in [root, child].iter() {
+            bone.push_quaternion_key(index, Vector3::new(0.0, 0.0, 0.0), Quaternion::from_angle_z(Rad(0.0)));
+        }
+
+        match bone.finish() {
+            Err(LabError::DuplicateId(id)) => assert_eq!(id, "Bip01_Head_pose_matrix-input"),
+            result => panic!("expected a duplicate id, got {:?}", result),
+        }
+
+        let mut anim_data = AnimDataBone::new();
+        anim_data.load(&mut File::open("./src/tests/anim-quat.lab").unwrap()).unwrap();
+        anim_data.validate_ids().unwrap();
+    }
+
+    #[test]
+    fn it_reparents_the_children_of_ignored_bones() {
+        let build = |options: ExportOptions| {
+            let mut bone = AnimDataBone::with_options(options);
+            bone.set_key_kind(KeyKind::Quaternion);
+            bone.set_frame_count(3);
+            let root = bone.push_bone("Root", 0, u32::MAX, Matrix4::identity());
+            let middle = bone.push_bone("Middle", 1, 0, Matrix4::identity());
+            let leaf = bone.push_bone("Leaf", 2, 1, Matrix4::identity());
+            bone.push_dummy(0, 1, Matrix4::from_translation(Vector3::new(0.0, 0.0, 1.0)));
+
+            for frame in 0..3 {
+                let angle = Rad(0.3 * frame as f32);
+                bone.push_quaternion_key(root, Vector3::new(frame as f32, 0.0, 0.0), Quaternion::from_angle_z(angle));
+                bone.push_quaternion_key(middle, Vector3::new(0.0, 2.0, 0.0), Quaternion::from_angle_x(angle));
+                bone.push_quaternion_key(leaf, Vector3::new(0.0, 1.0, 0.0), Quaternion::from_angle_y(angle));
+            }
+            bone.finish().unwrap();
+            bone
+        };
+
+        let original = build(ExportOptions::default());
+        let pruned = build(ExportOptions {
+            ignored_bones: vec!["Middle".to_string()],
+            dummy_space: DummySpace::Local,
+            ..Default::default()
+        });
+
+        assert_eq!(pruned.get_num_bones(), 2);
+        assert_eq!(pruned.find_bone("Middle"), None);
+        let leaf = pruned.find_bone("Leaf").unwrap();
+        assert_eq!(pruned.get_bone_parent_id(leaf), 0);
+        assert_eq!(pruned.get_bone_children(0), &[leaf]);
+
+        for frame in 0..3 {
+            let expected = original.get_world_transforms_for_frame(frame)[2];
+            let actual = pruned.get_world_transforms_for_frame(frame)[leaf];
+            for i in 0..4 {
+                for j in 0..4 {
+                    assert!((expected[i][j] - actual[i][j]).abs() < 1e-5, "{:?} != {:?}", expected, actual);
+                }
+            }
+        }
+
+        let dummies: Vec<DummyView> = pruned.dummies().collect();
+        assert_eq!(dummies[0].parent_bone_name.as_deref(), Some("Root"));
+        assert_eq!(get_translation(&dummies[0].matrix), Vector3::new(0.0, 2.0, 1.0));
+
+        // the keys of the leaf are rewritten relative to the root, so the saved file animates the same
+        let mut written = Vec::new();
+        pruned.write_lab(&mut written).unwrap();
+        let mut reloaded = AnimDataBone::new();
+        reloaded.load(&mut std::io::Cursor::new(written)).unwrap();
+        for frame in 0..3 {
+            let expected = original.get_world_transforms_for_frame(frame)[2];
+            let actual = reloaded.get_world_transforms_for_frame(frame)[leaf];
+            for i in 0..4 {
+                for j in 0..4 {
+                    assert!((expected[i][j] - actual[i][j]).abs() < 1e-5, "{:?} != {:?}", expected, actual);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn it_leaves_out_the_bones_deeper_than_the_max_depth() {
+        let mut original = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        original.load(&mut file).unwrap();
+        let mut pruned = AnimDataBone::with_options(ExportOptions { max_depth: Some(2), ..Default::default() });
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        pruned.load(&mut file).unwrap();
+
+        let kept: Vec<usize> = (0..original.get_num_bones()).filter(|&bone| original.bone_depth(bone) <= 2).collect();
+        assert!(kept.len() < original.get_num_bones());
+        assert_eq!(pruned.get_num_bones(), kept.len());
+        for (bone_index, &original_index) in kept.iter().enumerate() {
+            assert_eq!(pruned.get_bone_name(bone_index), original.get_bone_name(original_index));
+            assert_eq!(pruned.get_bone_parent_id(bone_index), original.get_bone_parent_id(original_index));
+            assert!(pruned.bone_depth(bone_index) <= 2);
+        }
+        assert_eq!(pruned.find_bone("Bip01 L Finger0Nub"), None);
+        assert!(pruned.validate().is_valid());
+
+        let world_matrices = (original.get_world_transforms_for_frame(10), pruned.get_world_transforms_for_frame(10));
+        for (bone_index, &original_index) in kept.iter().enumerate() {
+            assert_eq!(world_matrices.0[original_index], world_matrices.1[bone_index]);
+        }
+
+        // the pruned bones have no animation channel left
+        let xml_content = pruned.to_collada();
+        assert_eq!(xml_content.matches("<channel ").count(), kept.len());
+    }
+
+    #[test]
+    fn it_reads_back_every_frame_of_matrix_keys() {
+        let mut mat43_bone = AnimDataBone::new();
+        mat43_bone.set_key_kind(KeyKind::Mat43);
+        mat43_bone.set_frame_count(3);
+        let mut mat44_bone = AnimDataBone::new();
+        mat44_bone.set_key_kind(KeyKind::Mat44);
+        mat44_bone.set_frame_count(3);
+
+        for anim_data in [&mut mat43_bone, &mut mat44_bone].iter_mut() {
+            anim_data.push_bone("Root", 0, u32::MAX, Matrix4::identity());
+            anim_data.push_bone("Child", 1, 0, Matrix4::identity());
+        }
+        for bone_index in 0..2 {
+            for frame in 0..3 {
+                let offset = (10 * bone_index + frame) as f32;
+                mat43_bone.push_mat43_key(bone_index, lwMatrix43 {
+                    matrix: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [offset, 0.0, 0.0]],
+                });
+                mat44_bone.push_mat44_key(bone_index, lwMatrix44::new([
+                    [1.0, 0.0, 0.0, 0.0],
+                    [0.0, 1.0, 0.0, 0.0],
+                    [0.0, 0.0, 1.0, 0.0],
+                    [offset, 0.0, 0.0, 1.0],
+                ]));
+            }
+        }
+
+        for anim_data in [&mut mat43_bone, &mut mat44_bone].iter_mut() {
+            anim_data.finish().unwrap();
+
+            let mut written = Vec::new();
+            anim_data.write_lab(&mut written).unwrap();
+            let mut loaded = AnimDataBone::new();
+            loaded.load(&mut std::io::Cursor::new(written)).unwrap();
+
+            assert_eq!(loaded.transforms()[1][2][0][3], 12.0);
+            assert!(crate::anim::diff::diff(anim_data, &loaded, Some(0.0)).is_empty());
+        }
+    }
+
+    #[test]
+    fn it_exposes_the_quaternion_and_position_tracks() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        bone.load(&mut file).unwrap();
+
+        for bone_index in 0..bone.get_num_bones() {
+            assert_eq!(bone.quaternion_track(bone_index).unwrap().len(), bone.get_num_frames());
+            assert_eq!(bone.position_track(bone_index).unwrap().len(), bone.get_num_frames());
+        }
+        assert_eq!(bone.quaternion_track(bone.get_num_bones()), None);
+
+        let mut mat44_bone = AnimDataBone::new();
+        mat44_bone.set_key_kind(KeyKind::Mat44);
+        mat44_bone.set_frame_count(1);
+        let root = mat44_bone.push_bone("Root", 0, u32::MAX, Matrix4::identity());
+        mat44_bone.push_mat44_key(root, lwMatrix44::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]));
+        mat44_bone.finish().unwrap();
+        assert_eq!(mat44_bone.quaternion_track(root), None);
+        assert_eq!(mat44_bone.position_track(root), None);
+    }
+
+    #[test]
+    fn it_reads_matrix_key_files_like_their_quaternion_source() {
+        // the matrix fixtures hold the first 60 frames of anim-quat.lab converted to matrices
+        let mut quat_bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        quat_bone.load(&mut file).unwrap();
+
+        for path in ["./src/tests/anim-mat43.lab", "./src/tests/anim-mat44.lab"].iter() {
+            let mut matrix_bone = AnimDataBone::new();
+            let mut file = File::open(path).unwrap();
+            matrix_bone.load(&mut file).unwrap();
+            assert_eq!(matrix_bone.get_num_frames(), 60);
+
+            for bone_index in 0..quat_bone.get_num_bones() {
+                for frame in 0..60 {
+                    let expected = quat_bone.transformation_matrices[bone_index][frame];
+                    let matrix = matrix_bone.transformation_matrices[bone_index][frame];
+                    for column in 0..4 {
+                        assert!((matrix[column] - expected[column]).magnitude() < 1e-4);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn it_requires_a_key_for_every_frame() {
+        let mut bone = AnimDataBone::new();
+        bone.set_key_kind(KeyKind::Quaternion);
+        bone.set_frame_count(2);
+        let root = bone.push_bone("Root", 0, u32::MAX, Matrix4::identity());
+        bone.push_quaternion_key(root, Vector3::new(0.0, 0.0, 0.0), Quaternion::new(1.0, 0.0, 0.0, 0.0));
+
+        match bone.finish() {
+            Err(LabError::MissingKeys(0)) => {}
+            result => panic!("expected LabError::MissingKeys, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn it_reports_the_bone_a_truncated_file_ends_in() {
+        let mut bytes = std::fs::read("./src/tests/anim-quat.lab").unwrap();
+        // header, two whole bones and half of the third one
+        bytes.truncate(20 + 72 * 2 + 36);
+
+        let mut bone = AnimDataBone::new();
+        match bone.load(&mut std::io::Cursor::new(bytes)) {
+            Err(LabError::TruncatedBone(2)) => {}
+            result => panic!("expected LabError::TruncatedBone(2), got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn it_writes_an_animation_clip_per_action() {
+        let mut bone = AnimDataBone::new();
+        bone.set_clips(AnimationClips::parse("0,99,walk
+100,227,run
+").unwrap());
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        let xml_content = bone.load_from_file(&mut file).unwrap();
+
+        assert!(xml_content.contains("<animation_clip id=\"walk-clip\" name=\"walk\" start=\"0\" end=\"3.96\">"));
+        assert!(xml_content.contains("<animation_clip id=\"run-clip\" name=\"run\" start=\"4\" end=\"9.08\">"));
+        assert!(xml_content.contains("<instance_animation url=\"#Bip01_pose_matrix\"/>"));
+    }
+
+    #[test]
+    fn it_rejects_clips_past_the_last_frame() {
+        let mut bone = AnimDataBone::new();
+        bone.set_clips(AnimationClips::parse("0,228,all
+").unwrap());
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+
+        match bone.load(&mut file) {
+            Err(LabError::ClipOutOfRange(name)) => assert_eq!(name, "all"),
+            result => panic!("expected LabError::ClipOutOfRange, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn it_repeats_the_last_key_of_a_bone_with_missing_frames() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-short-keys.lab").unwrap();
+        bone.load(&mut file).unwrap();
+
+        let head = bone.find_bone("Head").unwrap();
+        assert_eq!(bone.get_key_count(head), 1);
+        assert_eq!(bone.transformation_matrices[head].len(), 2);
+        assert_eq!(bone.transformation_matrices[head][1], bone.transformation_matrices[head][0]);
+        assert_eq!(get_translation(&bone.transformation_matrices[head][1]), Vector3::new(0.0, 1.0, 0.0));
+        assert_eq!(
+            bone.warnings(),
+            ["bone Head only has keys for 1 of 2 frames, the missing frames repeat its last key"]
+        );
+
+        // the bones before it are complete
+        let root = bone.find_bone("Root").unwrap();
+        assert_eq!(get_translation(&bone.transformation_matrices[root][1]), Vector3::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn it_fails_on_short_blocks_with_strict_sizes() {
+        let strict = || AnimDataBone::with_options(ExportOptions { strict_sizes: true, ..Default::default() });
+        let load = |path: &str| strict().load_from_file(&mut File::open(path).unwrap());
+
+        assert!(load("./src/tests/anim-sparse-ids.lab").is_ok());
+        assert!(load("./src/tests/anim-mat43.lab").is_ok());
+        assert!(load("./src/tests/anim-mat44.lab").is_ok());
+        assert!(load("./src/tests/anim-scale.lab").is_ok());
+        match load("./src/tests/anim-short-keys.lab") {
+            Err(LabError::SizeMismatch(message)) => assert_eq!(message, "bone Head has 1 rotation keys for 2 frames"),
+            result => panic!("expected LabError::SizeMismatch, got {:?}", result),
+        }
+        match load("./src/tests/anim-truncated-invmats.lab") {
+            Err(LabError::SizeMismatch(message)) => {
+                assert_eq!(message, "it ends after 1 of the 3 inverse bind matrices")
+            }
+            result => panic!("expected LabError::SizeMismatch, got {:?}", result),
+        }
+
+        // a mat44 file cut off in the keys of its second bone, and one whose keys were lost
+        let mut bytes = std::fs::read("./src/tests/anim-mat44.lab").unwrap();
+        let key_start = bytes.len() - 35 * 60 * MAT44_BYTES;
+        bytes.truncate(key_start + 60 * MAT44_BYTES + 10 * MAT44_BYTES);
+        match strict().load(&mut std::io::Cursor::new(bytes)) {
+            Err(LabError::SizeMismatch(message)) => {
+                assert_eq!(message, "bone Bip01 Footsteps has 10 matrix keys for 60 frames")
+            }
+            result => panic!("expected LabError::SizeMismatch, got {:?}", result),
+        }
+
+        let mut bone = AnimDataBone::new();
+        bone.load(&mut File::open("./src/tests/anim-sparse-ids.lab").unwrap()).unwrap();
+        bone.key_seq[1].pos_seq.as_mut().unwrap().pop();
+        match bone.check_sizes() {
+            Err(LabError::SizeMismatch(message)) => assert_eq!(message, "bone Spine has 1 position keys for 2 frames"),
+            result => panic!("expected LabError::SizeMismatch, got {:?}", result),
+        }
+        bone.key_seq.pop();
+        assert!(matches!(bone.check_sizes(), Err(LabError::SizeMismatch(_))));
+    }
+
+    #[test]
+    fn it_warns_about_unknown_key_types() {
+        let mut bytes = std::fs::read("./src/tests/anim-no-frames.lab").unwrap();
+        bytes[16..20].copy_from_slice(&7u32.to_le_bytes());
+
+        let mut bone = AnimDataBone::new();
+        bone.load(&mut std::io::Cursor::new(bytes)).unwrap();
+        assert_eq!(bone.key_kind(), KeyKind::Invalid);
+        assert_eq!(bone.warnings(), ["the file has the unknown key type 7, its keys aren't read"]);
+    }
+
+    #[test]
+    fn it_binds_bones_missing_their_inverse_bind_matrix_at_the_origin() {
+        let mut complete = AnimDataBone::new();
+        complete.load(&mut File::open("./src/tests/anim-sparse-ids.lab").unwrap()).unwrap();
+
+        // the file ends halfway through the inverse bind matrix of the second bone
+        let mut bone = AnimDataBone::new();
+        bone.set_skin_weights(SkinWeights::parse("0:1\n1:0.5 2:0.5\n").unwrap());
+        let mut file = File::open("./src/tests/anim-truncated-invmats.lab").unwrap();
+        let xml_content = bone.load_from_file(&mut file).unwrap();
+
+        let inverse_bind_matrices = bone.get_inverse_bind_matrices();
+        assert_eq!(inverse_bind_matrices.len(), 3);
+        assert_eq!(inverse_bind_matrices[0], complete.get_inverse_bind_matrices()[0]);
+        assert_eq!(inverse_bind_matrices[1], Matrix4::identity());
+        assert_eq!(inverse_bind_matrices[2], Matrix4::identity());
+        assert_eq!(
+            bone.warnings()[0],
+            "the file ends in the inverse bind matrix of bone Spine, the 2 missing ones are set to the identity"
+        );
+        assert_eq!(read_float_array(&xml_content, "Skin-bind_poses-array").len(), 16 * 3);
+        assert_eq!(bone.bounds(), (Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn it_streams_the_document_into_a_writer() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        bone.load(&mut file).unwrap();
+
+        let mut buffer = Vec::new();
+        bone.write_collada(&mut buffer).unwrap();
+
+        let content = String::from_utf8(buffer).unwrap();
+        assert!(content.starts_with(XML_PROLOG));
+        assert!(content[XML_PROLOG.len()..].starts_with("<COLLADA"));
+        assert!(content.trim_end().ends_with("</COLLADA>"));
+    }
+
+    #[test]
+    fn it_mirrors_a_left_handed_skeleton() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        bone.load(&mut file).unwrap();
+        assert_eq!(bone.handedness(), Handedness::Right);
+
+        let mut left_handed = AnimDataBone::with_options(ExportOptions {
+            auto_handedness: true,
+            ..Default::default()
+        });
+        let mut file = File::open("./src/tests/anim-left-handed.lab").unwrap();
+        left_handed.load(&mut file).unwrap();
+        assert_eq!(left_handed.handedness(), Handedness::Left);
+
+        // the mirrored world transforms of the bind pose line up with the file's bind matrices again
+        let world_matrices = left_handed.get_world_transforms_for_frame(0);
+        for i in 0..left_handed.get_num_bones() {
+            let product = world_matrices[i] * left_handed.invmat_seq[i].matrix;
+            for column in 0..4 {
+                assert!((product[column] - Matrix4::<f32>::identity()[column]).magnitude() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn it_reads_an_inline_dummy_count() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-inline-dummies.lab").unwrap();
+        bone.load(&mut file).unwrap();
+
+        assert_eq!(bone.header.dummy_num, 1);
+        assert_eq!(bone.dummy_seq[&1][0].id, 0);
+        assert_eq!(get_translation(&bone.dummy_seq[&1][0].mat.matrix), Vector3::new(0.0, 0.5, 0.0));
+
+        // the key data after the dummy block is read from the right place
+        let root = bone.find_bone("Root").unwrap();
+        assert_eq!(get_translation(&bone.transformation_matrices[root][1]), Vector3::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn it_caps_the_number_of_exported_frames() {
+        let mut bone = AnimDataBone::with_options(ExportOptions {
+            max_frames: Some(10),
+            inject_bind_pose: true,
+            ..Default::default()
+        });
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        let xml_content = bone.load_from_file(&mut file).unwrap();
+
+        // the bind pose is injected in front of the first 10 frames
+        assert_eq!(read_float_array(&xml_content, "Bip01_pose_matrix-input-array").len(), 11);
+        assert_eq!(read_float_array(&xml_content, "Bip01_pose_matrix-output-array").len(), 16 * 11);
+        assert!(xml_content.contains("<float_array id=\"Bip01_pose_matrix-input-array\" count=\"11\">"));
+        assert!(xml_content.contains("<Name_array id=\"Bip01_pose_matrix-interpolation-array\" count=\"11\">"));
+    }
+
+    #[test]
+    fn it_writes_matrices_with_the_requested_precision() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        let xml_content = bone.load_from_file(&mut file).unwrap();
+
+        let mut rounded = AnimDataBone::with_options(ExportOptions {
+            precision: Some(4),
+            ..Default::default()
+        });
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        let rounded_xml_content = rounded.load_from_file(&mut file).unwrap();
+        assert!(rounded_xml_content.len() < xml_content.len());
+
+        let values = read_float_array(&xml_content, "Bip01_pose_matrix-output-array");
+        let rounded_values = read_float_array(&rounded_xml_content, "Bip01_pose_matrix-output-array");
+        assert_eq!(values.len(), rounded_values.len());
+        for (value, rounded_value) in values.iter().zip(rounded_values.iter()) {
+            assert!((value - rounded_value).abs() <= value.abs() * 1e-3 + 1e-6);
+        }
+    }
+
+    #[test]
+    fn it_animates_joints_with_continuous_euler_angles() {
+        let mut bone = AnimDataBone::with_options(ExportOptions {
+            euler_order: Some(EulerOrder::Xyz),
+            ..Default::default()
+        });
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        let xml_content = bone.load_from_file(&mut file).unwrap();
+
+        assert!(!xml_content.contains("target=\"Bip01_Pelvis/transform\""));
+        assert!(xml_content.contains("<translate sid=\"location\">"));
+        assert!(xml_content.contains("<rotate sid=\"rotationX\">"));
+        assert!(xml_content.contains("target=\"Bip01_Pelvis/rotationZ.ANGLE\""));
+        assert!(xml_content.contains("target=\"Bip01_Pelvis/location\""));
+
+        let locations = read_float_array(&xml_content, "Bip01_Pelvis_location-output-array");
+        assert_eq!(locations.len(), 3 * 228);
+        for axis in ["X", "Y", "Z"].iter() {
+            let angles = read_float_array(&xml_content, &format!("Bip01_Pelvis_rotation{}-output-array", axis));
+            assert_eq!(angles.len(), 228);
+            assert!(angles.windows(2).all(|pair| (pair[1] - pair[0]).abs() < 180.0));
+        }
+    }
+
+    #[test]
+    fn it_tags_joints_and_dummies_with_their_layer() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        let xml_content = bone.load_from_file(&mut file).unwrap();
+
+        let count_layer = |layer: &str| {
+            xml_content
+                .split("<technique profile=\"lab-parser\">")
+                .skip(1)
+                .filter(|technique| technique.split("</layer>").next().unwrap().contains(layer))
+                .count()
+        };
+        assert_eq!(count_layer("bone"), 35);
+        assert_eq!(count_layer("attachment"), 2);
+    }
+
+    #[test]
+    fn it_restores_an_animation_from_its_cache() {
+        let options = ExportOptions {
+            center_root: true,
+            ignored_bones: vec!["Spine".to_string()],
+            interleaved_keys: true,
+            ..Default::default()
+        };
+        let mut bone = AnimDataBone::with_options(options.clone());
+        bone.load(&mut File::open("./src/tests/anim-interleaved.lab").unwrap()).unwrap();
+        let mut cache = Vec::new();
+        bone.write_cache(&mut cache).unwrap();
+
+        let mut restored = AnimDataBone::with_options(options.clone());
+        restored.load_cache(&mut cache.as_slice()).unwrap();
+        assert_eq!(restored.summary(), bone.summary());
+        assert_eq!(restored.transforms(), bone.transforms());
+        assert_eq!(restored.bind_pose(), bone.bind_pose());
+        for i in 0..bone.get_num_bones() {
+            assert_eq!(restored.get_key_matrices(i), bone.get_key_matrices(i));
+            assert_eq!(restored.ancestors(i), bone.ancestors(i));
+        }
+
+        // a cache is only used with the options it was written with
+        let mut other = AnimDataBone::new();
+        match other.load_cache(&mut cache.as_slice()) {
+            Err(LabError::StaleCache) => {}
+            result => panic!("expected LabError::StaleCache, got {:?}", result),
+        }
+        match AnimDataBone::new().load_cache(&mut &cache[..cache.len() / 2]) {
+            Err(LabError::StaleCache) => {}
+            result => panic!("expected LabError::StaleCache, got {:?}", result),
+        }
+
+        // options that only change the output don't make it stale
+        let mut reduced = AnimDataBone::with_options(ExportOptions {
+            reduce_keys: true,
+            precision: Some(4),
+            ..options.clone()
+        });
+        reduced.load_cache(&mut cache.as_slice()).unwrap();
+        assert_eq!(reduced.transforms(), bone.transforms());
+
+        // and it goes through the checks of `load`
+        let mut weighted = AnimDataBone::with_options(options);
+        weighted.set_skin_weights(SkinWeights::parse("0:1\n1:0.5 3:0.5\n").unwrap());
+        match weighted.load_cache(&mut cache.as_slice()) {
+            Err(LabError::SkinWeightsOutOfRange(3, 2)) => {}
+            result => panic!("expected LabError::SkinWeightsOutOfRange, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn it_merges_animations_of_the_same_skeleton() {
+        let mut walk = AnimDataBone::new();
+        walk.load(&mut File::open("./src/tests/anim-sparse-ids.lab").unwrap()).unwrap();
+        let mut run = AnimDataBone::with_options(ExportOptions {
+            interleaved_keys: true,
+            max_frames: Some(1),
+            ..Default::default()
+        });
+        run.load(&mut File::open("./src/tests/anim-interleaved.lab").unwrap()).unwrap();
+
+        let xml_content = to_merged_collada(&[("walk", &walk), ("run fast", &run)]).unwrap();
+        assert_eq!(xml_content.matches("<node id=\"Spine\"").count(), 1);
+        assert_eq!(read_float_array(&xml_content, "walk_Spine_pose_matrix-input-array").len(), 2);
+        assert_eq!(read_float_array(&xml_content, "run_fast_Spine_pose_matrix-input-array").len(), 1);
+        assert_eq!(xml_content.matches("target=\"Spine/transform\"").count(), 2);
+        assert!(xml_content.contains("<instance_animation url=\"#run_fast\"/>"));
+
+        let mut other = AnimDataBone::new();
+        other.load(&mut File::open("./src/tests/anim-scale.lab").unwrap()).unwrap();
+        match to_merged_collada(&[("walk", &walk), ("other", &other)]) {
+            Err(LabError::MismatchedSkeleton(name)) => assert_eq!(name, "other"),
+            result => panic!("expected LabError::MismatchedSkeleton, got {:?}", result),
+        }
+        assert!(matches!(to_merged_collada(&[]), Err(LabError::NoAnimations)));
+
+        // a group id can't take the id of a node, of another group or of another group's clip
+        let collisions = [(["walk", "Spine"], "Spine"), (["walk", "walk"], "walk"), (["walk", "walk-clip"], "walk-clip")];
+        for (names, duplicate) in collisions.iter() {
+            match to_merged_collada(&[(names[0], &walk), (names[1], &walk)]) {
+                Err(LabError::DuplicateId(id)) => assert_eq!(id, *duplicate),
+                result => panic!("expected LabError::DuplicateId, got {:?}", result),
+            }
+        }
+    }
+
+    #[test]
+    fn it_takes_the_bone_names_of_a_reference_skeleton() {
+        let mut reference = AnimDataBone::new();
+        for (i, name) in ["Bip01", "Bip01 Spine", "Bip01 Head"].iter().enumerate() {
+            reference.push_bone(name, i as u32, u32::MAX, Matrix4::identity());
+        }
+
+        let mut bone = AnimDataBone::new();
+        bone.load(&mut File::open("./src/tests/anim-sparse-ids.lab").unwrap()).unwrap();
+        let original = bone.clone();
+        bone.take_bone_names_from(&reference).unwrap();
+
+        assert_eq!(bone.get_bone_name(1), "Bip01 Spine");
+        assert_eq!(bone.find_bone("Bip01 Spine"), Some(1));
+        assert_eq!(bone.find_bone("Spine"), None);
+        assert_eq!(bone.transforms(), original.transforms());
+        assert_eq!(bone.get_bone_parent_id(1), original.get_bone_parent_id(1));
+        let xml_content = bone.to_collada();
+        assert!(xml_content.contains("<node id=\"Bip01_Spine\""));
+        assert!(!xml_content.contains("<node id=\"Spine\""));
+
+        let mut other = AnimDataBone::new();
+        other.load(&mut File::open("./src/tests/anim-quat.lab").unwrap()).unwrap();
+        match other.take_bone_names_from(&reference) {
+            Err(LabError::BoneCountMismatch(expected, found)) => assert_eq!((expected, found), (3, 35)),
+            result => panic!("expected LabError::BoneCountMismatch, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn it_reads_keys_with_interleaved_positions_and_rotations() {
+        let mut planar = AnimDataBone::new();
+        planar.load(&mut File::open("./src/tests/anim-sparse-ids.lab").unwrap()).unwrap();
+
+        // the same keys, with the position and rotation of every frame side by side
+        let mut interleaved = AnimDataBone::with_options(ExportOptions {
+            interleaved_keys: true,
+            ..Default::default()
+        });
+        interleaved.load(&mut File::open("./src/tests/anim-interleaved.lab").unwrap()).unwrap();
+        let mut misread = AnimDataBone::new();
+        misread.load(&mut File::open("./src/tests/anim-interleaved.lab").unwrap()).unwrap();
+
+        for i in 0..planar.get_num_bones() {
+            assert_eq!(interleaved.get_key_matrices(i), planar.get_key_matrices(i));
+        }
+        assert!((0..planar.get_num_bones()).any(|i| misread.get_key_matrices(i) != planar.get_key_matrices(i)));
+    }
+
+    #[test]
+    fn it_reads_the_scale_of_quaternion_keys() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-scale.lab").unwrap();
+        bone.load(&mut file).unwrap();
+
+        let child = bone.find_bone("Child").unwrap();
+        assert_eq!(bone.bone_scales(child).unwrap()[1], Vector3::new(2.0, 2.0, 2.0));
+
+        // the scale applies before the translation, so the child's offset itself isn't scaled
+        let world_matrices = bone.get_world_transforms_for_frame(1);
+        assert_eq!(get_translation(&world_matrices[child]), Vector3::new(0.0, 1.0, 0.0));
+        assert!((world_matrices[child].determinant() - 8.0).abs() < 1e-5);
+
+        let mut written = Vec::new();
+        bone.write_lab(&mut written).unwrap();
+        assert!(written == std::fs::read("./src/tests/anim-scale.lab").unwrap());
+    }
+
+    #[test]
+    fn it_bakes_world_space_transforms_into_a_flat_hierarchy() {
+        let mut bone = AnimDataBone::new();
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        bone.load(&mut file).unwrap();
+        let world_matrix = bone.get_world_transforms_for_frame(10)[14];
+
+        let mut world_space = AnimDataBone::with_options(ExportOptions {
+            world_space: true,
+            ..Default::default()
+        });
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        let xml_content = world_space.load_from_file(&mut file).unwrap();
+
+        let values = read_float_array(&xml_content, "Bip01_L_Finger0Nub_pose_matrix-output-array");
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((values[16 * 10 + 4 * i + j] - world_matrix[i][j]).abs() < 1e-4);
+            }
+        }
+
+        // every joint sits right under the skeleton node
+        let mut depth = 0;
+        for tag in xml_content.split('<').skip(1) {
+            if tag.starts_with("node ") {
+                depth += 1;
+                if tag.contains("type=\"JOINT\"") {
+                    assert_eq!(depth, 2);
+                }
+            } else if tag.starts_with("/node>") {
+                depth -= 1;
+            }
+        }
+    }
+
+    #[test]
+    fn it_removes_the_mirror_of_mirrored_bones_without_moving_them() {
+        let build = |fix_mirror| {
+            let mut anim_data = AnimDataBone::with_options(ExportOptions { fix_mirror, ..Default::default() });
+            anim_data.set_key_kind(KeyKind::Mat44);
+            anim_data.set_frame_count(2);
+
+            let hand_local = Matrix4::from_translation(Vector3::new(2.0, 0.0, 0.0));
+            let arm_locals: Vec<Matrix4<f32>> = [0.5, 1.0]
+                .iter()
+                .map(|&angle| {
+                    Matrix4::from_nonuniform_scale(-1.0, 1.0, 1.0)
+                        * Matrix4::from_angle_z(Rad(angle))
+                        * Matrix4::from_translation(Vector3::new(0.0, 1.0, 0.0))
+                })
+                .collect();
+
+            let root = anim_data.push_bone("Root", 0, u32::MAX, Matrix4::identity());
+            let arm = anim_data.push_bone("Arm", 1, 0, arm_locals[0].invert().unwrap());
+            let hand = anim_data.push_bone("Hand", 2, 1, (hand_local * arm_locals[0]).invert().unwrap());
+            for arm_local in arm_locals.iter() {
+                anim_data.push_mat44_key(root, lwMatrix44 { matrix: Matrix4::identity() });
+                anim_data.push_mat44_key(arm, lwMatrix44 { matrix: *arm_local });
+                anim_data.push_mat44_key(hand, lwMatrix44 { matrix: hand_local });
+            }
+            anim_data.finish().unwrap();
+            anim_data
+        };
+        let mirrored = build(false);
+        let fixed = build(true);
+        let arm = 1;
+
+        assert_eq!(mirrored.validate().issues, vec![ValidationIssue::MirroredBone { bone: arm }]);
+        assert!(fixed.validate().is_valid(), "{}", fixed.validate());
+        assert!(fixed.warnings().iter().any(|warning| warning.contains("bone Arm is mirrored")));
+        assert!(fixed.bind_pose()[arm].1.determinant() > 0.0);
+        assert!(fixed.transforms()[arm].iter().all(|matrix| matrix.determinant() > 0.0));
+
+        let inverse_binds = (mirrored.get_inverse_bind_matrices(), fixed.get_inverse_bind_matrices());
+        for frame in 0..2 {
+            let (mirrored_world, fixed_world) =
+                (mirrored.get_world_transforms_for_frame(frame), fixed.get_world_transforms_for_frame(frame));
+            for bone in 0..3 {
+                for axis in 0..3 {
+                    assert!((mirrored_world[bone][axis][3] - fixed_world[bone][axis][3]).abs() < 1e-5);
+                }
+                let mirrored_skin = inverse_binds.0[bone] * mirrored_world[bone];
+                let fixed_skin = inverse_binds.1[bone] * fixed_world[bone];
+                for column in 0..4 {
+                    for row in 0..4 {
+                        assert!((mirrored_skin[column][row] - fixed_skin[column][row]).abs() < 1e-5);
+                    }
+                }
+            }
         }
+
+        // the flipped frames are written as the keys of the arm and the hand
+        let mut written = Vec::new();
+        fixed.write_lab(&mut written).unwrap();
+        let mut reloaded = AnimDataBone::new();
+        reloaded.load(&mut std::io::Cursor::new(written)).unwrap();
+        assert_eq!(reloaded.transforms(), fixed.transforms());
+        assert!(reloaded.validate().is_valid(), "{}", reloaded.validate());
     }
 }
@@ -0,0 +1,115 @@
+use std::fs;
+use std::path::Path;
+
+use super::error::LabError;
+
+/// a named range of frames, both ends included
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationClip {
+    pub start: usize,
+    pub end: usize,
+    pub name: String,
+}
+
+/// the actions packed into a single animation, read from a companion `.clips` file.
+///
+/// the file has one `start,end,name` line per action, where `start` and `end` are the first and last
+/// frame of the action, e.g. `0,24,idle`. empty lines and lines starting with `#` are ignored
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AnimationClips {
+    pub clips: Vec<AnimationClip>,
+}
+
+impl AnimationClips {
+    pub fn load_from_file(path: &Path) -> Result<AnimationClips, LabError> {
+        let content = fs::read_to_string(path).map_err(|why| LabError::from(why).in_file(path))?;
+        AnimationClips::parse(&content).map_err(|why| why.in_file(path))
+    }
+
+    pub fn parse(content: &str) -> Result<AnimationClips, LabError> {
+        let mut clips = Vec::new();
+
+        for (line_index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.splitn(3, ',').map(str::trim);
+            let start = parts.next().and_then(|value| value.parse::<usize>().ok());
+            let end = parts.next().and_then(|value| value.parse::<usize>().ok());
+            let name = parts.next().filter(|name| !name.is_empty());
+
+            match (start, end, name) {
+                (Some(start), Some(end), Some(name)) if start <= end => clips.push(AnimationClip {
+                    start,
+                    end,
+                    name: name.to_string(),
+                }),
+                _ => return Err(LabError::InvalidClip(line_index + 1)),
+            }
+        }
+
+        Ok(AnimationClips { clips })
+    }
+
+    /// checks that every clip ends before `frame_count` and that no two clips share a frame
+    pub fn validate(&self, frame_count: usize) -> Result<(), LabError> {
+        if let Some(clip) = self.clips.iter().find(|clip| clip.end >= frame_count) {
+            return Err(LabError::ClipOutOfRange(clip.name.clone()));
+        }
+
+        let mut sorted: Vec<&AnimationClip> = self.clips.iter().collect();
+        sorted.sort_by_key(|clip| clip.start);
+        for pair in sorted.windows(2) {
+            if pair[1].start <= pair[0].end {
+                return Err(LabError::OverlappingClips(pair[0].name.clone(), pair[1].name.clone()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_clip_ranges() {
+        let clips = AnimationClips::parse("# actions\n0,24,idle\n\n25, 60, run fast\n").unwrap();
+
+        assert_eq!(
+            clips.clips,
+            vec![
+                AnimationClip { start: 0, end: 24, name: "idle".to_string() },
+                AnimationClip { start: 25, end: 60, name: "run fast".to_string() },
+            ]
+        );
+        assert!(clips.validate(61).is_ok());
+    }
+
+    #[test]
+    fn it_reports_the_line_of_a_malformed_clip() {
+        match AnimationClips::parse("0,24,idle\n30,25,walk\n") {
+            Err(LabError::InvalidClip(line)) => assert_eq!(line, 2),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn it_rejects_clips_outside_the_animation_or_overlapping() {
+        let clips = AnimationClips::parse("0,24,idle\n20,40,walk\n").unwrap();
+
+        match clips.validate(30) {
+            Err(LabError::ClipOutOfRange(name)) => assert_eq!(name, "walk"),
+            other => panic!("unexpected result {:?}", other),
+        }
+        match clips.validate(41) {
+            Err(LabError::OverlappingClips(first, second)) => {
+                assert_eq!((first.as_str(), second.as_str()), ("idle", "walk"))
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
@@ -1,6 +1,23 @@
 use serde::{ Serialize, Deserialize };
+use bincode::Options;
 use cgmath::*;
 
+/// size in bytes of a 4x4 matrix of f32 in the file
+pub const MAT44_BYTES: usize = 64;
+
+/// size in bytes of a 4x3 matrix of f32 in the file
+pub const MAT43_BYTES: usize = 48;
+
+/// the bincode configuration the values of the file are decoded with: little-endian numbers of a fixed size,
+/// and arrays of a fixed length written back to back without a length prefix. bytes after the value are left
+/// alone. a big-endian file could be read with `file_encoding().with_big_endian()`
+pub fn file_encoding() -> impl Options + Copy {
+  bincode::DefaultOptions::new()
+    .with_fixint_encoding()
+    .with_little_endian()
+    .allow_trailing_bytes()
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct lwMatrix44 {
   pub matrix: Matrix4<f32>,
@@ -11,7 +28,80 @@ pub struct lwMatrix43 {
   pub matrix: [[f32; 3]; 4],
 }
 
+/// translation part of a matrix, which lives in the last row in the d3d convention used by the game
+pub fn get_translation(matrix: &Matrix4<f32>) -> Vector3<f32> {
+  Vector3::new(matrix[0][3], matrix[1][3], matrix[2][3])
+}
+
+/// splits a local matrix into its translation, rotation and scale. a mirrored matrix gets a negative x scale
+pub fn decompose(matrix: &Matrix4<f32>) -> (Vector3<f32>, Quaternion<f32>, Vector3<f32>) {
+  let mut rows = [
+    Vector3::new(matrix[0][0], matrix[1][0], matrix[2][0]),
+    Vector3::new(matrix[0][1], matrix[1][1], matrix[2][1]),
+    Vector3::new(matrix[0][2], matrix[1][2], matrix[2][2]),
+  ];
+
+  let mut scale = Vector3::new(rows[0].magnitude(), rows[1].magnitude(), rows[2].magnitude());
+  if rows[0].cross(rows[1]).dot(rows[2]) < 0.0 {
+    scale.x = -scale.x;
+  }
+  for (row, scale) in rows.iter_mut().zip([scale.x, scale.y, scale.z].iter()) {
+    if *scale != 0.0 {
+      *row /= *scale;
+    }
+  }
+
+  (get_translation(matrix), get_rotation(&rows), scale)
+}
+
+/// the quaternion of a rotation given by the rows of a row-vector matrix
+fn get_rotation(rows: &[Vector3<f32>; 3]) -> Quaternion<f32> {
+  // element (i, j) of the equivalent column-vector matrix
+  let m = |i: usize, j: usize| rows[j][i];
+
+  let trace = m(0, 0) + m(1, 1) + m(2, 2);
+  let rotation = if trace > 0.0 {
+    let s = 2.0 * (trace + 1.0).sqrt();
+    Quaternion::new(0.25 * s, (m(2, 1) - m(1, 2)) / s, (m(0, 2) - m(2, 0)) / s, (m(1, 0) - m(0, 1)) / s)
+  } else if m(0, 0) > m(1, 1) && m(0, 0) > m(2, 2) {
+    let s = 2.0 * (1.0 + m(0, 0) - m(1, 1) - m(2, 2)).sqrt();
+    Quaternion::new((m(2, 1) - m(1, 2)) / s, 0.25 * s, (m(0, 1) + m(1, 0)) / s, (m(0, 2) + m(2, 0)) / s)
+  } else if m(1, 1) > m(2, 2) {
+    let s = 2.0 * (1.0 + m(1, 1) - m(0, 0) - m(2, 2)).sqrt();
+    Quaternion::new((m(0, 2) - m(2, 0)) / s, (m(0, 1) + m(1, 0)) / s, 0.25 * s, (m(1, 2) + m(2, 1)) / s)
+  } else {
+    let s = 2.0 * (1.0 + m(2, 2) - m(0, 0) - m(1, 1)).sqrt();
+    Quaternion::new((m(1, 0) - m(0, 1)) / s, (m(0, 2) + m(2, 0)) / s, (m(1, 2) + m(2, 1)) / s, 0.25 * s)
+  };
+
+  rotation.normalize()
+}
+
 impl lwMatrix43 {
+  /// checked version of `get_matrix4`.
+  ///
+  /// the 4x3 matrix is stored the way d3d does: the first three rows are the basis vectors
+  /// and the last row is the translation. it is padded into a 4x4 matrix with a `[0, 0, 0, 1]`
+  /// last column, which gives the same layout `lwMatrix44::new` produces for a full d3d matrix.
+  /// returns `None` if the matrix contains non-finite values or its basis is singular, since it
+  /// can't be an affine transform then
+  pub fn to_matrix4(&self) -> Option<Matrix4<f32>> {
+    if self.matrix.iter().flatten().any(|value| !value.is_finite()) {
+      return None;
+    }
+
+    let basis = Matrix3::new(
+      self.matrix[0][0], self.matrix[1][0], self.matrix[2][0],
+      self.matrix[0][1], self.matrix[1][1], self.matrix[2][1],
+      self.matrix[0][2], self.matrix[1][2], self.matrix[2][2],
+    );
+    if basis.determinant().abs() <= f32::EPSILON {
+      return None;
+    }
+
+    Some(self.get_matrix4())
+  }
+
   pub fn get_matrix4(&self) -> Matrix4<f32> {
     Matrix4::new(
       self.matrix[0][0], self.matrix[1][0], self.matrix[2][0], self.matrix[3][0],
@@ -30,7 +120,89 @@ impl lwMatrix44 {
     }
   }
 
+  /// the rows of the matrix in the d3d layout it was read from, the inverse of `new`
+  pub fn to_rows(&self) -> [[f32; 4]; 4] {
+    let mut rows = [[0.0; 4]; 4];
+    for (i, row) in rows.iter_mut().enumerate() {
+      for (j, value) in row.iter_mut().enumerate() {
+        *value = self.matrix[j][i];
+      }
+    }
+    rows
+  }
+
   pub fn default() -> lwMatrix44 {
     lwMatrix44::new([[0.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 0.0]])
   }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn it_decodes_a_matrix_with_the_file_encoding() {
+    let mut bytes = Vec::with_capacity(MAT44_BYTES);
+    for value in 1..=16 {
+      bytes.extend_from_slice(&(value as f32).to_le_bytes());
+    }
+
+    let decoded: [[f32; 4]; 4] = file_encoding().deserialize(&bytes).unwrap();
+    assert_eq!(
+      decoded,
+      [[1.0, 2.0, 3.0, 4.0], [5.0, 6.0, 7.0, 8.0], [9.0, 10.0, 11.0, 12.0], [13.0, 14.0, 15.0, 16.0]]
+    );
+    assert_eq!(lwMatrix44::new(decoded).to_rows(), decoded);
+  }
+
+  #[test]
+  fn it_pads_a_mat43_into_the_mat44_layout() {
+    let mat43 = lwMatrix43 {
+      matrix: [[0.0, 1.0, 0.0], [-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [5.0, 6.0, 7.0]],
+    };
+    let mat44 = lwMatrix44::new([
+      [0.0, 1.0, 0.0, 0.0],
+      [-1.0, 0.0, 0.0, 0.0],
+      [0.0, 0.0, 1.0, 0.0],
+      [5.0, 6.0, 7.0, 1.0],
+    ]);
+
+    let matrix = mat43.to_matrix4().unwrap();
+    assert_eq!(matrix, mat44.matrix);
+    assert_eq!(get_translation(&matrix), Vector3::new(5.0, 6.0, 7.0));
+    assert_eq!(matrix[3], Vector4::new(0.0, 0.0, 0.0, 1.0));
+  }
+
+  #[test]
+  fn it_sizes_the_matrices_like_their_arrays() {
+    assert_eq!(MAT44_BYTES, std::mem::size_of::<[[f32; 4]; 4]>());
+    assert_eq!(MAT43_BYTES, std::mem::size_of::<[[f32; 3]; 4]>());
+    assert_eq!(MAT44_BYTES, std::mem::size_of::<lwMatrix44>());
+    assert_eq!(MAT43_BYTES, std::mem::size_of::<lwMatrix43>());
+  }
+
+  #[test]
+  fn it_returns_the_rows_a_mat44_was_built_from() {
+    let rows = [
+      [1.0, 2.0, 3.0, 4.0],
+      [5.0, 6.0, 7.0, 8.0],
+      [9.0, 10.0, 11.0, 12.0],
+      [13.0, 14.0, 15.0, 16.0],
+    ];
+
+    assert_eq!(lwMatrix44::new(rows).to_rows(), rows);
+  }
+
+  #[test]
+  fn it_rejects_a_mat43_that_is_not_affine() {
+    let singular = lwMatrix43 {
+      matrix: [[1.0, 0.0, 0.0], [2.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 0.0, 0.0]],
+    };
+    let non_finite = lwMatrix43 {
+      matrix: [[f32::NAN, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [0.0, 0.0, 0.0]],
+    };
+
+    assert_eq!(singular.to_matrix4(), None);
+    assert_eq!(non_finite.to_matrix4(), None);
+  }
+}
@@ -0,0 +1,177 @@
+use std::fmt;
+
+use cgmath::Matrix4;
+
+use super::bone::{AnimDataBone, KeyKind};
+
+/// a structural or per-frame difference between two animations
+#[derive(Debug, Clone, PartialEq)]
+pub enum LabDifference {
+    BoneCount(usize, usize),
+    FrameCount(usize, usize),
+    KeyKind(KeyKind, KeyKind),
+    BoneName { bone: usize, a: String, b: String },
+    BoneParent { bone: usize, a: u32, b: u32 },
+    /// the largest difference between the matrix elements of a bone, and the frame it happens at
+    Transform { bone: usize, frame: usize, delta: f32 },
+    /// the largest difference between the matrix elements of a bone's rest pose and its first frame
+    RestPose { bone: usize, name: String, delta: f32 },
+}
+
+impl fmt::Display for LabDifference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LabDifference::BoneCount(a, b) => write!(f, "bone count differs: {} vs {}", a, b),
+            LabDifference::FrameCount(a, b) => write!(f, "frame count differs: {} vs {}", a, b),
+            LabDifference::KeyKind(a, b) => write!(f, "key type differs: {:?} vs {:?}", a, b),
+            LabDifference::BoneName { bone, a, b } => {
+                write!(f, "bone {} name differs: {} vs {}", bone, a, b)
+            }
+            LabDifference::BoneParent { bone, a, b } => {
+                write!(f, "bone {} parent differs: {} vs {}", bone, a, b)
+            }
+            LabDifference::Transform { bone, frame, delta } => write!(
+                f,
+                "bone {} transform differs by up to {} (frame {})",
+                bone, delta, frame
+            ),
+            LabDifference::RestPose { bone, name, delta } => write!(
+                f,
+                "bone {} ({}) rest pose differs from frame 0 by up to {}",
+                bone, name, delta
+            ),
+        }
+    }
+}
+
+/// compares two loaded animations. the per-frame transforms are only compared when a threshold is given,
+/// in which case every bone whose matrices differ by more than it in any frame is reported once
+pub fn diff(a: &AnimDataBone, b: &AnimDataBone, threshold: Option<f32>) -> Vec<LabDifference> {
+    let mut differences = Vec::new();
+
+    if a.get_num_bones() != b.get_num_bones() {
+        differences.push(LabDifference::BoneCount(a.get_num_bones(), b.get_num_bones()));
+    }
+    if a.get_num_frames() != b.get_num_frames() {
+        differences.push(LabDifference::FrameCount(a.get_num_frames(), b.get_num_frames()));
+    }
+    if a.key_kind() != b.key_kind() {
+        differences.push(LabDifference::KeyKind(a.key_kind(), b.key_kind()));
+    }
+
+    let bone_count = a.get_num_bones().min(b.get_num_bones());
+    for bone in 0..bone_count {
+        let (name_a, name_b) = (a.get_bone_name(bone), b.get_bone_name(bone));
+        if name_a != name_b {
+            differences.push(LabDifference::BoneName {
+                bone,
+                a: name_a,
+                b: name_b,
+            });
+        }
+
+        let (parent_a, parent_b) = (a.get_bone_parent_id(bone), b.get_bone_parent_id(bone));
+        if parent_a != parent_b {
+            differences.push(LabDifference::BoneParent {
+                bone,
+                a: parent_a,
+                b: parent_b,
+            });
+        }
+    }
+
+    if let Some(threshold) = threshold {
+        let frames_a = a.transforms();
+        let frames_b = b.transforms();
+
+        for bone in 0..bone_count {
+            let mut largest: Option<(usize, f32)> = None;
+
+            for (frame, (matrix_a, matrix_b)) in frames_a[bone].iter().zip(frames_b[bone].iter()).enumerate() {
+                let delta = get_largest_delta(matrix_a, matrix_b);
+                if delta > threshold && largest.map_or(true, |(_, largest_delta)| delta > largest_delta) {
+                    largest = Some((frame, delta));
+                }
+            }
+
+            if let Some((frame, delta)) = largest {
+                differences.push(LabDifference::Transform { bone, frame, delta });
+            }
+        }
+    }
+
+    differences
+}
+
+/// compares the rest pose of every bone with its first frame, which are the same matrix in a file that was
+/// parsed right: the rest pose is read from the keys of frame 0. bones whose matrices differ by more than the
+/// epsilon of the export options point at a parsing or a convention bug, e.g. a transform applied to only one
+/// of them
+pub fn diff_rest_pose(anim_data: &AnimDataBone) -> Vec<LabDifference> {
+    let frames = anim_data.transforms();
+
+    anim_data
+        .bind_pose()
+        .into_iter()
+        .enumerate()
+        .filter_map(|(bone, (name, rest_matrix))| {
+            let delta = get_largest_delta(&rest_matrix, frames[bone].first()?);
+            if delta > anim_data.get_epsilon() {
+                Some(LabDifference::RestPose { bone, name, delta })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+fn get_largest_delta(a: &Matrix4<f32>, b: &Matrix4<f32>) -> f32 {
+    let matrix_delta = a - b;
+    (0..4)
+        .flat_map(|column| (0..4).map(move |row| (column, row)))
+        .map(|(column, row)| matrix_delta[column][row].abs())
+        .fold(0.0, f32::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    fn load(path: &str) -> AnimDataBone {
+        let mut anim_data = AnimDataBone::new();
+        let mut file = File::open(path).unwrap();
+        anim_data.load(&mut file).unwrap();
+        anim_data
+    }
+
+    #[test]
+    fn it_finds_no_differences_between_identical_files() {
+        let a = load("./src/tests/anim-quat.lab");
+        let b = load("./src/tests/anim-quat.lab");
+
+        assert_eq!(diff(&a, &b, Some(0.0)), vec![]);
+    }
+
+    #[test]
+    fn it_reports_structural_differences() {
+        let a = load("./src/tests/anim-quat.lab");
+        let b = load("./src/tests/anim-sparse-ids.lab");
+
+        let differences = diff(&a, &b, None);
+        assert!(differences.contains(&LabDifference::BoneCount(35, 3)));
+        assert!(differences.contains(&LabDifference::FrameCount(228, 2)));
+        assert!(differences.contains(&LabDifference::BoneName {
+            bone: 0,
+            a: String::from("Bip01"),
+            b: String::from("Root"),
+        }));
+    }
+
+    #[test]
+    fn it_finds_the_rest_pose_of_a_sample_file_at_frame_0() {
+        for path in ["./src/tests/anim-quat.lab", "./src/tests/anim-mat43.lab", "./src/tests/anim-scale.lab"].iter() {
+            assert_eq!(diff_rest_pose(&load(path)), vec![], "{}", path);
+        }
+    }
+}
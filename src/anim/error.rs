@@ -0,0 +1,147 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::bone::KeyKind;
+
+/// errors that can happen while reading or converting animation data
+#[derive(Debug)]
+pub enum LabError {
+    Io(io::Error),
+    /// the file has key data but zero frames, so there is nothing to animate
+    NoFrames,
+    /// the file has no bones, so there is no skeleton to export
+    NoBones,
+    /// no bone of the file has the parent id of a root, so there is no root joint to export the skeleton from
+    NoRoot,
+    /// the parent id of the bone at this index doesn't match any bone
+    DanglingParent(usize),
+    /// the file ends in the middle of the bone at this index
+    TruncatedBone(usize),
+    /// the bone at this index doesn't have a key for every frame of the animation
+    MissingKeys(usize),
+    /// the collada document does not contain a skeleton
+    NoSkeleton,
+    /// the collada document contains more than one skeleton, ToP animations only support one
+    MultipleSkeletons(usize),
+    /// a line of a skin weights file could not be parsed, holds the line number
+    InvalidSkinWeights(usize),
+    /// the skin weights reference the bone index, the first value, of an animation with the second value as
+    /// its bone count
+    SkinWeightsOutOfRange(usize, usize),
+    /// a line of a clips file could not be parsed, holds the line number
+    InvalidClip(usize),
+    /// the named clip ends after the last frame of the animation
+    ClipOutOfRange(String),
+    /// the two named clips share frames
+    OverlappingClips(String, String),
+    /// the export doesn't support the key kind of the animation yet
+    UnsupportedKeyKind(KeyKind),
+    /// two elements of the collada document would get this id, e.g. bones named `Bip01 Head` and `Bip01_Head`
+    DuplicateId(String),
+    /// the frame, the first value, is past the last frame of an animation with the second value as its
+    /// frame count
+    FrameOutOfRange(usize, usize),
+    /// no animations were given to merge
+    NoAnimations,
+    /// the named animation doesn't have the same bones as the first of the animations merged with it
+    MismatchedSkeleton(String),
+    /// the reference skeleton has the first value as its bone count, which doesn't match the second value, the
+    /// bone count of the animation taking its names
+    BoneCountMismatch(usize, usize),
+    /// a block of the file doesn't have the size its header gives it, described by the message
+    SizeMismatch(String),
+    /// the cache was written by another version of the cache format or with other export options
+    StaleCache,
+    /// an error raised while reading the file at this path, so batches can tell which file failed
+    InFile { path: PathBuf, source: Box<LabError> },
+}
+
+impl LabError {
+    /// attaches the path of the file being read to the error. an error that already has a path keeps it
+    pub fn in_file<P: AsRef<Path>>(self, path: P) -> LabError {
+        match self {
+            LabError::InFile { .. } => self,
+            error => LabError::InFile {
+                path: path.as_ref().to_path_buf(),
+                source: Box::new(error),
+            },
+        }
+    }
+}
+
+impl fmt::Display for LabError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LabError::Io(error) => write!(f, "{}", error),
+            LabError::NoFrames => write!(f, "The animation has no frames"),
+            LabError::NoBones => write!(f, "The animation has no bones"),
+            LabError::NoRoot => write!(f, "The animation has no root bone"),
+            LabError::DanglingParent(bone) => write!(f, "The parent of bone {} doesn't match any bone", bone),
+            LabError::TruncatedBone(bone) => write!(f, "The file ends in the middle of bone {}", bone),
+            LabError::MissingKeys(bone) => {
+                write!(f, "Bone {} doesn't have a key for every frame", bone)
+            }
+            LabError::NoSkeleton => write!(f, "No skeleton found in the collada file"),
+            LabError::MultipleSkeletons(count) => write!(
+                f,
+                "Found {} skeletons in the collada file, only one is supported",
+                count
+            ),
+            LabError::InvalidSkinWeights(line) => {
+                write!(f, "Invalid bone influence in skin weights on line {}", line)
+            }
+            LabError::SkinWeightsOutOfRange(bone, bone_count) => write!(
+                f,
+                "The skin weights reference bone {}, but the animation only has {} bones",
+                bone, bone_count
+            ),
+            LabError::InvalidClip(line) => write!(f, "Invalid clip on line {}", line),
+            LabError::ClipOutOfRange(name) => {
+                write!(f, "Clip {} ends after the last frame of the animation", name)
+            }
+            LabError::OverlappingClips(first, second) => {
+                write!(f, "Clips {} and {} overlap", first, second)
+            }
+            LabError::UnsupportedKeyKind(key_kind) => {
+                write!(f, "Animations with {:?} keys can't be exported to this format yet", key_kind)
+            }
+            LabError::DuplicateId(id) => {
+                write!(f, "More than one element of the collada file would have the id {}", id)
+            }
+            LabError::FrameOutOfRange(frame, frame_count) => write!(
+                f,
+                "Frame {} is past the last frame of the animation, {}",
+                frame,
+                frame_count.saturating_sub(1)
+            ),
+            LabError::NoAnimations => write!(f, "There are no animations to merge"),
+            LabError::MismatchedSkeleton(name) => {
+                write!(f, "Animation {} doesn't have the same bones as the first animation", name)
+            }
+            LabError::BoneCountMismatch(expected, found) => write!(
+                f,
+                "The reference skeleton has {} bones, the animation has {}",
+                expected, found
+            ),
+            LabError::SizeMismatch(message) => write!(f, "The file doesn't match its header: {}", message),
+            LabError::StaleCache => write!(f, "The cache is out of date"),
+            LabError::InFile { path, source } => write!(f, "{}: {}", path.display(), source),
+        }
+    }
+}
+
+impl std::error::Error for LabError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LabError::InFile { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for LabError {
+    fn from(error: io::Error) -> LabError {
+        LabError::Io(error)
+    }
+}
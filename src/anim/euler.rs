@@ -0,0 +1,234 @@
+use cgmath::{InnerSpace, Matrix4, Vector3};
+
+/// the order the rotations around the three axes are applied to a vertex in, e.g. `Xyz` rotates around x
+/// first and z last. this is the convention blender names its euler modes after
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EulerOrder {
+    Xyz,
+    Xzy,
+    Yxz,
+    Yzx,
+    Zxy,
+    Zyx,
+}
+
+impl EulerOrder {
+    /// parses an order given as its three axes, e.g. `xyz` or `ZYX`
+    pub fn parse(value: &str) -> Option<EulerOrder> {
+        match value.to_ascii_lowercase().as_str() {
+            "xyz" => Some(EulerOrder::Xyz),
+            "xzy" => Some(EulerOrder::Xzy),
+            "yxz" => Some(EulerOrder::Yxz),
+            "yzx" => Some(EulerOrder::Yzx),
+            "zxy" => Some(EulerOrder::Zxy),
+            "zyx" => Some(EulerOrder::Zyx),
+            _ => None,
+        }
+    }
+
+    /// the axes in the order they are applied, 0 being x
+    pub fn axes(&self) -> [usize; 3] {
+        match self {
+            EulerOrder::Xyz => [0, 1, 2],
+            EulerOrder::Xzy => [0, 2, 1],
+            EulerOrder::Yxz => [1, 0, 2],
+            EulerOrder::Yzx => [1, 2, 0],
+            EulerOrder::Zxy => [2, 0, 1],
+            EulerOrder::Zyx => [2, 1, 0],
+        }
+    }
+
+    /// whether the axes are an even permutation of xyz, which flips the signs in the decomposition
+    fn is_even(&self) -> bool {
+        matches!(self, EulerOrder::Xyz | EulerOrder::Yzx | EulerOrder::Zxy)
+    }
+}
+
+/// the angles in degrees around x, y and z of the rotation of a d3d matrix, any scale in it is ignored.
+/// at gimbal lock the first rotation is set to zero and the last one takes all of the remaining rotation
+pub fn to_euler_angles(matrix: &Matrix4<f32>, order: EulerOrder) -> [f32; 3] {
+    // the d3d rows, normalized to drop the scale. element (r, c) of the equivalent column-vector matrix
+    // is row c, column r
+    let rows: Vec<Vector3<f32>> = (0..3)
+        .map(|row| {
+            let row = Vector3::new(matrix[0][row], matrix[1][row], matrix[2][row]);
+            if row.magnitude() > 0.0 {
+                row.normalize()
+            } else {
+                row
+            }
+        })
+        .collect();
+    let m = |r: usize, c: usize| rows[c][r];
+
+    let [i, j, k] = order.axes();
+    let sign = if order.is_even() { 1.0 } else { -1.0 };
+
+    let sin_second = (-sign * m(k, i)).clamp(-1.0, 1.0);
+    let (first, second, third) = if sin_second.abs() < 0.99999 {
+        (
+            (sign * m(k, j)).atan2(m(k, k)),
+            sin_second.asin(),
+            (sign * m(j, i)).atan2(m(i, i)),
+        )
+    } else {
+        (0.0, sin_second.asin(), (-sign * m(i, j)).atan2(m(j, j)))
+    };
+
+    let mut angles = [0.0; 3];
+    angles[i] = first.to_degrees();
+    angles[j] = second.to_degrees();
+    angles[k] = third.to_degrees();
+    angles
+}
+
+/// makes consecutive frames of euler angles continuous. every frame is switched to whichever of its two
+/// equivalent angle sets is closest to the previous frame, with each angle shifted by whole turns, so the
+/// curves don't jump by 360 degrees or flip around the middle axis
+pub fn unwrap_euler_angles(frames: &mut [[f32; 3]], order: EulerOrder) {
+    let [i, j, k] = order.axes();
+
+    for frame in 1..frames.len() {
+        let previous = frames[frame - 1];
+        let angles = frames[frame];
+
+        let mut alternative = angles;
+        alternative[i] += 180.0;
+        alternative[j] = 180.0 - alternative[j];
+        alternative[k] += 180.0;
+
+        let angles = unwrap_towards(angles, previous);
+        let alternative = unwrap_towards(alternative, previous);
+        frames[frame] = if get_distance(&alternative, &previous) < get_distance(&angles, &previous) {
+            alternative
+        } else {
+            angles
+        };
+    }
+}
+
+fn unwrap_towards(mut angles: [f32; 3], previous: [f32; 3]) -> [f32; 3] {
+    for (angle, previous) in angles.iter_mut().zip(previous.iter()) {
+        *angle -= ((*angle - previous) / 360.0).round() * 360.0;
+    }
+    angles
+}
+
+fn get_distance(a: &[f32; 3], b: &[f32; 3]) -> f32 {
+    a.iter().zip(b.iter()).map(|(a, b)| (a - b).abs()).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORDERS: [EulerOrder; 6] = [
+        EulerOrder::Xyz,
+        EulerOrder::Xzy,
+        EulerOrder::Yxz,
+        EulerOrder::Yzx,
+        EulerOrder::Zxy,
+        EulerOrder::Zyx,
+    ];
+
+    /// the d3d matrix of the rotations around each axis applied in the given order
+    fn from_euler_angles(angles: [f32; 3], order: EulerOrder) -> Matrix4<f32> {
+        let mut rotation = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        for &axis in order.axes().iter() {
+            let (sin, cos) = angles[axis].to_radians().sin_cos();
+            let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+            let mut axis_rotation = [[0.0; 3]; 3];
+            axis_rotation[axis][axis] = 1.0;
+            axis_rotation[a][a] = cos;
+            axis_rotation[a][b] = -sin;
+            axis_rotation[b][a] = sin;
+            axis_rotation[b][b] = cos;
+
+            // column-vector matrices, so the later rotation multiplies from the left
+            let mut product = [[0.0; 3]; 3];
+            for r in 0..3 {
+                for c in 0..3 {
+                    product[r][c] = (0..3).map(|n| axis_rotation[r][n] * rotation[n][c]).sum();
+                }
+            }
+            rotation = product;
+        }
+
+        // the d3d matrix is the transpose, and element (r, c) of it is stored at [c][r]
+        let mut matrix = Matrix4::new(
+            0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+        );
+        for r in 0..3 {
+            for c in 0..3 {
+                matrix[r][c] = rotation[r][c];
+            }
+        }
+        matrix
+    }
+
+    fn assert_same_rotation(a: &Matrix4<f32>, b: &Matrix4<f32>) {
+        for c in 0..3 {
+            for r in 0..3 {
+                assert!((a[c][r] - b[c][r]).abs() < 1e-4, "{:?} != {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn it_decomposes_rotations_in_every_order() {
+        for &order in ORDERS.iter() {
+            let angles = [30.0, -20.0, 75.0];
+            let decomposed = to_euler_angles(&from_euler_angles(angles, order), order);
+            for axis in 0..3 {
+                assert!((decomposed[axis] - angles[axis]).abs() < 1e-3, "{:?}: {:?}", order, decomposed);
+            }
+        }
+    }
+
+    #[test]
+    fn it_decomposes_rotations_at_gimbal_lock() {
+        for &order in ORDERS.iter() {
+            let mut angles = [10.0, 20.0, 30.0];
+            angles[order.axes()[1]] = 90.0;
+            let matrix = from_euler_angles(angles, order);
+
+            let decomposed = to_euler_angles(&matrix, order);
+            assert_same_rotation(&from_euler_angles(decomposed, order), &matrix);
+        }
+    }
+
+    #[test]
+    fn it_unwraps_angles_across_frames() {
+        let order = EulerOrder::Xyz;
+        let mut frames: Vec<[f32; 3]> = (0..8)
+            .map(|frame| {
+                let angles = [0.0, 10.0, 150.0 + 20.0 * frame as f32];
+                to_euler_angles(&from_euler_angles(angles, order), order)
+            })
+            .collect();
+        assert!(frames.windows(2).any(|pair| (pair[1][2] - pair[0][2]).abs() > 180.0));
+
+        unwrap_euler_angles(&mut frames, order);
+        for pair in frames.windows(2) {
+            assert!((pair[1][2] - pair[0][2] - 20.0).abs() < 1e-3);
+        }
+        assert!((frames[7][2] - 290.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn it_keeps_the_rotation_of_unwrapped_angles() {
+        let order = EulerOrder::Zyx;
+        let originals: Vec<Matrix4<f32>> = (0..10)
+            .map(|frame| from_euler_angles([40.0 * frame as f32, 85.0 - 3.0 * frame as f32, -25.0], order))
+            .collect();
+        let mut frames: Vec<[f32; 3]> = originals.iter().map(|matrix| to_euler_angles(matrix, order)).collect();
+
+        unwrap_euler_angles(&mut frames, order);
+        for (angles, original) in frames.iter().zip(originals.iter()) {
+            assert_same_rotation(&from_euler_angles(*angles, order), original);
+        }
+        for pair in frames.windows(2) {
+            assert!(get_distance(&pair[0], &pair[1]) < 180.0);
+        }
+    }
+}
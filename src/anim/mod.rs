@@ -1,2 +1,13 @@
 pub mod bone;
-pub mod d3d;
\ No newline at end of file
+pub mod clips;
+pub mod d3d;
+pub mod diff;
+pub mod error;
+pub mod euler;
+pub mod options;
+pub mod pointcache;
+pub mod pose;
+pub mod reader;
+pub mod skin;
+pub mod usd;
+pub mod validation;
\ No newline at end of file
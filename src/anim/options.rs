@@ -0,0 +1,321 @@
+use cgmath::{Matrix4, SquareMatrix};
+use encoding_rs::Encoding;
+use serde::{Deserialize, Serialize};
+
+use super::d3d::lwMatrix44;
+use super::euler::EulerOrder;
+
+/// tolerance of the float comparisons when `ExportOptions::epsilon` isn't set
+pub const DEFAULT_EPSILON: f32 = 1e-6;
+
+/// options that control how the parsed animation data is exported
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    /// offsets the root joint so that its frame-0 translation sits at the origin
+    pub center_root: bool,
+    /// prepends the rest pose as an extra first frame of the animation
+    pub inject_bind_pose: bool,
+    /// leaves the dummy objects out of the exported hierarchy
+    pub strip_dummies: bool,
+    /// the space the matrices of the dummies are stored in
+    pub dummy_space: DummySpace,
+    /// exports only the first frames of the animation, the skeleton is kept whole
+    pub max_frames: Option<usize>,
+    /// bakes the world-space matrices of the bones into their animations and writes all the joints directly
+    /// under the skeleton, for importers that can't rebuild the hierarchy
+    pub world_space: bool,
+    /// writes every bone and its animation into a separate file instead of a single one
+    pub split_bones: bool,
+    /// writes the skeleton into a `{stem}.skel.dae` and its animation into a `{stem}.anim.dae` instead of a
+    /// single file
+    pub split_skeleton: bool,
+    /// frames to also write as static poses, each into a `{stem}_pose{frame}.dae` without animations
+    pub pose_frames: Vec<usize>,
+    /// companion file with per-vertex bone weights to export as a skin, see `SkinWeights`
+    pub skin_weights_path: Option<String>,
+    /// companion `.clips` file splitting the animation into named actions, see `AnimationClips`
+    pub clips_path: Option<String>,
+    /// .lab file whose bone names replace the names of the animation's bones with the same index, see
+    /// `AnimDataBone::take_bone_names_from`
+    pub skeleton_from_path: Option<String>,
+    /// plays the animation back at the rate that makes its last frame land at this many seconds, instead of
+    /// the game's 25 frames per second. only the times of the keys change, the poses aren't resampled
+    pub target_duration: Option<f32>,
+    /// seconds added to the time of every key and clip, to line the animation up with others on a shared
+    /// timeline. the animation starts at 0 when this is not set
+    pub time_offset: Option<f32>,
+    /// writes only the keys where the animation of a joint changes, leaving out the frames it holds still
+    pub reduce_keys: bool,
+    /// leaves the interpolation source and the sampler's `INTERPOLATION` input out of every animation, for
+    /// importers that interpolate linearly without them
+    pub no_interpolation_source: bool,
+    /// tolerance of the float comparisons, `DEFAULT_EPSILON` when this is not set. values of consecutive frames
+    /// closer than this count as unchanged when the keys are reduced, and `AnimDataBone::validate` reports
+    /// the bind matrices whose determinant is this close to zero as singular
+    pub epsilon: Option<f32>,
+    /// number of significant digits the matrix values are written with, all of them when this is not set
+    pub precision: Option<usize>,
+    /// legacy encoding of the bone names, they are read as utf-8 when this is not set
+    pub name_encoding: Option<&'static Encoding>,
+    /// writes the inverse bind matrices of the bones into a `{stem}.invbind.json` file next to the `.dae`
+    pub emit_invbind_json: bool,
+    /// mirrors the skeleton on the z axis if its bind matrices are left-handed, see `AnimDataBone::handedness`
+    pub auto_handedness: bool,
+    /// removes the mirror of every bone whose rest matrix is mirrored by flipping its x axis, and the
+    /// matrices that depend on it to match, see `AnimDataBone::validate`
+    pub fix_mirror: bool,
+    /// builds the matrices of quaternion keys from the stored quaternions as they are. they are normalized
+    /// first otherwise, as quantization leaves some of them slightly off unit length, which scales the bones
+    pub keep_unnormalized_quats: bool,
+    /// reads the position and rotation of every frame of a quaternion file side by side, as some exporters
+    /// write them, instead of all the positions of a bone followed by all its rotations. both layouts share
+    /// the same versions and sizes, so the file can't tell which one it uses
+    pub interleaved_keys: bool,
+    /// reads a footer of exactly one little-endian `[f32; 3]` per frame as a translation track of the root,
+    /// which some rigs store apart from the keys, and adds it to the root joint's frames. nothing in the
+    /// header tells these files apart from the ones with another footer, see `AnimDataBone::root_motion`
+    pub root_motion_track: bool,
+    /// transform applied on top of the root joint, and so the whole skeleton
+    pub root_transform: Option<Matrix4<f32>>,
+    /// coordinate system to convert the skeleton into, applied after the root transform
+    pub axis_map: Option<AxisMap>,
+    /// program named in the `<authoring_tool>` of the collada documents, `lab-parser` and its version when
+    /// this is not set
+    pub authoring_tool: Option<String>,
+    /// keeps the loaded animation in a `{stem}.labcache` next to the output, which later conversions load
+    /// instead of the `.lab` while it is newer than it, see `AnimDataBone::write_cache`
+    pub cache: bool,
+    /// generates the output in memory and reports the files that would be written, without writing them
+    pub dry_run: bool,
+    /// checks that the ids of the collada document are unique when the animation is loaded. they are always
+    /// checked in debug builds
+    pub validate: bool,
+    /// fails the load of a file whose inverse bind matrices or keys are fewer than its header says, instead
+    /// of filling them in with a warning, see `AnimDataBone::check_sizes`
+    pub strict_sizes: bool,
+    /// names of the bones to leave out of the skeleton, their children are reparented to the closest kept
+    /// ancestor
+    pub ignored_bones: Vec<String>,
+    /// leaves out the bones deeper in the hierarchy than this, the root being at depth 0, for a cheaper
+    /// skeleton. see `AnimDataBone::bone_depth`
+    pub max_depth: Option<usize>,
+    /// animates the joints with a translation and a rotation per axis, in this order, instead of a matrix
+    pub euler_order: Option<EulerOrder>,
+}
+
+/// the space the matrix of a dummy is given in
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum DummySpace {
+    /// relative to the bone the dummy hangs off, it is exported as it is
+    Local,
+    /// in model space, like in every sample file. it is exported relative to the bind pose of the bone the
+    /// dummy hangs off
+    #[default]
+    World,
+}
+
+impl DummySpace {
+    /// parses `local` or `world`
+    pub fn parse(value: &str) -> Option<DummySpace> {
+        match value {
+            "local" => Some(DummySpace::Local),
+            "world" => Some(DummySpace::World),
+            _ => None,
+        }
+    }
+}
+
+/// a remapping of the game's axes onto the axes of another coordinate system. `+X+Z-Y` e.g. takes the new
+/// x from the game's x, the new y from the game's z (up) and the new z from the game's -y
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AxisMap {
+    /// for every new axis, the game axis it is taken from (0 being x) and its sign
+    axes: [(usize, f32); 3],
+}
+
+impl AxisMap {
+    /// parses three signed axes such as `+X+Z-Y`. every axis has to be used exactly once, as anything else
+    /// wouldn't be an orthonormal basis
+    pub fn parse(value: &str) -> Option<AxisMap> {
+        let chars: Vec<char> = value.trim().chars().collect();
+        if chars.len() != 6 {
+            return None;
+        }
+
+        let mut axes = [(0, 0.0); 3];
+        for (axis, pair) in axes.iter_mut().zip(chars.chunks(2)) {
+            let sign = match pair[0] {
+                '+' => 1.0,
+                '-' => -1.0,
+                _ => return None,
+            };
+            let source = match pair[1].to_ascii_lowercase() {
+                'x' => 0,
+                'y' => 1,
+                'z' => 2,
+                _ => return None,
+            };
+            *axis = (source, sign);
+        }
+
+        let mut sources: Vec<usize> = axes.iter().map(|&(source, _)| source).collect();
+        sources.sort_unstable();
+        if sources != [0, 1, 2] {
+            return None;
+        }
+
+        Some(AxisMap { axes })
+    }
+
+    /// the row-vector matrix taking a point from the game's axes to the new ones. it mirrors the skeleton
+    /// if the map flips the handedness, e.g. `+X+Y-Z`
+    pub fn matrix(&self) -> Matrix4<f32> {
+        let mut rows = [[0.0; 4]; 4];
+        for (axis, &(source, sign)) in self.axes.iter().enumerate() {
+            rows[source][axis] = sign;
+        }
+        rows[3][3] = 1.0;
+
+        lwMatrix44::new(rows).matrix
+    }
+
+    /// the collada `up_axis` of the new coordinate system, the axis the game's up (+z) ends up on. `None` if
+    /// it ends up pointing down a negative axis, which collada can't describe
+    pub fn up_axis(&self) -> Option<&'static str> {
+        let axis = self.axes.iter().position(|&(source, _)| source == 2)?;
+        if self.axes[axis].1 < 0.0 {
+            return None;
+        }
+
+        Some(["X_UP", "Y_UP", "Z_UP"][axis])
+    }
+}
+
+/// parses a root transform given either as a preset rotation (`x90`, `x-90`, `y180`, ...) or as 16
+/// comma-separated floats, row by row in the d3d layout of the game (translation in the last row).
+/// returns `None` if the value is neither, or doesn't describe an invertible transform
+pub fn parse_root_transform(value: &str) -> Option<Matrix4<f32>> {
+    let rows = match parse_rotation_preset(value) {
+        Some(rows) => rows,
+        None => {
+            let values: Vec<f32> = value
+                .split(',')
+                .map(|value| value.trim().parse::<f32>())
+                .collect::<Result<_, _>>()
+                .ok()?;
+            if values.len() != 16 || values.iter().any(|value| !value.is_finite()) {
+                return None;
+            }
+
+            let mut rows = [[0.0; 4]; 4];
+            for (i, value) in values.iter().enumerate() {
+                rows[i / 4][i % 4] = *value;
+            }
+            rows
+        }
+    };
+
+    let matrix = lwMatrix44::new(rows).matrix;
+    if matrix.determinant().abs() <= f32::EPSILON {
+        return None;
+    }
+
+    Some(matrix)
+}
+
+/// rows of a rotation of a multiple of 90 degrees around one of the axes, e.g. `x-90`
+fn parse_rotation_preset(value: &str) -> Option<[[f32; 4]; 4]> {
+    let mut chars = value.chars();
+    let axis = chars.next()?;
+    let degrees = chars.as_str().parse::<i32>().ok()?;
+    if degrees % 90 != 0 {
+        return None;
+    }
+
+    let (sin, cos) = match degrees.rem_euclid(360) {
+        0 => (0.0, 1.0),
+        90 => (1.0, 0.0),
+        180 => (0.0, -1.0),
+        _ => (-1.0, 0.0),
+    };
+
+    // row-vector rotation matrices, matching the d3d convention of the game
+    match axis {
+        'x' => Some([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, cos, sin, 0.0],
+            [0.0, -sin, cos, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]),
+        'y' => Some([
+            [cos, 0.0, -sin, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [sin, 0.0, cos, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]),
+        'z' => Some([
+            [cos, sin, 0.0, 0.0],
+            [-sin, cos, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anim::d3d::get_translation;
+    use cgmath::Vector3;
+
+    #[test]
+    fn it_parses_root_transform_presets() {
+        let matrix = parse_root_transform("x-90").unwrap();
+        let mut point = Matrix4::identity();
+        point[0][3] = 0.0;
+        point[1][3] = 1.0;
+        point[2][3] = 0.0;
+
+        // with row vectors, y up rotated -90 degrees around x ends up pointing down z
+        assert_eq!(get_translation(&(point * matrix)), Vector3::new(0.0, 0.0, -1.0));
+        assert_eq!(parse_root_transform("z0"), Some(Matrix4::identity()));
+    }
+
+    #[test]
+    fn it_parses_root_transform_matrices() {
+        let matrix = parse_root_transform("1,0,0,0, 0,1,0,0, 0,0,1,0, 1,2,3,1").unwrap();
+        assert_eq!(get_translation(&matrix), Vector3::new(1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn it_remaps_the_axes() {
+        let axis_map = AxisMap::parse("+X+Z-Y").unwrap();
+        let mut point = Matrix4::identity();
+        point[0][3] = 1.0;
+        point[1][3] = 2.0;
+        point[2][3] = 3.0;
+
+        assert_eq!(get_translation(&(point * axis_map.matrix())), Vector3::new(1.0, 3.0, -2.0));
+        assert_eq!(axis_map.up_axis(), Some("Y_UP"));
+        assert_eq!(AxisMap::parse("+x+y+z").unwrap().matrix(), Matrix4::identity());
+        assert_eq!(AxisMap::parse("+Y-Z+X").unwrap().up_axis(), None);
+    }
+
+    #[test]
+    fn it_rejects_axis_maps_that_are_not_a_basis() {
+        assert_eq!(AxisMap::parse("+X+X-Y"), None);
+        assert_eq!(AxisMap::parse("+X+Z"), None);
+        assert_eq!(AxisMap::parse("XZY"), None);
+        assert_eq!(AxisMap::parse("+X+Z-W"), None);
+    }
+
+    #[test]
+    fn it_rejects_invalid_root_transforms() {
+        assert_eq!(parse_root_transform("x45"), None);
+        assert_eq!(parse_root_transform("w90"), None);
+        assert_eq!(parse_root_transform("1,0,0,0"), None);
+        assert_eq!(parse_root_transform("0,0,0,0, 0,0,0,0, 0,0,0,0, 0,0,0,0"), None);
+        assert_eq!(parse_root_transform("1,0,0,0, 0,1,0,0, 0,0,1,0, 0,0,0,nan"), None);
+    }
+}
@@ -0,0 +1,73 @@
+use std::io::{self, Write};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use super::bone::AnimDataBone;
+use super::d3d::get_translation;
+
+/// bytes at the start of every point cache file
+pub const POINT_CACHE_MAGIC: &[u8; 4] = b"LPC1";
+
+/// writes the world-space position of every joint at every exported frame as a point cache, for tools that
+/// only need where the joints are rather than a rigged skeleton. the layout is little-endian:
+///
+/// - the magic bytes `LPC1`
+/// - the frame count and the point count as u32s
+/// - for every frame, the x, y and z of every joint as f32s, the joints in the order of the bones in the file
+pub fn write_point_cache<W: Write>(anim_data: &AnimDataBone, writer: &mut W) -> io::Result<()> {
+    let frame_count = anim_data.get_exported_animation_frame_count();
+    let point_count = anim_data.get_num_bones();
+
+    writer.write_all(POINT_CACHE_MAGIC)?;
+    writer.write_u32::<LittleEndian>(frame_count as u32)?;
+    writer.write_u32::<LittleEndian>(point_count as u32)?;
+
+    for frame in 0..frame_count {
+        for world_matrix in anim_data.get_world_transforms_for_frame(frame).iter() {
+            let position = get_translation(world_matrix);
+            writer.write_f32::<LittleEndian>(position.x)?;
+            writer.write_f32::<LittleEndian>(position.y)?;
+            writer.write_f32::<LittleEndian>(position.z)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anim::options::ExportOptions;
+    use byteorder::ReadBytesExt;
+    use std::fs::File;
+    use std::io::{Cursor, Read};
+
+    #[test]
+    fn it_writes_the_world_position_of_every_joint_per_frame() {
+        let mut anim_data = AnimDataBone::with_options(ExportOptions {
+            max_frames: Some(10),
+            ..Default::default()
+        });
+        let mut file = File::open("./src/tests/anim-quat.lab").unwrap();
+        anim_data.load(&mut file).unwrap();
+
+        let mut point_cache = Vec::new();
+        write_point_cache(&anim_data, &mut point_cache).unwrap();
+        assert_eq!(point_cache.len(), 12 + 10 * 35 * 12);
+
+        let mut reader = Cursor::new(point_cache);
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic).unwrap();
+        assert_eq!(&magic, POINT_CACHE_MAGIC);
+        assert_eq!(reader.read_u32::<LittleEndian>().unwrap(), 10);
+        assert_eq!(reader.read_u32::<LittleEndian>().unwrap(), 35);
+
+        // the spine at the second frame
+        let spine = anim_data.find_bone("Bip01 Spine").unwrap();
+        let expected = get_translation(&anim_data.get_world_transforms_for_frame(1)[spine]);
+        reader.set_position(12 + (35 + spine as u64) * 12);
+        assert_eq!(reader.read_f32::<LittleEndian>().unwrap(), expected.x);
+        assert_eq!(reader.read_f32::<LittleEndian>().unwrap(), expected.y);
+        assert_eq!(reader.read_f32::<LittleEndian>().unwrap(), expected.z);
+    }
+}
@@ -0,0 +1,73 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use super::bone::AnimDataBone;
+use super::d3d::decompose;
+use super::error::LabError;
+
+/// the local transform of a bone at one frame: its translation, its rotation as an `[x, y, z, w]` quaternion
+/// and its scale
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BonePose {
+    pub translation: [f32; 3],
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+}
+
+/// the local transform of every bone at the given frame, keyed by the name of the bone
+pub fn get_pose(anim_data: &AnimDataBone, frame: usize) -> Result<BTreeMap<String, BonePose>, LabError> {
+    if frame >= anim_data.get_num_frames() {
+        return Err(LabError::FrameOutOfRange(frame, anim_data.get_num_frames()));
+    }
+
+    let frames = anim_data.transforms();
+    let pose = (0..anim_data.get_num_bones())
+        .map(|bone| {
+            let (translation, rotation, scale) = decompose(&frames[bone][frame]);
+            let bone_pose = BonePose {
+                translation: translation.into(),
+                rotation: [rotation.v.x, rotation.v.y, rotation.v.z, rotation.s],
+                scale: scale.into(),
+            };
+            (anim_data.get_bone_name(bone), bone_pose)
+        })
+        .collect();
+
+    Ok(pose)
+}
+
+/// the pose at the given frame as a json object mapping the name of every bone to its local transform
+pub fn write_pose_json(anim_data: &AnimDataBone, frame: usize) -> Result<String, LabError> {
+    let pose = get_pose(anim_data, frame)?;
+    Ok(serde_json::to_string_pretty(&pose).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{InnerSpace, Quaternion, Vector3};
+    use std::fs::File;
+
+    #[test]
+    fn it_decomposes_the_local_transform_of_every_bone() {
+        let mut anim_data = AnimDataBone::new();
+        anim_data.load(&mut File::open("./src/tests/anim-quat.lab").unwrap()).unwrap();
+
+        let pose = get_pose(&anim_data, 50).unwrap();
+        assert_eq!(pose.len(), anim_data.get_num_bones());
+
+        let bone = anim_data.find_bone("Bip01 L Forearm").unwrap();
+        let bone_pose = &pose["Bip01 L Forearm"];
+        let position = anim_data.position_track(bone).unwrap()[50];
+        let rotation = anim_data.quaternion_track(bone).unwrap()[50].normalize();
+        let [x, y, z, w] = bone_pose.rotation;
+        assert!((Vector3::from(bone_pose.translation) - position).magnitude() < 1e-4);
+        assert!(Quaternion::new(w, x, y, z).dot(rotation).abs() > 0.9999);
+        assert!((Vector3::from(bone_pose.scale) - Vector3::new(1.0, 1.0, 1.0)).magnitude() < 1e-4);
+
+        let json: serde_json::Value = serde_json::from_str(&write_pose_json(&anim_data, 50).unwrap()).unwrap();
+        assert_eq!(json["Bip01 L Forearm"]["translation"].as_array().unwrap().len(), 3);
+        assert!(matches!(get_pose(&anim_data, 228), Err(LabError::FrameOutOfRange(228, 228))));
+    }
+}
@@ -0,0 +1,89 @@
+use flate2::read::GzDecoder;
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
+use std::fs::File;
+use std::io::{self, Cursor, Read};
+use std::path::Path;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// the bytes of a .lab file, either read into memory or mapped from disk
+pub enum LabBytes {
+    Owned(Vec<u8>),
+    #[cfg(feature = "mmap")]
+    Mapped(Mmap),
+}
+
+impl AsRef<[u8]> for LabBytes {
+    fn as_ref(&self) -> &[u8] {
+        match self {
+            LabBytes::Owned(bytes) => bytes,
+            #[cfg(feature = "mmap")]
+            LabBytes::Mapped(mmap) => mmap,
+        }
+    }
+}
+
+/// reads the whole .lab file into memory so the loaders can seek around it.
+/// gzip compressed files (.lab.gz) are detected by their magic bytes and decompressed first
+#[cfg(not(feature = "mmap"))]
+pub fn open_lab_file(path: &Path) -> io::Result<Cursor<LabBytes>> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+
+    Ok(Cursor::new(LabBytes::Owned(decompress_if_gzip(bytes)?)))
+}
+
+/// maps the .lab file into memory so the loaders read straight from the page cache, which avoids
+/// a copy of every file in large batch jobs. gzip compressed files are still decompressed into memory
+#[cfg(feature = "mmap")]
+pub fn open_lab_file(path: &Path) -> io::Result<Cursor<LabBytes>> {
+    let file = File::open(path)?;
+    // the mapping is only ever read from, the file is not expected to change while it is being converted
+    let mmap = unsafe { Mmap::map(&file)? };
+
+    if mmap.starts_with(&GZIP_MAGIC) {
+        return Ok(Cursor::new(LabBytes::Owned(decompress_if_gzip(mmap.to_vec())?)));
+    }
+
+    Ok(Cursor::new(LabBytes::Mapped(mmap)))
+}
+
+/// returns the bytes as they are if they are not gzip compressed
+pub fn decompress_if_gzip(bytes: Vec<u8>) -> io::Result<Vec<u8>> {
+    if !bytes.starts_with(&GZIP_MAGIC) {
+        return Ok(bytes);
+    }
+
+    let mut decompressed = Vec::new();
+    GzDecoder::new(&bytes[..]).read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    #[test]
+    fn it_decompresses_gzip_input_transparently() {
+        let raw = std::fs::read("./src/tests/anim-quat.lab").unwrap();
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&raw).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        assert_eq!(decompress_if_gzip(compressed).unwrap(), raw);
+        assert_eq!(decompress_if_gzip(raw.clone()).unwrap(), raw);
+    }
+
+    #[test]
+    fn it_serves_the_whole_file_to_the_loaders() {
+        let raw = std::fs::read("./src/tests/anim-quat.lab").unwrap();
+        let file = open_lab_file(Path::new("./src/tests/anim-quat.lab")).unwrap();
+
+        assert_eq!(file.get_ref().as_ref(), &raw[..]);
+    }
+}
@@ -0,0 +1,95 @@
+use std::fs;
+use std::path::Path;
+
+use super::error::LabError;
+
+/// per-vertex joint influences read from a companion weights file.
+///
+/// the file has one line per vertex of the mesh the animation drives, each line listing the vertex's
+/// influences as `bone_index:weight` pairs separated by whitespace, e.g. `0:0.75 2:0.25`.
+/// bone indices are in the order the bones appear in the .lab file. empty lines and lines starting
+/// with `#` are ignored
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SkinWeights {
+    pub vertices: Vec<Vec<(usize, f32)>>,
+}
+
+impl SkinWeights {
+    pub fn load_from_file(path: &Path) -> Result<SkinWeights, LabError> {
+        let content = fs::read_to_string(path).map_err(|why| LabError::from(why).in_file(path))?;
+        SkinWeights::parse(&content).map_err(|why| why.in_file(path))
+    }
+
+    pub fn parse(content: &str) -> Result<SkinWeights, LabError> {
+        let mut vertices = Vec::new();
+
+        for (line_index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut influences = Vec::new();
+            for pair in line.split_whitespace() {
+                let mut parts = pair.splitn(2, ':');
+                let bone_index = parts.next().and_then(|value| value.parse::<usize>().ok());
+                let weight = parts.next().and_then(|value| value.parse::<f32>().ok());
+
+                match (bone_index, weight) {
+                    (Some(bone_index), Some(weight)) => influences.push((bone_index, weight)),
+                    _ => return Err(LabError::InvalidSkinWeights(line_index + 1)),
+                }
+            }
+
+            vertices.push(influences);
+        }
+
+        Ok(SkinWeights { vertices })
+    }
+
+    /// the highest bone index any vertex is influenced by
+    pub fn max_bone_index(&self) -> Option<usize> {
+        self.vertices
+            .iter()
+            .flatten()
+            .map(|(bone_index, _)| *bone_index)
+            .max()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_vertex_influences() {
+        let weights = SkinWeights::parse("# vertex weights\n0:1\n\n0:0.75 2:0.25\n").unwrap();
+
+        assert_eq!(weights.vertices, vec![vec![(0, 1.0)], vec![(0, 0.75), (2, 0.25)]]);
+        assert_eq!(weights.max_bone_index(), Some(2));
+    }
+
+    #[test]
+    fn it_reports_the_line_of_a_malformed_influence() {
+        match SkinWeights::parse("0:1\n1-0.5\n") {
+            Err(LabError::InvalidSkinWeights(line)) => assert_eq!(line, 2),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn it_reports_the_file_of_a_malformed_influence() {
+        let path = std::env::temp_dir().join("lab-parser-malformed.weights");
+        fs::write(&path, "0:1\n1-0.5\n").unwrap();
+
+        let error = SkinWeights::load_from_file(&path).unwrap_err();
+        assert_eq!(error.to_string(), format!("{}: Invalid bone influence in skin weights on line 2", path.display()));
+        match error {
+            LabError::InFile { path: error_path, source } => {
+                assert_eq!(error_path, path);
+                assert!(matches!(*source, LabError::InvalidSkinWeights(2)));
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+}
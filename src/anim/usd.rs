@@ -0,0 +1,218 @@
+use std::fmt::Write;
+
+use cgmath::{Matrix4, Quaternion, SquareMatrix, Vector3};
+
+use super::bone::{AnimDataBone, KeyKind, FRAMES_PER_SECOND};
+use super::d3d::decompose;
+use super::error::LabError;
+
+/// the translation, rotation and scale of a joint at one frame
+type JointSample = (Vector3<f32>, Quaternion<f32>, Vector3<f32>);
+
+/// writes a loaded animation as a usda (ascii usd) layer: a `SkelRoot` holding the `Skeleton` and its
+/// `SkelAnimation`. usd uses the same row-vector convention as d3d, so matrices are written row by row with
+/// the translation in the last row. only quaternion animations are supported for now
+pub fn write_usda(anim_data: &AnimDataBone) -> Result<String, LabError> {
+    if anim_data.key_kind() != KeyKind::Quaternion {
+        return Err(LabError::UnsupportedKeyKind(anim_data.key_kind()));
+    }
+
+    let joint_order = get_joint_order(anim_data);
+    let joints: Vec<String> = joint_order
+        .iter()
+        .map(|&bone| format!("\"{}\"", get_joint_path(anim_data, bone)))
+        .collect();
+    let joints = format!("[{}]", joints.join(", "));
+
+    let inverse_bind_matrices = anim_data.get_inverse_bind_matrices();
+    let bind_transforms: Vec<Matrix4<f32>> = joint_order
+        .iter()
+        .map(|&bone| inverse_bind_matrices[bone].invert().unwrap_or_else(Matrix4::identity))
+        .collect();
+    let rest_pose = anim_data.bind_pose();
+    let rest_transforms: Vec<Matrix4<f32>> = joint_order.iter().map(|&bone| rest_pose[bone].1).collect();
+
+    let frame_count = anim_data.get_exported_animation_frame_count();
+    let frames = anim_data.transforms();
+    let has_scales = (0..anim_data.get_num_bones()).any(|bone| anim_data.bone_scales(bone).is_some());
+
+    let mut usda = String::new();
+    writeln!(usda, "#usda 1.0").unwrap();
+    writeln!(usda, "(").unwrap();
+    writeln!(usda, "    defaultPrim = \"Root\"").unwrap();
+    writeln!(usda, "    startTimeCode = 0").unwrap();
+    writeln!(usda, "    endTimeCode = {}", frame_count.saturating_sub(1)).unwrap();
+    writeln!(usda, "    timeCodesPerSecond = {}", FRAMES_PER_SECOND).unwrap();
+    writeln!(usda, "    upAxis = \"Z\"").unwrap();
+    writeln!(usda, ")").unwrap();
+    writeln!(usda).unwrap();
+    writeln!(usda, "def SkelRoot \"Root\"").unwrap();
+    writeln!(usda, "{{").unwrap();
+    writeln!(usda, "    def Skeleton \"Skeleton\" (").unwrap();
+    writeln!(usda, "        prepend apiSchemas = [\"SkelBindingAPI\"]").unwrap();
+    writeln!(usda, "    )").unwrap();
+    writeln!(usda, "    {{").unwrap();
+    writeln!(usda, "        uniform token[] joints = {}", joints).unwrap();
+    writeln!(usda, "        uniform matrix4d[] bindTransforms = {}", format_matrices(&bind_transforms)).unwrap();
+    writeln!(usda, "        uniform matrix4d[] restTransforms = {}", format_matrices(&rest_transforms)).unwrap();
+    writeln!(usda, "        rel skel:animationSource = </Root/Skeleton/Animation>").unwrap();
+    writeln!(usda).unwrap();
+    writeln!(usda, "        def SkelAnimation \"Animation\"").unwrap();
+    writeln!(usda, "        {{").unwrap();
+    writeln!(usda, "            uniform token[] joints = {}", joints).unwrap();
+
+    let samples: Vec<Vec<JointSample>> = (0..frame_count)
+        .map(|frame| {
+            joint_order
+                .iter()
+                .map(|&bone| decompose(&frames[bone][frame]))
+                .collect()
+        })
+        .collect();
+
+    writeln!(usda, "            float3[] translations.timeSamples = {{").unwrap();
+    for (frame, sample) in samples.iter().enumerate() {
+        let values: Vec<String> = sample.iter().map(|(translation, _, _)| format_vector(translation)).collect();
+        writeln!(usda, "                {}: [{}],", frame, values.join(", ")).unwrap();
+    }
+    writeln!(usda, "            }}").unwrap();
+
+    writeln!(usda, "            quatf[] rotations.timeSamples = {{").unwrap();
+    for (frame, sample) in samples.iter().enumerate() {
+        let values: Vec<String> = sample.iter().map(|(_, rotation, _)| format_quaternion(rotation)).collect();
+        writeln!(usda, "                {}: [{}],", frame, values.join(", ")).unwrap();
+    }
+    writeln!(usda, "            }}").unwrap();
+
+    if has_scales {
+        writeln!(usda, "            half3[] scales.timeSamples = {{").unwrap();
+        for (frame, sample) in samples.iter().enumerate() {
+            let values: Vec<String> = sample.iter().map(|(_, _, scale)| format_vector(scale)).collect();
+            writeln!(usda, "                {}: [{}],", frame, values.join(", ")).unwrap();
+        }
+        writeln!(usda, "            }}").unwrap();
+    } else {
+        let values = vec!["(1, 1, 1)"; joint_order.len()];
+        writeln!(usda, "            half3[] scales = [{}]", values.join(", ")).unwrap();
+    }
+
+    writeln!(usda, "        }}").unwrap();
+    writeln!(usda, "    }}").unwrap();
+    writeln!(usda, "}}").unwrap();
+
+    Ok(usda)
+}
+
+/// the bones ordered so that every parent comes before its children, as usd requires of the joints.
+/// bones keep their file order within the same depth
+fn get_joint_order(anim_data: &AnimDataBone) -> Vec<usize> {
+    let mut joint_order: Vec<usize> = (0..anim_data.get_num_bones()).collect();
+    joint_order.sort_by_key(|&bone| anim_data.bone_depth(bone));
+    joint_order
+}
+
+/// the path of a joint from the root, e.g. `Bip01/Bip01_Pelvis`, with every name made a valid usd identifier
+fn get_joint_path(anim_data: &AnimDataBone, bone_index: usize) -> String {
+    let names: Vec<String> = anim_data
+        .ancestors(bone_index)
+        .into_iter()
+        .rev()
+        .map(|bone| get_identifier(&anim_data.get_bone_name(bone)))
+        .collect();
+    names.join("/")
+}
+
+fn get_identifier(name: &str) -> String {
+    let identifier: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+
+    match identifier.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => identifier,
+        _ => format!("_{}", identifier),
+    }
+}
+
+fn format_vector(vector: &Vector3<f32>) -> String {
+    format!("({}, {}, {})", vector.x, vector.y, vector.z)
+}
+
+/// usd writes quaternions with the real part first
+fn format_quaternion(quaternion: &Quaternion<f32>) -> String {
+    format!("({}, {}, {}, {})", quaternion.s, quaternion.v.x, quaternion.v.y, quaternion.v.z)
+}
+
+fn format_matrices(matrices: &[Matrix4<f32>]) -> String {
+    let matrices: Vec<String> = matrices
+        .iter()
+        .map(|matrix| {
+            let rows: Vec<String> = (0..4)
+                .map(|row| format!("({}, {}, {}, {})", matrix[0][row], matrix[1][row], matrix[2][row], matrix[3][row]))
+                .collect();
+            format!("( {} )", rows.join(", "))
+        })
+        .collect();
+    format!("[{}]", matrices.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anim::bone::BoneKeys;
+    use cgmath::InnerSpace;
+    use std::fs::File;
+
+    fn load(path: &str) -> AnimDataBone {
+        let mut anim_data = AnimDataBone::new();
+        let mut file = File::open(path).unwrap();
+        anim_data.load(&mut file).unwrap();
+        anim_data
+    }
+
+    #[test]
+    fn it_writes_a_skeleton_and_its_animation() {
+        let anim_data = load("./src/tests/anim-quat.lab");
+        let usda = write_usda(&anim_data).unwrap();
+
+        assert!(usda.starts_with("#usda 1.0\n"));
+        assert!(usda.contains("endTimeCode = 227\n"));
+        assert!(usda.contains("def Skeleton \"Skeleton\""));
+        assert!(usda.contains("def SkelAnimation \"Animation\""));
+        assert!(usda.contains("uniform token[] joints = [\"Bip01\", "));
+        assert!(usda.contains("\"Bip01/Bip01_Pelvis/Bip01_Spine\""));
+        assert!(usda.contains("                227: ["));
+        assert!(!usda.contains("                228: ["));
+    }
+
+    #[test]
+    fn it_orders_parents_before_their_children() {
+        let anim_data = load("./src/tests/anim-sparse-ids.lab");
+        let joint_order = get_joint_order(&anim_data);
+
+        for (position, &bone) in joint_order.iter().enumerate() {
+            if let Some(&parent) = anim_data.ancestors(bone).get(1) {
+                assert!(joint_order[..position].contains(&parent));
+            }
+        }
+    }
+
+    #[test]
+    fn it_recovers_the_rotations_of_the_keys() {
+        let anim_data = load("./src/tests/anim-quat.lab");
+        let frames = anim_data.transforms();
+
+        let (positions, rotations) = match anim_data.bone_keys(1) {
+            BoneKeys::Quaternion { positions, rotations } => (positions, rotations),
+            _ => panic!("expected quaternion keys"),
+        };
+        for frame in [0, 100, 227].iter() {
+            let (translation, rotation, scale) = decompose(&frames[1][*frame]);
+            let expected = rotations[*frame].normalize();
+
+            assert!((translation - positions[*frame]).magnitude() < 1e-4);
+            assert!(rotation.dot(expected).abs() > 0.9999);
+            assert!((scale - Vector3::new(1.0, 1.0, 1.0)).magnitude() < 1e-4);
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use cgmath::{Matrix4, SquareMatrix};
+
+use super::bone::AnimDataBone;
+
+/// a problem found in a loaded animation, with the bone or frame it was found at
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationIssue {
+    /// the bone's parent id doesn't match any bone
+    DanglingParent { bone: usize, parent_id: u32 },
+    /// following the parents from the bone leads back to a bone already visited
+    Cycle { bone: usize },
+    /// the bone has the same name as an earlier bone
+    DuplicateName { bone: usize, name: String },
+    /// the bone has the same id as an earlier bone
+    DuplicateId { bone: usize, id: u32 },
+    /// the bind matrix of the bone, or its matrix at a frame, holds a NaN
+    NanMatrix { bone: usize, frame: Option<usize> },
+    /// the animation has bones but no frames
+    NoFrames,
+    /// the inverse bind matrix of the bone can't be inverted back into its bind matrix: its determinant is no
+    /// further from zero than the epsilon of the export options
+    SingularBindMatrix { bone: usize },
+    /// the rest matrix of the bone is mirrored, its determinant is negative. `ExportOptions::fix_mirror`
+    /// removes the mirror
+    MirroredBone { bone: usize },
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationIssue::DanglingParent { bone, parent_id } => {
+                write!(f, "bone {} has the parent id {}, which doesn't match any bone", bone, parent_id)
+            }
+            ValidationIssue::Cycle { bone } => write!(f, "the parents of bone {} loop back on themselves", bone),
+            ValidationIssue::DuplicateName { bone, name } => {
+                write!(f, "bone {} has the name {} of an earlier bone", bone, name)
+            }
+            ValidationIssue::DuplicateId { bone, id } => write!(f, "bone {} has the id {} of an earlier bone", bone, id),
+            ValidationIssue::NanMatrix { bone, frame: Some(frame) } => {
+                write!(f, "the matrix of bone {} at frame {} is not a number", bone, frame)
+            }
+            ValidationIssue::NanMatrix { bone, frame: None } => {
+                write!(f, "the bind matrix of bone {} is not a number", bone)
+            }
+            ValidationIssue::NoFrames => write!(f, "the animation has no frames"),
+            ValidationIssue::SingularBindMatrix { bone } => {
+                write!(f, "the inverse bind matrix of bone {} can't be inverted", bone)
+            }
+            ValidationIssue::MirroredBone { bone } => write!(f, "the rest matrix of bone {} is mirrored", bone),
+        }
+    }
+}
+
+/// every issue found by `AnimDataBone::validate`, in the order of the checks
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationReport {
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// whether no issue was found
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for issue in self.issues.iter() {
+            writeln!(f, "{}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+/// runs every check on a loaded animation. see `AnimDataBone::validate`
+pub fn validate(anim_data: &AnimDataBone) -> ValidationReport {
+    let mut issues = Vec::new();
+    let bone_count = anim_data.get_num_bones();
+
+    // the first bone with each id and name, later ones are reported as duplicates
+    let mut index_by_id = HashMap::new();
+    let mut index_by_name = HashMap::new();
+    for bone in 0..bone_count {
+        let id = anim_data.get_bone_id(bone);
+        if *index_by_id.entry(id).or_insert(bone) != bone {
+            issues.push(ValidationIssue::DuplicateId { bone, id });
+        }
+
+        let name = anim_data.get_bone_name(bone);
+        if *index_by_name.entry(name.clone()).or_insert(bone) != bone {
+            issues.push(ValidationIssue::DuplicateName { bone, name });
+        }
+    }
+
+    for bone in 0..bone_count {
+        let parent_id = anim_data.get_bone_parent_id(bone);
+        if parent_id == u32::MAX {
+            continue;
+        }
+        if !index_by_id.contains_key(&parent_id) {
+            issues.push(ValidationIssue::DanglingParent { bone, parent_id });
+        } else if is_in_cycle(&index_by_id, anim_data, bone) {
+            issues.push(ValidationIssue::Cycle { bone });
+        }
+    }
+
+    if bone_count > 0 && anim_data.get_num_frames() == 0 {
+        issues.push(ValidationIssue::NoFrames);
+    }
+
+    for (bone, inverse_bind_matrix) in anim_data.get_inverse_bind_matrices().iter().enumerate() {
+        if has_nan(inverse_bind_matrix) {
+            issues.push(ValidationIssue::NanMatrix { bone, frame: None });
+        } else if inverse_bind_matrix.determinant().abs() <= anim_data.get_epsilon() {
+            issues.push(ValidationIssue::SingularBindMatrix { bone });
+        }
+    }
+
+    for (bone, (_, rest_matrix)) in anim_data.bind_pose().iter().enumerate() {
+        if rest_matrix.determinant() < 0.0 {
+            issues.push(ValidationIssue::MirroredBone { bone });
+        }
+    }
+
+    for (bone, matrices) in anim_data.transforms().iter().enumerate() {
+        if let Some(frame) = matrices.iter().position(has_nan) {
+            issues.push(ValidationIssue::NanMatrix { bone, frame: Some(frame) });
+        }
+    }
+
+    ValidationReport { issues }
+}
+
+/// whether following the parents up from the bone comes back to it
+fn is_in_cycle(index_by_id: &HashMap<u32, usize>, anim_data: &AnimDataBone, bone: usize) -> bool {
+    let mut visited = vec![bone];
+    let mut current = bone;
+    while let Some(&parent) = index_by_id.get(&anim_data.get_bone_parent_id(current)) {
+        if parent == bone {
+            return true;
+        }
+        if visited.contains(&parent) {
+            return false;
+        }
+        visited.push(parent);
+        current = parent;
+    }
+    false
+}
+
+fn has_nan(matrix: &Matrix4<f32>) -> bool {
+    (0..4).any(|column| (0..4).any(|row| matrix[column][row].is_nan()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anim::bone::KeyKind;
+    use crate::anim::error::LabError;
+    use crate::anim::options::ExportOptions;
+    use cgmath::{Quaternion, Vector3};
+    use std::fs::File;
+
+    /// a three bone chain with a frame of keys, built with the given parent ids
+    fn build(parent_ids: [u32; 3], names: [&str; 3]) -> AnimDataBone {
+        let mut anim_data = AnimDataBone::new();
+        anim_data.set_key_kind(KeyKind::Quaternion);
+        anim_data.set_frame_count(1);
+        for (i, (&parent_id, name)) in parent_ids.iter().zip(names.iter()).enumerate() {
+            let bone = anim_data.push_bone(name, i as u32, parent_id, Matrix4::identity());
+            anim_data.push_quaternion_key(bone, Vector3::new(0.0, 1.0, 0.0), Quaternion::new(1.0, 0.0, 0.0, 0.0));
+        }
+        anim_data
+    }
+
+    #[test]
+    fn it_finds_nothing_wrong_with_a_sample_file() {
+        let mut anim_data = AnimDataBone::new();
+        anim_data.load(&mut File::open("./src/tests/anim-quat.lab").unwrap()).unwrap();
+
+        let report = anim_data.validate();
+        assert!(report.is_valid(), "{}", report);
+    }
+
+    #[test]
+    fn it_reports_broken_hierarchies() {
+        let mut anim_data = build([u32::MAX, 7, 2], ["Root", "Arm", "Hand"]);
+        assert!(matches!(anim_data.finish(), Err(LabError::DanglingParent(1))));
+
+        let issues = anim_data.validate().issues;
+        assert!(issues.contains(&ValidationIssue::DanglingParent { bone: 1, parent_id: 7 }));
+        assert!(issues.contains(&ValidationIssue::Cycle { bone: 2 }));
+        assert_eq!(issues.len(), 2);
+    }
+
+    #[test]
+    fn it_reports_duplicate_names_and_ids() {
+        let mut anim_data = build([u32::MAX, 0, 0], ["Root", "Arm", "Arm"]);
+        anim_data.push_bone("Hand", 1, 1, Matrix4::identity());
+
+        let issues = validate(&anim_data).issues;
+        assert!(issues.contains(&ValidationIssue::DuplicateId { bone: 3, id: 1 }));
+        assert!(issues.contains(&ValidationIssue::DuplicateName { bone: 2, name: "Arm".to_string() }));
+    }
+
+    #[test]
+    fn it_reports_bad_matrices() {
+        let mut anim_data = AnimDataBone::new();
+        anim_data.set_key_kind(KeyKind::Quaternion);
+        anim_data.set_frame_count(1);
+        let root = anim_data.push_bone("Root", 0, u32::MAX, Matrix4::identity());
+        let flat = anim_data.push_bone("Flat", 1, 0, Matrix4::from_nonuniform_scale(1.0, 0.0, 1.0));
+        let broken = anim_data.push_bone("Broken", 2, 0, Matrix4::from_scale(f32::NAN));
+        anim_data.push_quaternion_key(root, Vector3::new(f32::NAN, 0.0, 0.0), Quaternion::new(1.0, 0.0, 0.0, 0.0));
+        anim_data.push_quaternion_key(flat, Vector3::new(0.0, 0.0, 0.0), Quaternion::new(1.0, 0.0, 0.0, 0.0));
+        anim_data.push_quaternion_key(broken, Vector3::new(0.0, 0.0, 0.0), Quaternion::new(1.0, 0.0, 0.0, 0.0));
+        anim_data.finish().unwrap();
+
+        let issues = anim_data.validate().issues;
+        assert!(issues.contains(&ValidationIssue::SingularBindMatrix { bone: flat }));
+        assert!(issues.contains(&ValidationIssue::NanMatrix { bone: broken, frame: None }));
+        assert!(issues.contains(&ValidationIssue::NanMatrix { bone: root, frame: Some(0) }));
+    }
+
+    #[test]
+    fn it_uses_the_epsilon_of_the_options_for_singular_matrices() {
+        let build_scaled = |epsilon| {
+            let mut anim_data = AnimDataBone::with_options(ExportOptions { epsilon, ..Default::default() });
+            anim_data.set_key_kind(KeyKind::Quaternion);
+            anim_data.set_frame_count(1);
+            anim_data.push_bone("Root", 0, u32::MAX, Matrix4::from_scale(0.05));
+            anim_data
+        };
+
+        assert!(validate(&build_scaled(None)).is_valid());
+        assert_eq!(
+            validate(&build_scaled(Some(1e-3))).issues,
+            vec![ValidationIssue::SingularBindMatrix { bone: 0 }]
+        );
+    }
+
+    #[test]
+    fn it_reports_an_animation_without_frames() {
+        let mut anim_data = build([u32::MAX, 0, 1], ["Root", "Arm", "Hand"]);
+        anim_data.set_frame_count(0);
+
+        assert_eq!(validate(&anim_data).issues, vec![ValidationIssue::NoFrames]);
+    }
+}
@@ -0,0 +1,165 @@
+//! a minimal C ABI over the lab2dae conversion, for calling the converter from other languages
+//! (e.g. python through ctypes). panics are caught at the boundary and turned into error codes
+
+use std::ffi::CStr;
+use std::fs;
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::anim::bone::AnimDataBone;
+use crate::anim::reader::open_lab_file;
+use crate::MIN_VERSION;
+
+pub const LAB_OK: c_int = 0;
+/// a path is null or not valid utf-8
+pub const LAB_ERROR_INVALID_ARGUMENT: c_int = -1;
+/// the input couldn't be read or the output couldn't be written
+pub const LAB_ERROR_IO: c_int = -2;
+/// the file's version is older than `MIN_VERSION`
+pub const LAB_ERROR_UNSUPPORTED_VERSION: c_int = -3;
+/// the file couldn't be parsed
+pub const LAB_ERROR_INVALID_FILE: c_int = -4;
+/// the output buffer is too small for the document, `required_len` holds the size it needs
+pub const LAB_ERROR_BUFFER_TOO_SMALL: c_int = -5;
+/// the conversion panicked
+pub const LAB_ERROR_PANIC: c_int = -6;
+
+/// converts the .lab file at `input_path` and writes the collada document to `output_path`.
+/// returns `LAB_OK` or one of the `LAB_ERROR_*` codes
+///
+/// # Safety
+///
+/// both paths must be null or point to nul terminated strings
+#[no_mangle]
+pub unsafe extern "C" fn lab_to_dae(input_path: *const c_char, output_path: *const c_char) -> c_int {
+    catch_panic(|| {
+        let output_path = match path_from_c(output_path) {
+            Some(path) => path,
+            None => return LAB_ERROR_INVALID_ARGUMENT,
+        };
+
+        match convert(input_path) {
+            Ok(xml_content) => match fs::write(output_path, xml_content) {
+                Ok(()) => LAB_OK,
+                Err(_) => LAB_ERROR_IO,
+            },
+            Err(code) => code,
+        }
+    })
+}
+
+/// converts the .lab file at `input_path` and copies the nul terminated collada document into `buffer`.
+/// the length of the document, without the nul, is always stored in `required_len` when it is not null,
+/// so the function can be called with a null buffer first to size it.
+/// returns `LAB_OK` or one of the `LAB_ERROR_*` codes
+///
+/// # Safety
+///
+/// `input_path` must be null or point to a nul terminated string, `buffer` must be null or valid for
+/// writes of `buffer_len` bytes and `required_len` must be null or valid for a write
+#[no_mangle]
+pub unsafe extern "C" fn lab_to_dae_string(
+    input_path: *const c_char,
+    buffer: *mut c_char,
+    buffer_len: usize,
+    required_len: *mut usize,
+) -> c_int {
+    catch_panic(|| {
+        let xml_content = match convert(input_path) {
+            Ok(xml_content) => xml_content,
+            Err(code) => return code,
+        };
+
+        if !required_len.is_null() {
+            *required_len = xml_content.len();
+        }
+        if buffer.is_null() || buffer_len <= xml_content.len() {
+            return LAB_ERROR_BUFFER_TOO_SMALL;
+        }
+
+        ptr::copy_nonoverlapping(xml_content.as_ptr() as *const c_char, buffer, xml_content.len());
+        *buffer.add(xml_content.len()) = 0;
+        LAB_OK
+    })
+}
+
+fn catch_panic<F: FnOnce() -> c_int>(f: F) -> c_int {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(LAB_ERROR_PANIC)
+}
+
+unsafe fn path_from_c<'p>(path: *const c_char) -> Option<&'p Path> {
+    if path.is_null() {
+        return None;
+    }
+
+    CStr::from_ptr(path).to_str().ok().map(Path::new)
+}
+
+/// the collada document of a .lab file, including the xml prolog
+unsafe fn convert(input_path: *const c_char) -> Result<String, c_int> {
+    let input_path = path_from_c(input_path).ok_or(LAB_ERROR_INVALID_ARGUMENT)?;
+    let mut file = open_lab_file(input_path).map_err(|_| LAB_ERROR_IO)?;
+
+    let version = file.read_u16::<LittleEndian>().map_err(|_| LAB_ERROR_INVALID_FILE)?;
+    if version < MIN_VERSION {
+        return Err(LAB_ERROR_UNSUPPORTED_VERSION);
+    }
+
+    let mut anim_data = AnimDataBone::new();
+    anim_data.load(&mut file).map_err(|_| LAB_ERROR_INVALID_FILE)?;
+
+    let mut xml_content = Vec::new();
+    anim_data.write_collada(&mut xml_content).map_err(|_| LAB_ERROR_IO)?;
+    String::from_utf8(xml_content).map_err(|_| LAB_ERROR_INVALID_FILE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anim::bone::XML_PROLOG;
+    use std::ffi::CString;
+
+    #[test]
+    fn it_converts_into_a_caller_buffer() {
+        let input_path = CString::new("./src/tests/anim-quat.lab").unwrap();
+
+        let mut required_len = 0;
+        let code = unsafe { lab_to_dae_string(input_path.as_ptr(), ptr::null_mut(), 0, &mut required_len) };
+        assert_eq!(code, LAB_ERROR_BUFFER_TOO_SMALL);
+
+        let mut buffer = vec![0 as c_char; required_len + 1];
+        let code = unsafe {
+            lab_to_dae_string(input_path.as_ptr(), buffer.as_mut_ptr(), buffer.len(), &mut required_len)
+        };
+        assert_eq!(code, LAB_OK);
+
+        let xml_content = unsafe { CStr::from_ptr(buffer.as_ptr()) }.to_str().unwrap();
+        assert_eq!(xml_content.len(), required_len);
+        assert!(xml_content.starts_with(XML_PROLOG));
+        assert!(xml_content.contains("<library_animations>"));
+    }
+
+    #[test]
+    fn it_returns_error_codes_instead_of_panicking() {
+        let missing_path = CString::new("./src/tests/missing.lab").unwrap();
+        let output_path = CString::new("./target/missing.dae").unwrap();
+        unsafe {
+            assert_eq!(lab_to_dae(missing_path.as_ptr(), output_path.as_ptr()), LAB_ERROR_IO);
+            assert_eq!(lab_to_dae(ptr::null(), output_path.as_ptr()), LAB_ERROR_INVALID_ARGUMENT);
+        }
+
+        let rootless_path = CString::new("./src/tests/anim-no-root.lab").unwrap();
+        let code = unsafe { lab_to_dae(rootless_path.as_ptr(), output_path.as_ptr()) };
+        assert_eq!(code, LAB_ERROR_INVALID_FILE);
+    }
+
+    #[test]
+    fn it_turns_a_panic_into_an_error_code() {
+        assert_eq!(catch_panic(|| panic!("a bug in the conversion")), LAB_ERROR_PANIC);
+        assert_eq!(catch_panic(|| LAB_OK), LAB_OK);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod anim;
+pub mod ffi;
+
+pub const MIN_VERSION: u16 = 4010;
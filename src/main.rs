@@ -1,21 +1,779 @@
-use anim::bone::AnimDataBone;
+use lab_parser::anim::bone::{probe, to_merged_collada, AnimDataBone, Handedness, XML_PROLOG};
+use lab_parser::anim::clips::AnimationClips;
+use lab_parser::anim::options::{parse_root_transform, AxisMap, DummySpace, ExportOptions};
+use lab_parser::anim::pointcache::write_point_cache;
+use lab_parser::anim::pose::write_pose_json;
+use lab_parser::anim::diff::{diff, diff_rest_pose};
+use lab_parser::anim::error::LabError;
+use lab_parser::anim::euler::EulerOrder;
+use lab_parser::anim::reader::{open_lab_file, LabBytes};
+use lab_parser::anim::skin::SkinWeights;
+use lab_parser::anim::usd::write_usda;
+use lab_parser::MIN_VERSION;
 use byteorder::{LittleEndian, ReadBytesExt};
-use std::{env, fs::File};
+use encoding_rs::Encoding;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::env;
+use std::io::{self, Cursor};
+use std::panic;
+use std::process;
 use std::fs::*;
 use std::io::prelude::*;
 use std::path::Path;
 use std::ffi::OsStr;
 
-mod anim;
-
-pub const MIN_VERSION: u16 = 4010;
-
 fn get_extension_from_filename(filename: &str) -> Option<&str> {
     Path::new(filename)
         .extension()
         .and_then(OsStr::to_str)
 }
 
+/// name of the config file that is picked up from the working directory when `--config` isn't given
+const CONFIG_FILE_NAME: &str = "lab-parser.toml";
+
+/// default export options read from a toml config file. the keys are named after the flags they stand
+/// for, e.g. `max-frames = 100` or `ignore-bone = ["Bip01 Footsteps"]`. a switch set to false stands for its
+/// `--no-` flag, so a config can turn off what another layer turned on
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+struct ExportConfig {
+    center_root: Option<bool>,
+    inject_bind_pose: Option<bool>,
+    strip_dummies: Option<bool>,
+    split_bones: Option<bool>,
+    split_skeleton: Option<bool>,
+    world_space: Option<bool>,
+    auto_handedness: Option<bool>,
+    fix_mirror: Option<bool>,
+    no_normalize_quats: Option<bool>,
+    interleaved_keys: Option<bool>,
+    root_motion_track: Option<bool>,
+    emit_invbind_json: Option<bool>,
+    dry_run: Option<bool>,
+    cache: Option<bool>,
+    validate: Option<bool>,
+    strict_sizes: Option<bool>,
+    reduce_keys: Option<bool>,
+    no_interpolation_source: Option<bool>,
+    skin_weights: Option<String>,
+    max_frames: Option<usize>,
+    target_duration: Option<f32>,
+    time_offset: Option<f32>,
+    precision: Option<usize>,
+    epsilon: Option<f32>,
+    dummy_space: Option<String>,
+    euler: Option<String>,
+    max_depth: Option<usize>,
+    ignore_bone: Vec<String>,
+    pose_frames: Vec<usize>,
+    clips: Option<String>,
+    skeleton_from: Option<String>,
+    root_transform: Option<String>,
+    axis_map: Option<String>,
+    encoding: Option<String>,
+    authoring_tool: Option<String>,
+}
+
+impl ExportConfig {
+    /// the flags the config stands for, so its values go through the same checks as the command line
+    fn to_flags(&self) -> Vec<String> {
+        let mut flags = Vec::new();
+        // the flags a switch stands for when it is set to true and to false
+        let switches = [
+            ("--center-root", "--no-center-root", self.center_root),
+            ("--inject-bind-pose", "--no-inject-bind-pose", self.inject_bind_pose),
+            ("--strip-dummies", "--no-strip-dummies", self.strip_dummies),
+            ("--split-bones", "--no-split-bones", self.split_bones),
+            ("--split-skeleton", "--no-split-skeleton", self.split_skeleton),
+            ("--world-space", "--no-world-space", self.world_space),
+            ("--auto-handedness", "--no-auto-handedness", self.auto_handedness),
+            ("--fix-mirror", "--no-fix-mirror", self.fix_mirror),
+            ("--no-normalize-quats", "--normalize-quats", self.no_normalize_quats),
+            ("--interleaved-keys", "--no-interleaved-keys", self.interleaved_keys),
+            ("--root-motion-track", "--no-root-motion-track", self.root_motion_track),
+            ("--emit-invbind-json", "--no-emit-invbind-json", self.emit_invbind_json),
+            ("--dry-run", "--no-dry-run", self.dry_run),
+            ("--cache", "--no-cache", self.cache),
+            ("--validate", "--no-validate", self.validate),
+            ("--strict-sizes", "--no-strict-sizes", self.strict_sizes),
+            ("--reduce-keys", "--no-reduce-keys", self.reduce_keys),
+            ("--no-interpolation-source", "--interpolation-source", self.no_interpolation_source),
+        ];
+        for (on_flag, off_flag, value) in switches.iter() {
+            match value {
+                Some(true) => flags.push(on_flag.to_string()),
+                Some(false) => flags.push(off_flag.to_string()),
+                None => {}
+            }
+        }
+
+        let values = [
+            ("--skin-weights", self.skin_weights.clone()),
+            ("--max-frames", self.max_frames.map(|max_frames| max_frames.to_string())),
+            ("--target-duration", self.target_duration.map(|target_duration| target_duration.to_string())),
+            ("--time-offset", self.time_offset.map(|time_offset| time_offset.to_string())),
+            ("--precision", self.precision.map(|precision| precision.to_string())),
+            ("--epsilon", self.epsilon.map(|epsilon| epsilon.to_string())),
+            ("--dummy-space", self.dummy_space.clone()),
+            ("--euler", self.euler.clone()),
+            ("--max-depth", self.max_depth.map(|max_depth| max_depth.to_string())),
+            ("--clips", self.clips.clone()),
+            ("--skeleton-from", self.skeleton_from.clone()),
+            ("--root-transform", self.root_transform.clone()),
+            ("--axis-map", self.axis_map.clone()),
+            ("--encoding", self.encoding.clone()),
+            ("--authoring-tool", self.authoring_tool.clone()),
+        ];
+        for (flag, value) in values.iter() {
+            if let Some(value) = value {
+                flags.push(flag.to_string());
+                flags.push(value.clone());
+            }
+        }
+
+        for name in self.ignore_bone.iter() {
+            flags.push("--ignore-bone".to_string());
+            flags.push(name.clone());
+        }
+        if !self.pose_frames.is_empty() {
+            let frames: Vec<String> = self.pose_frames.iter().map(|frame| frame.to_string()).collect();
+            flags.push("--pose-frames".to_string());
+            flags.push(frames.join(","));
+        }
+
+        flags
+    }
+}
+
+/// the flags of the config file given with `--config`, or of `lab-parser.toml` in the working directory
+/// if there is one, followed by the rest of the flags. the explicit flags come last so they override the
+/// config, and bones ignored on the command line replace the ones of the config rather than adding to them
+fn apply_config_file(flags: &[String]) -> Vec<String> {
+    let mut config_path = None;
+    let mut other_flags = Vec::new();
+    let mut flag_iter = flags.iter();
+    while let Some(flag) = flag_iter.next() {
+        if flag == "--config" {
+            match flag_iter.next() {
+                Some(path) => config_path = Some(path.clone()),
+                None => panic!("--config expects the path of a config file"),
+            }
+        } else {
+            other_flags.push(flag.clone());
+        }
+    }
+
+    let config_path = match config_path {
+        Some(path) => path,
+        None if Path::new(CONFIG_FILE_NAME).is_file() => CONFIG_FILE_NAME.to_string(),
+        None => return other_flags,
+    };
+    let mut config: ExportConfig = match read_to_string(&config_path) {
+        Err(why) => panic!("Couldn't read the config file {}: {}", config_path, why),
+        Ok(content) => match toml::from_str(&content) {
+            Err(why) => panic!("Invalid config file {}: {}", config_path, why),
+            Ok(config) => config,
+        },
+    };
+
+    if other_flags.iter().any(|flag| flag == "--ignore-bone") {
+        config.ignore_bone.clear();
+    }
+    let mut flags = config.to_flags();
+    flags.extend(other_flags);
+    flags
+}
+
+fn parse_export_options(flags: &[String]) -> ExportOptions {
+    let mut options = ExportOptions::default();
+
+    let flags = apply_config_file(flags);
+    let mut flags = flags.iter();
+    while let Some(flag) = flags.next() {
+        match flag.as_str() {
+            "--center-root" => options.center_root = true,
+            "--no-center-root" => options.center_root = false,
+            "--inject-bind-pose" => options.inject_bind_pose = true,
+            "--no-inject-bind-pose" => options.inject_bind_pose = false,
+            "--strip-dummies" => options.strip_dummies = true,
+            "--no-strip-dummies" => options.strip_dummies = false,
+            "--split-bones" => options.split_bones = true,
+            "--no-split-bones" => options.split_bones = false,
+            "--split-skeleton" => options.split_skeleton = true,
+            "--no-split-skeleton" => options.split_skeleton = false,
+            // the name other tools give to a flat export
+            "--world-space" | "--flatten-hierarchy" => options.world_space = true,
+            "--no-world-space" | "--no-flatten-hierarchy" => options.world_space = false,
+            "--auto-handedness" => options.auto_handedness = true,
+            "--no-auto-handedness" => options.auto_handedness = false,
+            "--fix-mirror" => options.fix_mirror = true,
+            "--no-fix-mirror" => options.fix_mirror = false,
+            "--normalize-quats" => options.keep_unnormalized_quats = false,
+            "--no-normalize-quats" => options.keep_unnormalized_quats = true,
+            "--interleaved-keys" => options.interleaved_keys = true,
+            "--no-interleaved-keys" => options.interleaved_keys = false,
+            "--root-motion-track" => options.root_motion_track = true,
+            "--no-root-motion-track" => options.root_motion_track = false,
+            "--emit-invbind-json" => options.emit_invbind_json = true,
+            "--no-emit-invbind-json" => options.emit_invbind_json = false,
+            "--dry-run" => options.dry_run = true,
+            "--no-dry-run" => options.dry_run = false,
+            "--cache" => options.cache = true,
+            "--no-cache" => options.cache = false,
+            "--validate" => options.validate = true,
+            "--no-validate" => options.validate = false,
+            "--strict-sizes" => options.strict_sizes = true,
+            "--no-strict-sizes" => options.strict_sizes = false,
+            "--reduce-keys" => options.reduce_keys = true,
+            "--no-reduce-keys" => options.reduce_keys = false,
+            "--no-interpolation-source" => options.no_interpolation_source = true,
+            "--interpolation-source" => options.no_interpolation_source = false,
+            "--skin-weights" => match flags.next() {
+                Some(path) => options.skin_weights_path = Some(path.clone()),
+                None => panic!("--skin-weights expects the path of a weights file"),
+            },
+            "--max-frames" => match flags.next().and_then(|value| value.parse::<usize>().ok()) {
+                Some(max_frames) if max_frames > 0 => options.max_frames = Some(max_frames),
+                _ => panic!("--max-frames expects a positive number of frames"),
+            },
+            "--target-duration" => match flags.next().and_then(|value| value.parse::<f32>().ok()) {
+                Some(target_duration) if target_duration > 0.0 => options.target_duration = Some(target_duration),
+                _ => panic!("--target-duration expects a positive number of seconds"),
+            },
+            "--time-offset" => match flags.next().and_then(|value| value.parse::<f32>().ok()) {
+                Some(time_offset) if time_offset >= 0.0 => options.time_offset = Some(time_offset),
+                _ => panic!("--time-offset expects a number of seconds that isn't negative"),
+            },
+            "--precision" => match flags.next().and_then(|value| value.parse::<usize>().ok()) {
+                Some(precision) if precision > 0 => options.precision = Some(precision),
+                _ => panic!("--precision expects a positive number of significant digits"),
+            },
+            "--epsilon" => match flags.next().and_then(|value| value.parse::<f32>().ok()) {
+                Some(epsilon) if epsilon >= 0.0 => options.epsilon = Some(epsilon),
+                _ => panic!("--epsilon expects a tolerance of zero or more"),
+            },
+            "--dummy-space" => match flags.next().and_then(|value| DummySpace::parse(value)) {
+                Some(dummy_space) => options.dummy_space = dummy_space,
+                None => panic!("--dummy-space expects local or world"),
+            },
+            "--euler" => match flags.next().and_then(|value| EulerOrder::parse(value)) {
+                Some(euler_order) => options.euler_order = Some(euler_order),
+                None => panic!("--euler expects a rotation order such as xyz or zyx"),
+            },
+            "--ignore-bone" => match flags.next() {
+                Some(name) => options.ignored_bones.push(name.clone()),
+                None => panic!("--ignore-bone expects the name of a bone"),
+            },
+            "--max-depth" => match flags.next().and_then(|value| value.parse::<usize>().ok()) {
+                Some(max_depth) => options.max_depth = Some(max_depth),
+                None => panic!("--max-depth expects a depth, 0 keeping only the root"),
+            },
+            "--pose-frames" => {
+                let frames = flags
+                    .next()
+                    .map(|value| value.split(',').map(|frame| frame.trim().parse::<usize>()).collect());
+                match frames {
+                    Some(Ok(frames)) => options.pose_frames = frames,
+                    _ => panic!("--pose-frames expects comma-separated frame numbers, e.g. 0,50,100"),
+                }
+            },
+            "--clips" => match flags.next() {
+                Some(path) => options.clips_path = Some(path.clone()),
+                None => panic!("--clips expects the path of a clips file"),
+            },
+            "--skeleton-from" => match flags.next() {
+                Some(path) => options.skeleton_from_path = Some(path.clone()),
+                None => panic!("--skeleton-from expects the path of a .lab file"),
+            },
+            "--root-transform" => {
+                let value = match flags.next() {
+                    Some(value) => value,
+                    None => panic!("--root-transform expects a preset or 16 comma-separated numbers"),
+                };
+                match parse_root_transform(value) {
+                    Some(root_transform) => options.root_transform = Some(root_transform),
+                    None => panic!("Invalid root transform {}", value),
+                }
+            },
+            "--axis-map" => match flags.next().and_then(|value| AxisMap::parse(value)) {
+                Some(axis_map) => options.axis_map = Some(axis_map),
+                None => panic!("--axis-map expects the three signed axes of the new x, y and z, e.g. +X+Z-Y"),
+            },
+            "--encoding" => {
+                let label = match flags.next() {
+                    Some(label) => label,
+                    None => panic!("--encoding expects the name of an encoding"),
+                };
+                match Encoding::for_label(label.as_bytes()) {
+                    Some(encoding) => options.name_encoding = Some(encoding),
+                    None => panic!("Unknown encoding {}", label),
+                }
+            },
+            "--authoring-tool" => match flags.next() {
+                Some(authoring_tool) => options.authoring_tool = Some(authoring_tool.clone()),
+                None => panic!("--authoring-tool expects the name of the program to credit"),
+            },
+            _ => panic!("Unknown option {}", flag),
+        }
+    }
+
+    options
+}
+
+/// opens a .lab (or .lab.gz) file and checks that its version is supported. the returned reader is
+/// positioned right after the version
+fn open_lab_input(path: &str) -> Cursor<LabBytes> {
+    match get_extension_from_filename(path) {
+        Some(extension) => {
+            if extension != "lab" && !path.ends_with(".lab.gz") {
+                panic!("Can't read non .lab file");
+            }
+        },
+
+        None => panic!("Unrecognized file format")
+    };
+
+    let lab_file_path = Path::new(path);
+    let display = lab_file_path.display();
+    let mut file = match open_lab_file(lab_file_path) {
+        Err(why) => panic!("Couldn't open {}: {}", display, why),
+        Ok(file) => file,
+    };
+
+    let version = file.read_u16::<LittleEndian>().unwrap();
+    if version < MIN_VERSION {
+        panic!("The animation file's version is incompatible with this program");
+    }
+
+    file
+}
+
+fn get_file_stem(path: &str) -> &str {
+    let file_stem = OsStr::to_str(Path::new(path).file_stem().unwrap()).unwrap();
+    file_stem.trim_end_matches(".lab")
+}
+
+fn create_dae_file(result_file_name: &str) -> File {
+    OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .create(true)
+        .open(result_file_name).unwrap()
+}
+
+fn write_dae_file(result_file_name: &str, xml_content: &str) {
+    let mut file = create_dae_file(result_file_name);
+    file.write_all(XML_PROLOG.as_bytes()).unwrap();
+    file.write_all(xml_content.as_bytes()).unwrap();
+}
+
+fn print_dry_run(result_file_name: &str, size: usize) {
+    println!("Would write {} ({} bytes)", result_file_name, size);
+}
+
+/// an output file of a batch conversion, as listed in the manifest
+#[derive(Serialize)]
+struct ManifestEntry {
+    source: String,
+    bones: usize,
+    frames: usize,
+    warnings: Vec<String>,
+}
+
+/// converts a .lab file into a .dae file named after it in the working directory, or writes the document
+/// to stdout with `to_stdout`, in which case the progress messages go to stderr so they don't end up in the
+/// piped document. returns the manifest entry of every file written
+fn convert_lab_to_dae(path: &str, flags: &[String], to_stdout: bool) -> BTreeMap<String, ManifestEntry> {
+    let status = |message: &str| {
+        if to_stdout {
+            eprintln!("{}", message);
+        } else {
+            println!("{}", message);
+        }
+    };
+
+    let mut file = open_lab_input(path);
+    let file_stem = get_file_stem(path);
+
+    let options = parse_export_options(flags);
+    let writes_several_files = options.split_bones
+        || options.split_skeleton
+        || options.emit_invbind_json
+        || !options.pose_frames.is_empty();
+    if to_stdout && writes_several_files {
+        panic!(
+            "--split-bones, --split-skeleton, --emit-invbind-json and --pose-frames write several files, they \
+             can't be written to stdout"
+        );
+    }
+    if options.split_bones && options.split_skeleton {
+        panic!("--split-bones and --split-skeleton can't be combined");
+    }
+    let split_bones = options.split_bones;
+    let split_skeleton = options.split_skeleton;
+    let emit_invbind_json = options.emit_invbind_json;
+    let auto_handedness = options.auto_handedness;
+    let dry_run = options.dry_run;
+    let cache = options.cache;
+    let pose_frames = options.pose_frames.clone();
+    let skin_weights = options.skin_weights_path.as_ref().map(|path| {
+        match SkinWeights::load_from_file(Path::new(path)) {
+            Err(why) => panic!("Couldn't read skin weights from {}", why),
+            Ok(skin_weights) => skin_weights,
+        }
+    });
+    let clips = options.clips_path.as_ref().map(|path| {
+        match AnimationClips::load_from_file(Path::new(path)) {
+            Err(why) => panic!("Couldn't read clips from {}", why),
+            Ok(clips) => clips,
+        }
+    });
+    let reference_skeleton = options.skeleton_from_path.as_deref().map(load_reference_skeleton);
+    let mut anim_data = AnimDataBone::with_options(options);
+    if let Some(skin_weights) = skin_weights {
+        anim_data.set_skin_weights(skin_weights);
+    }
+    if let Some(clips) = clips {
+        anim_data.set_clips(clips);
+    }
+    let cache_path = format!("./{}.labcache", file_stem);
+    let mut loaded_from_cache = false;
+    if cache && is_newer_than(&cache_path, path) {
+        let loaded = File::open(&cache_path)
+            .map_err(LabError::from)
+            .and_then(|mut cache_file| anim_data.load_cache(&mut cache_file));
+        match loaded {
+            Ok(()) => {
+                status(&format!("Loaded the animation data from {}", cache_path));
+                loaded_from_cache = true;
+            }
+            Err(LabError::StaleCache) => status(&format!("{} is out of date, it will be replaced", cache_path)),
+            Err(why) => panic!("Couldn't load {}", why.in_file(&cache_path)),
+        }
+    }
+    if !loaded_from_cache {
+        status("Loading animation data...");
+
+        if let Err(why) = anim_data.load(&mut file) {
+            panic!("Couldn't load {}", why.in_file(path));
+        }
+
+        if cache && !dry_run {
+            let written = File::create(&cache_path)
+                .map_err(LabError::from)
+                .and_then(|mut cache_file| anim_data.write_cache(&mut cache_file));
+            if let Err(why) = written {
+                panic!("Couldn't write the cache {}: {}", cache_path, why);
+            }
+        }
+    }
+    print_warnings(&anim_data);
+    if let Some(reference_skeleton) = &reference_skeleton {
+        if let Err(why) = anim_data.take_bone_names_from(reference_skeleton) {
+            panic!("Couldn't take the bone names of --skeleton-from for {}: {}", path, why);
+        }
+    }
+    if auto_handedness {
+        match anim_data.handedness() {
+            Handedness::Left => status("Detected a left-handed skeleton, mirroring it on the z axis..."),
+            Handedness::Right => status("Detected a right-handed skeleton, leaving it as it is..."),
+        }
+    }
+
+    if emit_invbind_json {
+        let json_content = anim_data.write_inverse_bind_json();
+        let result_file_name = format!("./{}.invbind.json", file_stem);
+        if dry_run {
+            print_dry_run(&result_file_name, json_content.len());
+        } else if let Err(why) = write(result_file_name, json_content) {
+            panic!("Couldn't write the inverse bind matrices: {}", why);
+        }
+    }
+
+    let mut output_files = Vec::new();
+    let entry = || ManifestEntry {
+        source: path.to_string(),
+        bones: anim_data.get_num_bones(),
+        frames: anim_data.get_num_frames(),
+        warnings: anim_data.warnings().to_vec(),
+    };
+
+    if split_bones {
+        status("Writing data to a collada file per bone...");
+
+        for i in 0..anim_data.get_num_bones() {
+            let bone_name = anim_data.get_bone_name(i).replace(" ", "_");
+            let xml_content = anim_data.write_bone_collada_data(i);
+            let result_file_name = format!("./{}_{}.dae", file_stem, bone_name);
+            if dry_run {
+                print_dry_run(&result_file_name, XML_PROLOG.len() + xml_content.len());
+            } else {
+                write_dae_file(&result_file_name, &xml_content);
+            }
+            output_files.push((result_file_name, entry()));
+        }
+    } else if split_skeleton {
+        status("Writing the skeleton and the animation to separate collada files...");
+
+        let documents = [
+            (format!("./{}.skel.dae", file_stem), anim_data.to_skeleton_collada()),
+            (format!("./{}.anim.dae", file_stem), anim_data.to_animation_collada()),
+        ];
+        for (result_file_name, xml_content) in documents.iter() {
+            if dry_run {
+                print_dry_run(result_file_name, XML_PROLOG.len() + xml_content.len());
+            } else {
+                write_dae_file(result_file_name, xml_content);
+            }
+            output_files.push((result_file_name.clone(), entry()));
+        }
+    } else if to_stdout {
+        status("Writing data to stdout...");
+        if dry_run {
+            let mut xml_content = Vec::new();
+            if let Err(why) = anim_data.write_collada(&mut xml_content) {
+                panic!("Couldn't generate the collada file: {}", why);
+            }
+            eprintln!("Would write {} bytes to stdout", xml_content.len());
+        } else {
+            let stdout = io::stdout();
+            let mut stdout = stdout.lock();
+            match anim_data.write_collada(&mut stdout).and_then(|()| stdout.flush()) {
+                // the reading end stopped early, e.g. `| head`, which isn't an error of the conversion
+                Err(ref why) if why.kind() == io::ErrorKind::BrokenPipe => {}
+                Err(why) => panic!("Couldn't write the collada file to stdout: {}", why),
+                Ok(()) => {}
+            }
+        }
+    } else {
+        status("Writing data to a collada file...");
+        let result_file_name = format!("./{}.dae", file_stem);
+        output_files.push((result_file_name.clone(), entry()));
+        if dry_run {
+            let mut xml_content = Vec::new();
+            if let Err(why) = anim_data.write_collada(&mut xml_content) {
+                panic!("Couldn't generate the collada file: {}", why);
+            }
+            print_dry_run(&result_file_name, xml_content.len());
+        } else {
+            let mut dae_file = create_dae_file(&result_file_name);
+            if let Err(why) = anim_data.write_collada(&mut dae_file) {
+                panic!("Couldn't write the collada file: {}", why);
+            }
+        }
+    }
+
+    if !pose_frames.is_empty() {
+        status("Writing the poses to a collada file per frame...");
+    }
+    for frame in pose_frames {
+        let result_file_name = format!("./{}_pose{}.dae", file_stem, frame);
+        output_files.push((result_file_name.clone(), entry()));
+        if dry_run {
+            let mut xml_content = Vec::new();
+            if let Err(why) = anim_data.write_pose_collada(&mut xml_content, frame) {
+                panic!("Couldn't generate the pose of frame {}: {}", frame, why);
+            }
+            print_dry_run(&result_file_name, xml_content.len());
+        } else {
+            let mut dae_file = create_dae_file(&result_file_name);
+            if let Err(why) = anim_data.write_pose_collada(&mut dae_file, frame) {
+                panic!("Couldn't write the pose of frame {}: {}", frame, why);
+            }
+        }
+    }
+    status("Done!");
+
+    output_files.into_iter().collect()
+}
+
+/// converts every .lab file of a directory. each file is converted on its own so a corrupt file is
+/// reported and skipped instead of aborting the rest of the batch. `--manifest <path>` writes a json
+/// object mapping every written file to its source
+fn convert_directory_to_dae(directory: &Path, flags: &[String]) {
+    let mut manifest_path = None;
+    let mut export_flags = Vec::new();
+    let mut flags = flags.iter();
+    while let Some(flag) = flags.next() {
+        if flag == "--manifest" {
+            match flags.next() {
+                Some(path) => manifest_path = Some(path.clone()),
+                None => panic!("--manifest expects the path of the manifest file"),
+            }
+        } else {
+            export_flags.push(flag.clone());
+        }
+    }
+
+    let paths = get_lab_paths(directory);
+
+    let mut manifest = BTreeMap::new();
+    let mut failures = Vec::new();
+    for path in paths.iter() {
+        println!("Converting {}...", path);
+        match panic::catch_unwind(|| convert_lab_to_dae(path, &export_flags, false)) {
+            Ok(entries) => manifest.extend(entries),
+            Err(payload) => failures.push((path, get_panic_message(payload.as_ref()))),
+        }
+    }
+
+    if let Some(manifest_path) = manifest_path {
+        if let Err(why) = write(&manifest_path, serde_json::to_string_pretty(&manifest).unwrap()) {
+            panic!("Couldn't write the manifest to {}: {}", manifest_path, why);
+        }
+    }
+
+    println!("Converted {} of {} files", paths.len() - failures.len(), paths.len());
+    if !failures.is_empty() {
+        println!("Failed to convert:");
+        for (path, message) in failures.iter() {
+            println!("  {}: {}", path, message);
+        }
+        process::exit(1);
+    }
+}
+
+/// converts several .lab files of the same skeleton into a single .dae, with the animation of each file
+/// grouped under its file stem. the output path comes first, then the .lab files and then the export
+/// options, which apply to every file
+fn merge_lab_to_dae(args: &[String]) {
+    let input_count = args.iter().skip(1).take_while(|arg| !arg.starts_with("--")).count();
+    if input_count == 0 {
+        panic!("labmerge expects the output .dae file followed by the .lab files to merge");
+    }
+    let (output_path, input_paths, flags) = (&args[0], &args[1..=input_count], &args[input_count + 1..]);
+
+    let reference_skeleton = parse_export_options(flags).skeleton_from_path.as_deref().map(load_reference_skeleton);
+    let mut animations = Vec::new();
+    for path in input_paths.iter() {
+        println!("Loading {}...", path);
+        let mut anim_data = AnimDataBone::with_options(parse_export_options(flags));
+        if let Err(why) = anim_data.load(&mut open_lab_input(path)) {
+            panic!("Couldn't load {}", why.in_file(path));
+        }
+        print_warnings(&anim_data);
+        if let Some(reference_skeleton) = &reference_skeleton {
+            if let Err(why) = anim_data.take_bone_names_from(reference_skeleton) {
+                panic!("Couldn't take the bone names of --skeleton-from for {}: {}", path, why);
+            }
+        }
+        animations.push((get_file_stem(path), anim_data));
+    }
+
+    let animations: Vec<(&str, &AnimDataBone)> =
+        animations.iter().map(|(name, anim_data)| (*name, anim_data)).collect();
+    match to_merged_collada(&animations) {
+        Ok(xml_content) => write_dae_file(output_path, &xml_content),
+        Err(why) => panic!("Couldn't merge the animations: {}", why),
+    }
+    println!("Done!");
+}
+
+/// loads the .lab file given to --skeleton-from, whose bone names the converted animations take
+fn load_reference_skeleton(path: &str) -> AnimDataBone {
+    let mut reference_skeleton = AnimDataBone::new();
+    if let Err(why) = reference_skeleton.load(&mut open_lab_input(path)) {
+        panic!("Couldn't load the reference skeleton {}", why.in_file(path));
+    }
+    print_warnings(&reference_skeleton);
+    reference_skeleton
+}
+
+/// prints the warnings raised while loading an animation to stderr
+fn print_warnings(anim_data: &AnimDataBone) {
+    for warning in anim_data.warnings().iter() {
+        eprintln!("Warning: {}", warning);
+    }
+}
+
+/// whether the file at `path` was modified after the one at `other_path`, false if either can't be read
+fn is_newer_than(path: &str, other_path: &str) -> bool {
+    let modified = |path: &str| metadata(path).and_then(|metadata| metadata.modified()).ok();
+    match (modified(path), modified(other_path)) {
+        (Some(modified), Some(other_modified)) => modified > other_modified,
+        _ => false,
+    }
+}
+
+/// the .lab and .lab.gz files of a directory, sorted by name
+fn get_lab_paths(directory: &Path) -> Vec<String> {
+    let mut paths: Vec<String> = match read_dir(directory) {
+        Err(why) => panic!("Couldn't read {}: {}", directory.display(), why),
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path().to_string_lossy().into_owned())
+            .filter(|path| path.ends_with(".lab") || path.ends_with(".lab.gz"))
+            .collect(),
+    };
+    paths.sort();
+    paths
+}
+
+/// lists the version, key type and counts of every .lab file of a directory, reading only their headers.
+/// `--csv` prints the list as csv with a header row instead of a table. files that can't be read are
+/// reported at the end
+fn audit_directory(directory: &Path, flags: &[String]) {
+    let mut csv = false;
+    for flag in flags.iter() {
+        match flag.as_str() {
+            "--csv" => csv = true,
+            _ => panic!("Unknown option {}", flag),
+        }
+    }
+
+    let mut failures = Vec::new();
+    if csv {
+        println!("file,version,key_type,bones,frames,dummies");
+    } else {
+        println!("{:<40} {:>7} {:<8} {:>6} {:>7} {:>8}", "file", "version", "keys", "bones", "frames", "dummies");
+    }
+    for path in get_lab_paths(directory).iter() {
+        let info = open_lab_file(Path::new(path))
+            .map_err(LabError::from)
+            .and_then(|mut file| probe(&mut file));
+        let info = match info {
+            Ok(info) => info,
+            Err(why) => {
+                failures.push(why.in_file(path));
+                continue;
+            }
+        };
+
+        let file_name = Path::new(path).file_name().unwrap().to_string_lossy();
+        if csv {
+            println!(
+                "{},{},{},{},{},{}",
+                file_name, info.version, info.key_kind.name(), info.bone_count, info.frame_count, info.dummy_count
+            );
+        } else {
+            println!(
+                "{:<40} {:>7} {:<8} {:>6} {:>7} {:>8}",
+                file_name, info.version, info.key_kind.name(), info.bone_count, info.frame_count, info.dummy_count
+            );
+        }
+    }
+
+    if !failures.is_empty() {
+        eprintln!("Failed to read:");
+        for why in failures.iter() {
+            eprintln!("  {}", why);
+        }
+        process::exit(1);
+    }
+}
+
+fn get_panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else {
+        "unknown error".to_string()
+    }
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
 
@@ -26,43 +784,155 @@ fn main() {
     let operation = &args[1];
     match operation.as_str() {
         "lab2dae" => {
-             match get_extension_from_filename(&args[2]) {
-                Some(extension) => {
-                    if extension != "lab" {
-                        panic!("Can't read non .lab file");
-                    }
-                },
+            if Path::new(&args[2]).is_dir() {
+                convert_directory_to_dae(Path::new(&args[2]), &args[3..]);
+            } else if args.get(3).map(String::as_str) == Some("-") {
+                convert_lab_to_dae(&args[2], &args[4..], true);
+            } else {
+                convert_lab_to_dae(&args[2], &args[3..], false);
+            }
+        },
+        "lab2usd" => {
+            let mut file = open_lab_input(&args[2]);
+            let file_stem = get_file_stem(&args[2]);
+
+            let mut anim_data = AnimDataBone::with_options(parse_export_options(&args[3..]));
+            println!("Loading animation data...");
+
+            if let Err(why) = anim_data.load(&mut file) {
+                panic!("Couldn't load {}", why.in_file(&args[2]));
+            }
+            print_warnings(&anim_data);
 
-                None => panic!("Unrecognized file format")
+            println!("Writing data to a usda file...");
+            let usda_content = match write_usda(&anim_data) {
+                Ok(usda_content) => usda_content,
+                Err(why) => panic!("Couldn't convert {}: {}", args[2], why),
             };
+            if let Err(why) = write(format!("./{}.usda", file_stem), usda_content) {
+                panic!("Couldn't write the usda file: {}", why);
+            }
+            println!("Done!");
+        },
+        "lab2pointcache" => {
+            let mut file = open_lab_input(&args[2]);
+            let file_stem = get_file_stem(&args[2]);
+
+            let mut anim_data = AnimDataBone::with_options(parse_export_options(&args[3..]));
+            println!("Loading animation data...");
+
+            if let Err(why) = anim_data.load(&mut file) {
+                panic!("Couldn't load {}", why.in_file(&args[2]));
+            }
+            print_warnings(&anim_data);
 
-            let lab_file_path = Path::new(&args[2]);
-            let file_stem = OsStr::to_str(lab_file_path.file_stem().unwrap()).unwrap();
-            let display = lab_file_path.display();
-            let mut file = match File::open(&lab_file_path) {
-                Err(why) => panic!("Couldn't open {}: {}", display, why),
-                Ok(file) => file,
+            println!("Writing data to a point cache file...");
+            let mut point_cache = Vec::new();
+            if let Err(why) = write_point_cache(&anim_data, &mut point_cache) {
+                panic!("Couldn't generate the point cache: {}", why);
+            }
+            if let Err(why) = write(format!("./{}.pc", file_stem), point_cache) {
+                panic!("Couldn't write the point cache file: {}", why);
+            }
+            println!("Done!");
+        },
+        "labpose" => {
+            let frame = match args.get(3).and_then(|value| value.parse::<usize>().ok()) {
+                Some(frame) => frame,
+                None => panic!("labpose expects the .lab file and a frame number"),
             };
 
-            let version = file.read_u16::<LittleEndian>().unwrap();
-            if version < MIN_VERSION {
-                panic!("The animation file's version is incompatible with this program");
+            let mut anim_data = AnimDataBone::with_options(parse_export_options(&args[4..]));
+            if let Err(why) = anim_data.load(&mut open_lab_input(&args[2])) {
+                panic!("Couldn't load {}", why.in_file(&args[2]));
             }
+            print_warnings(&anim_data);
 
+            match write_pose_json(&anim_data, frame) {
+                Ok(json_content) => println!("{}", json_content),
+                Err(why) => panic!("Couldn't get the pose of {}", why.in_file(&args[2])),
+            }
+        },
+        "labmerge" => {
+            merge_lab_to_dae(&args[2..]);
+        },
+        "labaudit" => {
+            audit_directory(Path::new(&args[2]), &args[3..]);
+        },
+        "labdiff" => {
+            if args.len() < 4 {
+                panic!("labdiff expects the two .lab files to compare");
+            }
+
+            let threshold = match args.get(4).map(String::as_str) {
+                Some("--threshold") => match args.get(5).and_then(|value| value.parse::<f32>().ok()) {
+                    Some(threshold) => Some(threshold),
+                    None => panic!("--threshold expects a number"),
+                },
+                Some(flag) => panic!("Unknown option {}", flag),
+                None => None,
+            };
+
+            let mut anim_data_a = AnimDataBone::new();
+            if let Err(why) = anim_data_a.load(&mut open_lab_input(&args[2])) {
+                panic!("Couldn't load {}", why.in_file(&args[2]));
+            }
+            print_warnings(&anim_data_a);
+            let mut anim_data_b = AnimDataBone::new();
+            if let Err(why) = anim_data_b.load(&mut open_lab_input(&args[3])) {
+                panic!("Couldn't load {}", why.in_file(&args[3]));
+            }
+            print_warnings(&anim_data_b);
+
+            let differences = diff(&anim_data_a, &anim_data_b, threshold);
+            if differences.is_empty() {
+                println!("No differences found");
+                return;
+            }
+
+            for difference in differences.iter() {
+                println!("{}", difference);
+            }
+            println!("{} differences found", differences.len());
+            process::exit(1);
+        },
+        "labrestdiff" => {
+            let mut anim_data = AnimDataBone::with_options(parse_export_options(&args[3..]));
+            if let Err(why) = anim_data.load(&mut open_lab_input(&args[2])) {
+                panic!("Couldn't load {}", why.in_file(&args[2]));
+            }
+            print_warnings(&anim_data);
+
+            let differences = diff_rest_pose(&anim_data);
+            if differences.is_empty() {
+                println!("The rest pose matches frame 0");
+                return;
+            }
+
+            for difference in differences.iter() {
+                println!("{}", difference);
+            }
+            println!("{} bones differ", differences.len());
+            process::exit(1);
+        },
+        "verify" => {
             let mut anim_data = AnimDataBone::new();
-            println!("Loading animation data...");
-            let xml_content = anim_data.load_from_file(&mut file);
-            let result_file_name = &format!("./{}.dae",file_stem);
-            let mut file = OpenOptions::new()
-                .write(true)
-                .truncate(true)
-                .create(true)
-                .open(result_file_name).unwrap();
-            println!("Writing data to a collada file...");
-
-            file.write_all("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n".as_bytes()).unwrap();
-            file.write_all(xml_content.as_bytes()).unwrap();
-            println!("Done!");
+            match anim_data.load(&mut open_lab_input(&args[2])) {
+                Ok(()) => {
+                    print_warnings(&anim_data);
+                    println!("{}: {}", args[2], anim_data.summary());
+                }
+                Err(why) => {
+                    println!("{}", why.in_file(&args[2]));
+                    process::exit(1);
+                }
+            }
+
+            let report = anim_data.validate();
+            if !report.is_valid() {
+                print!("{}", report);
+                process::exit(1);
+            }
         },
         "dae2lab" => {
             println!("This operation is currently not supported");